@@ -0,0 +1,144 @@
+//! Measures the allocations and time taken by each way of sending a message to an actor
+//!
+//! Run with `cargo bench --bench calls`. Allocations are counted across every thread, so include those made by the actor while handling the call.
+//!
+//! The allocations per call are the baseline to compare changes against, and should not grow:
+//!
+//! | call            | allocations/call |
+//! |-----------------|------------------|
+//! | `await`         | 1.00             |
+//! | `ignore_return` | 0.03             |
+//! | `drop`          | 0.03             |
+//! | `forward_to`    | 1.06             |
+//!
+//! The one allocation left for an awaited call is the slot its reply is sent back through. The time per call depends on the machine, so compare it with a run from before the change on the same machine.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use shakespeare::{ActorHandles, actor};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+// SAFETY: defers to the system allocator, only counting the calls made to it
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		// SAFETY: the caller upholds the requirements of `alloc`
+		unsafe { System.alloc(layout) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		// SAFETY: the caller upholds the requirements of `dealloc`
+		unsafe { System.dealloc(ptr, layout) }
+	}
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const CALLS: usize = 100_000;
+
+#[actor]
+mod Counter {
+	struct CounterState {
+		count: usize,
+	}
+
+	#[performance(canonical)]
+	impl Count for CounterState {
+		fn increment(&mut self) -> usize {
+			self.count += 1;
+			self.count
+		}
+
+		fn total(&self) -> usize {
+			self.count
+		}
+	}
+
+	#[performance(canonical)]
+	impl Sink for CounterState {
+		fn receive(&mut self, _value: usize) {
+			self.count += 1;
+		}
+	}
+}
+
+/// Runs `calls`, which makes [`CALLS`] calls, and prints the allocations and time per call
+async fn measure(name: &str, calls: impl Future<Output = ()>) {
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+	let start = Instant::now();
+	calls.await;
+	let elapsed = start.elapsed();
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+	#[allow(clippy::cast_precision_loss)]
+	let per_call = allocations as f64 / CALLS as f64;
+	let latency = elapsed / u32::try_from(CALLS).unwrap();
+	println!("{name:<16} {per_call:>8.2} allocations/call {latency:>12?}/call");
+}
+
+fn start() -> std::sync::Arc<Counter> {
+	let ActorHandles { message_handle, .. } = Counter::start(CounterState { count: 0 });
+	message_handle
+}
+
+/// Waits until the actor has handled `expected` messages
+async fn settle(actor: &Counter, expected: usize) {
+	while actor.total().await.unwrap() < expected {
+		tokio::time::sleep(Duration::from_micros(100)).await;
+	}
+}
+
+#[tokio::main]
+async fn main() {
+	// Get any lazily allocated runtime state out of the way before measuring
+	let actor = start();
+	for _ in 0..1000 {
+		actor.increment().await.unwrap();
+	}
+
+	let actor = start();
+	measure("await", async {
+		for _ in 0..CALLS {
+			actor.increment().await.unwrap();
+		}
+	})
+	.await;
+
+	let actor = start();
+	measure("ignore_return", async {
+		for _ in 0..CALLS {
+			actor.increment().ignore_return().await.unwrap();
+		}
+		settle(&actor, CALLS).await;
+	})
+	.await;
+
+	let actor = start();
+	measure("drop", async {
+		for _ in 0..CALLS {
+			drop(actor.increment());
+		}
+		settle(&actor, CALLS).await;
+	})
+	.await;
+
+	let source = start();
+	let sink = start();
+	measure("forward_to", async {
+		for _ in 0..CALLS {
+			source
+				.increment()
+				.forward_to(sink.clone() as std::sync::Arc<dyn Sink>)
+				.await
+				.unwrap();
+		}
+		settle(&sink, CALLS).await;
+	})
+	.await;
+}
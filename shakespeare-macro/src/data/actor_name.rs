@@ -1,16 +1,48 @@
-use quote::ToTokens;
-use syn::TypePath;
+use quote::{ToTokens, format_ident};
+use syn::{Generics, Ident, TypePath};
 
-pub(crate) struct ActorName(TypePath);
+use super::turbofish;
+
+/// The path to an actor's shell, along with the generic parameters it was declared with
+pub(crate) struct ActorName {
+	path:     TypePath,
+	generics: Generics,
+}
 
 impl ActorName {
-	pub(crate) fn new(p: TypePath) -> ActorName {
-		ActorName(p)
+	pub(crate) fn new(p: TypePath, generics: Generics) -> ActorName {
+		ActorName {
+			path: turbofish(p),
+			generics,
+		}
+	}
+
+	/// The generic parameters, and their bounds, that every item generated for the actor is declared with
+	pub(crate) fn generics(&self) -> &Generics {
+		&self.generics
+	}
+
+	/// The name the shell is declared with
+	pub(crate) fn ident(&self) -> &Ident {
+		&self.path.path.segments.last().unwrap().ident
+	}
+
+	/// The name the enum that wraps a message sent via any of the actor's roles is declared with
+	pub(crate) fn payload_ident(&self) -> Ident {
+		format_ident!("{}Payload", self.ident())
+	}
+
+	/// The type of the enum that wraps a message sent via any of the actor's roles
+	pub(crate) fn payload_name(&self) -> TypePath {
+		let mut path = self.path.clone();
+		let leaf = path.path.segments.last_mut().unwrap();
+		leaf.ident = self.payload_ident();
+		path
 	}
 }
 
 impl ToTokens for ActorName {
 	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-		self.0.to_tokens(tokens);
+		self.path.to_tokens(tokens);
 	}
 }
@@ -1,5 +1,5 @@
 use quote::ToTokens;
-use syn::{Item, ItemEnum, ItemStruct, ItemUnion, Path, PathSegment};
+use syn::{Generics, Ident, Item, ItemEnum, ItemStruct, ItemUnion, Path, PathSegment, parse_quote};
 
 use super::DataName;
 
@@ -12,20 +12,41 @@ pub(crate) enum DataItem {
 
 impl DataItem {
 	pub(crate) fn name(&self) -> DataName {
+		let ident = self.ident();
+		let generics = self.generics();
+		let mut seg = PathSegment::from(ident.clone());
+		if !generics.params.is_empty() {
+			let (_, ty_generics, _) = generics.split_for_impl();
+			seg.arguments = syn::PathArguments::AngleBracketed(parse_quote! { #ty_generics });
+		}
+		let segments = std::iter::once(seg).collect();
+		DataName::new(
+			syn::TypePath {
+				qself: None,
+				path:  Path {
+					segments,
+					leading_colon: None,
+				},
+			},
+			generics.clone(),
+		)
+	}
+
+	/// The name the item is declared with
+	pub(crate) fn ident(&self) -> &Ident {
 		match self {
 			DataItem::Struct(ItemStruct { ident, .. })
 			| DataItem::Enum(ItemEnum { ident, .. })
-			| DataItem::Union(ItemUnion { ident, .. }) => {
-				let seg = PathSegment::from(ident.clone());
-				let segments = std::iter::once(seg).collect();
-				DataName::new(syn::TypePath {
-					qself: None,
-					path:  Path {
-						segments,
-						leading_colon: None,
-					},
-				})
-			}
+			| DataItem::Union(ItemUnion { ident, .. }) => ident,
+		}
+	}
+
+	/// The generic parameters the item is declared with, which the actor's shell is given too
+	pub(crate) fn generics(&self) -> &Generics {
+		match self {
+			DataItem::Struct(ItemStruct { generics, .. })
+			| DataItem::Enum(ItemEnum { generics, .. })
+			| DataItem::Union(ItemUnion { generics, .. }) => generics,
 		}
 	}
 }
@@ -1,27 +1,41 @@
 use quote::ToTokens;
-use syn::{TypePath, parse_quote};
+use syn::{Generics, TypePath, parse_quote};
 
-use super::ActorName;
+use super::{ActorName, turbofish};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub(crate) struct DataName(TypePath);
+pub(crate) struct DataName {
+	path:     TypePath,
+	generics: Generics,
+}
 
 impl DataName {
-	pub(crate) fn new(p: TypePath) -> Self {
+	pub(crate) fn new(p: TypePath, generics: Generics) -> Self {
 		debug_assert!(!p.path.segments.is_empty());
-		Self(p)
+		Self {
+			path: turbofish(p),
+			generics,
+		}
+	}
+
+	/// The generic parameters, and their bounds, that the state type was declared with
+	pub(crate) fn generics(&self) -> &Generics {
+		&self.generics
 	}
 
 	pub(crate) fn get_shell_type_path(&self) -> ActorName {
-		let path = &self.0;
-		ActorName::new(parse_quote! {
-			<#path as ::shakespeare::ActorState>::ShellType
-		})
+		let path = &self.path;
+		ActorName::new(
+			parse_quote! {
+				<#path as ::shakespeare::ActorState>::ShellType
+			},
+			self.generics.clone(),
+		)
 	}
 }
 
 impl ToTokens for DataName {
 	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-		self.0.to_tokens(tokens);
+		self.path.to_tokens(tokens);
 	}
 }
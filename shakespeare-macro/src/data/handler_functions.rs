@@ -1,5 +1,5 @@
 use quote::{ToTokens, quote};
-use syn::{Ident, ItemFn, ReturnType};
+use syn::{Error, FnArg, Ident, ItemFn, Result, ReturnType, Type};
 
 use super::DataName;
 
@@ -30,15 +30,27 @@ impl HandlerFunctions {
 		self.state_name = Some(name);
 	}
 
-	pub(crate) fn add(&mut self, fun: &ItemFn) -> bool {
-		let storage = match &fun.sig.ident.to_string()[..] {
-			"stop" => &mut self.exit,
-			"catch" => &mut self.panic,
-			_ => return false,
+	pub(crate) fn add(&mut self, fun: &ItemFn) -> Result<bool> {
+		let (storage, arity) = match &fun.sig.ident.to_string()[..] {
+			"stop" => (&mut self.exit, 1),
+			"catch" => (&mut self.panic, 2),
+			_ => return Ok(false),
 		};
 
+		if let Some(extra) = fun.sig.inputs.iter().nth(arity)
+			&& !is_unhandled(extra)
+		{
+			return Err(Error::new_spanned(
+				extra,
+				"Only the messages left unhandled, as `Unhandled<...>`, can be taken here",
+			));
+		}
+		if let Some(extra) = fun.sig.inputs.iter().nth(arity + 1) {
+			return Err(Error::new_spanned(extra, "Unexpected argument"));
+		}
+
 		*storage = Some(fun.clone());
-		true
+		Ok(true)
 	}
 
 	pub(crate) fn exit_name(&self) -> Option<&Ident> {
@@ -49,6 +61,20 @@ impl HandlerFunctions {
 		self.panic.as_ref().map(|i| &i.sig.ident)
 	}
 
+	/// Whether the exit handler takes the messages left unhandled, as an argument after `self`
+	pub(crate) fn exit_takes_unhandled(&self) -> bool {
+		self.exit
+			.as_ref()
+			.is_some_and(|fun| fun.sig.inputs.iter().nth(1).is_some_and(is_unhandled))
+	}
+
+	/// Whether the panic handler takes the messages left unhandled, as an argument after `self` and the panic
+	pub(crate) fn panic_takes_unhandled(&self) -> bool {
+		self.panic
+			.as_ref()
+			.is_some_and(|fun| fun.sig.inputs.iter().nth(2).is_some_and(is_unhandled))
+	}
+
 	pub(crate) fn panic_return(&self) -> FuncReturnType<'_> {
 		FuncReturnType(self.panic.as_ref(), HandlerFunctionType::Panic)
 	}
@@ -68,8 +94,9 @@ impl ToTokens for HandlerFunctions {
 		else {
 			panic!("Actor is missing internal state type")
 		};
+		let (impl_generics, _, where_clause) = state_name.generics().split_for_impl();
 		quote! {
-			impl #state_name {
+			impl #impl_generics #state_name #where_clause {
 				#panic
 				#exit
 			}
@@ -78,6 +105,22 @@ impl ToTokens for HandlerFunctions {
 	}
 }
 
+/// Whether the argument is typed as `Unhandled<...>`, however the path to it is written
+fn is_unhandled(arg: &FnArg) -> bool {
+	let FnArg::Typed(arg) = arg else {
+		return false;
+	};
+	let Type::Path(ty) = &*arg.ty else {
+		return false;
+	};
+	ty.qself.is_none()
+		&& ty
+			.path
+			.segments
+			.last()
+			.is_some_and(|segment| segment.ident == "Unhandled")
+}
+
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct FuncReturnType<'a>(Option<&'a ItemFn>, HandlerFunctionType);
 impl ToTokens for FuncReturnType<'_> {
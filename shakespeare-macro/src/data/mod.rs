@@ -17,3 +17,13 @@ pub(crate) type PayloadPath = syn::Path;
 
 pub(crate) use handler_functions::{FuncReturnType, HandlerFunctions};
 pub(crate) use signature_ext::SignatureExt;
+
+/// Writes any generic arguments in a path with `::<>`, so that it can be used in expressions and patterns as well as types
+pub(crate) fn turbofish(mut p: syn::TypePath) -> syn::TypePath {
+	for seg in &mut p.path.segments {
+		if let syn::PathArguments::AngleBracketed(args) = &mut seg.arguments {
+			args.colon2_token.get_or_insert_default();
+		}
+	}
+	p
+}
@@ -1,41 +1,199 @@
 use convert_case::{Case, Casing};
+use itertools::Itertools;
 use proc_macro2::Ident;
-use quote::{ToTokens, format_ident};
-use syn::{Path, PathSegment, TypePath};
+use quote::{ToTokens, format_ident, quote};
+use syn::{GenericArgument, Path, PathArguments, PathSegment, Token, Type, TypePath, parse_quote};
 
 use super::MethodName;
 use crate::macros::fallible_quote;
 
+/// A role, which is written as its type: `dyn` the role's trait, or an actor's shell for its `#[message]` methods
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub(crate) struct RoleName(Path);
+pub(crate) struct RoleName {
+	path:  Path,
+	/// Whether `path` is the actor's shell, rather than a trait
+	shell: bool,
+}
 
 impl RoleName {
 	pub(crate) fn new(p: Path) -> RoleName {
 		debug_assert!(!p.segments.is_empty());
-		RoleName(p)
+		RoleName {
+			path:  p,
+			shell: false,
+		}
+	}
+
+	/// The role of an actor's `#[message]` methods, which is the shell itself, so that they need no trait
+	pub(crate) fn shell(p: Path) -> RoleName {
+		debug_assert!(!p.segments.is_empty());
+		RoleName {
+			path:  p,
+			shell: true,
+		}
+	}
+
+	/// Whether the role is an actor's shell, rather than a trait
+	pub(crate) fn is_shell(&self) -> bool {
+		self.shell
 	}
 
+	/// The name the generated items for the role are based on, in `CamelCase`
 	pub(crate) fn path_leaf(&self) -> String {
-		self.0.segments.last().as_ref().unwrap().ident.to_string()
+		let (readable, hash) = self.leaf_parts();
+		readable + &hash.unwrap_or_default()
+	}
+
+	/// The name the generated items for the role are based on, in `snake_case`
+	pub(crate) fn snake_leaf(&self) -> String {
+		let (readable, hash) = self.leaf_parts();
+		let readable = readable.to_case(Case::Snake);
+		match hash {
+			Some(hash) => format!("{readable}_{hash}"),
+			None => readable,
+		}
+	}
+
+	/// The role's name followed by those of its type arguments, which is enough to read, and a hash of all of its arguments, which keeps apart roles whose arguments only differ in ways the names don't show, such as `Handles<a::Ping>` and `Handles<b::Ping>`
+	fn leaf_parts(&self) -> (String, Option<String>) {
+		let leaf = self.path.segments.last().unwrap();
+		let mut name = leaf.ident.to_string();
+		if self.shell {
+			// The shell's parameters are those of the actor, which only performs its messages once
+			name.push_str("Message");
+			return (name, None);
+		}
+		let args = self.enum_arguments();
+		if args.is_empty() {
+			return (name, None);
+		}
+		for arg in &args {
+			if let GenericArgument::Type(Type::Path(ty)) = arg
+				&& let Some(segment) = ty.path.segments.last()
+			{
+				name.push_str(&segment.ident.to_string());
+			}
+		}
+		let hash = stable_hash(&quote!(#(#args),*).to_string());
+		(name, Some(format!("{hash:016x}")))
+	}
+
+	/// The arguments the role is given, with those of its parameters in order, followed by its associated types ordered by name, as in the parameters of its payload enums
+	fn enum_arguments(&self) -> Vec<GenericArgument> {
+		let PathArguments::AngleBracketed(args) = &self.path.segments.last().unwrap().arguments
+		else {
+			return vec![];
+		};
+		let params = args
+			.args
+			.iter()
+			.filter(|arg| matches!(arg, GenericArgument::Type(_) | GenericArgument::Const(_)));
+		let assoc = self
+			.assoc_bindings()
+			.into_iter()
+			.map(|(_, ty)| GenericArgument::Type(ty.clone()));
+		params.cloned().chain(assoc).collect()
+	}
+
+	/// Whether the role is given any type arguments or associated types
+	pub(crate) fn is_generic(&self) -> bool {
+		!self.path.segments.last().unwrap().arguments.is_none()
+	}
+
+	/// The associated types that the role is given, as in `Sink<Item = u32>`, ordered by name
+	pub(crate) fn assoc_bindings(&self) -> Vec<(&Ident, &Type)> {
+		let PathArguments::AngleBracketed(args) = &self.path.segments.last().unwrap().arguments
+		else {
+			return vec![];
+		};
+		args.args
+			.iter()
+			.filter_map(|arg| match arg {
+				GenericArgument::AssocType(binding) => Some((&binding.ident, &binding.ty)),
+				_ => None,
+			})
+			.sorted_by_key(|(ident, _)| *ident)
+			.collect()
+	}
+
+	/// The path to the role's trait, without its associated types, to implement it with
+	pub(crate) fn trait_path(&self) -> Path {
+		let mut path = self.path.clone();
+		let leaf = path.segments.last_mut().unwrap();
+		if let PathArguments::AngleBracketed(args) = &mut leaf.arguments {
+			args.args = std::mem::take(&mut args.args)
+				.into_iter()
+				.filter(|arg| !matches!(arg, GenericArgument::AssocType(_)))
+				.collect();
+			if args.args.is_empty() {
+				leaf.arguments = PathArguments::None;
+			}
+		}
+		path
 	}
 
 	pub(crate) fn queue_name(&self) -> Ident {
-		format_ident!("{}", self.path_leaf().to_case(Case::Snake))
+		format_ident!("{}", self.snake_leaf())
 	}
 
 	pub(crate) fn method_name(&self) -> MethodName {
-		let role_name = self.path_leaf();
-		format_ident!("{}", format!("perform_{role_name}").to_case(Case::Snake))
+		format_ident!("perform_{}", self.snake_leaf())
+	}
+
+	pub(crate) fn shared_method_name(&self) -> MethodName {
+		format_ident!("{}_shared", self.method_name())
+	}
+
+	pub(crate) fn shared_check_name(&self) -> MethodName {
+		format_ident!("{}_is_shared", self.method_name())
+	}
+
+	pub(crate) fn batch_method_name(&self) -> MethodName {
+		format_ident!("{}_batch", self.method_name())
+	}
+
+	pub(crate) fn batch_limit_name(&self) -> MethodName {
+		format_ident!("{}_batch_limit", self.method_name())
+	}
+
+	pub(crate) fn batch_variant_name(&self) -> Ident {
+		format_ident!("{}Batch", self.path_leaf())
+	}
+
+	pub(crate) fn variant_name(&self) -> Ident {
+		format_ident!("{}", self.path_leaf())
 	}
 
 	pub(crate) fn payload_path(&self) -> syn::Path {
-		map_path_leaf(self.0.clone(), |data_name| {
-			format_ident!("{}Payload", data_name)
-		})
+		self.enum_path(|data_name| format_ident!("{}Payload", data_name))
 	}
 
 	pub(crate) fn return_payload_path(&self) -> syn::Path {
-		map_path_leaf(self.0.clone(), |p| format_ident!("{}ReturnPayload", p))
+		self.enum_path(|p| format_ident!("{}ReturnPayload", p))
+	}
+
+	/// The path to one of the role's enums, which are given the role's types in the order of [`Self::enum_arguments`], and can be used in expressions as well as types
+	fn enum_path<F>(&self, f: F) -> syn::Path
+	where
+		F: Fn(Ident) -> Ident,
+	{
+		let args = self.enum_arguments();
+		// The shell's enums are kept apart from the actor's own payload enum
+		let shell = self.shell;
+		let mut path = map_path_leaf(self.path.clone(), |ident| {
+			if shell {
+				f(format_ident!("{ident}Message"))
+			} else {
+				f(ident)
+			}
+		});
+		let leaf = path.segments.last_mut().unwrap();
+		leaf.arguments = if args.is_empty() {
+			PathArguments::None
+		} else {
+			PathArguments::AngleBracketed(parse_quote! { ::<#(#args),*> })
+		};
+		path
 	}
 
 	pub(crate) fn sender_method_name(&self) -> Ident {
@@ -43,18 +201,36 @@ impl RoleName {
 		format_ident!("push_to_{field_name}")
 	}
 
+	pub(crate) fn try_sender_method_name(&self) -> Ident {
+		let field_name = self.queue_name();
+		format_ident!("try_push_to_{field_name}")
+	}
+
 	pub(crate) fn sender_type_name(&self) -> TypePath {
-		let path = &self.0;
-		fallible_quote! { <<dyn #path as ::shakespeare::Role>::Channel as ::shakespeare::Channel>::Sender }.unwrap()
+		if self.shell {
+			// The shell holds this queue, so its type can't be worked out from the shell's `Role` impl, which needs the shell to be `Send`
+			return fallible_quote! { ::shakespeare::tokio_export::sync::mpsc::UnboundedSender<::shakespeare::ReturnEnvelope<#self>> }.unwrap();
+		}
+		fallible_quote! { <<#self as ::shakespeare::Role>::Channel as ::shakespeare::Channel>::Sender }.unwrap()
 	}
 }
 
 impl ToTokens for RoleName {
 	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-		self.0.to_tokens(tokens);
+		if !self.shell {
+			<Token![dyn]>::default().to_tokens(tokens);
+		}
+		self.path.to_tokens(tokens);
 	}
 }
 
+/// FNV-1a, which unlike the standard library's hasher is the same with every compiler
+fn stable_hash(text: &str) -> u64 {
+	text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+		(hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+	})
+}
+
 fn map_path_leaf<F>(mut p: Path, f: F) -> Path
 where
 	F: Fn(Ident) -> Ident,
@@ -7,6 +7,7 @@ use crate::macros::{fallible_quote, filter_unwrap};
 
 pub(crate) trait SignatureExt {
 	fn has_context_input(&self) -> bool;
+	fn has_shared_receiver(&self) -> bool;
 	fn remove_context_param(&mut self);
 	fn extract_return_type(&self) -> Type;
 	fn enum_variant_name(&self) -> Ident;
@@ -29,6 +30,24 @@ impl SignatureExt for Signature {
 		}
 	}
 
+	fn has_shared_receiver(&self) -> bool {
+		let Some(FnArg::Receiver(receiver)) = self.inputs.first() else {
+			return false;
+		};
+		if receiver.reference.is_none() || receiver.mutability.is_some() {
+			return false;
+		}
+		if !self.has_context_input() {
+			return true;
+		}
+		match self.inputs.iter().nth(1) {
+			Some(FnArg::Typed(PatType { ty, .. })) => {
+				matches!(&**ty, Type::Reference(r) if r.mutability.is_none())
+			}
+			_ => false,
+		}
+	}
+
 	fn remove_context_param(&mut self) {
 		if self.has_context_input() {
 			let mut items = std::mem::take(&mut self.inputs).into_iter().collect_vec();
@@ -1,7 +1,10 @@
 use itertools::Itertools;
+use proc_macro2::Span;
+use structmeta::{Flag, NameArgs, StructMeta};
 use syn::spanned::Spanned;
 use syn::{
-	Attribute, Error, ImplItem, Item, ItemImpl, ItemMod, Path, Result, TypePath, Visibility,
+	Attribute, Error, Ident, ImplItem, Item, ItemImpl, ItemMod, Meta, Path, PathArguments, Result,
+	Type, TypePath, Visibility, parse_quote,
 };
 
 use crate::data::{ActorName, DataItem, HandlerFunctions};
@@ -9,8 +12,18 @@ use crate::declarations::performance::PerformanceAttribute;
 use crate::macros::filter_unwrap;
 use crate::{PerformanceDecl, RoleDecl};
 
+#[derive(StructMeta)]
+pub(crate) struct ActorAttribute {
+	pub(crate) concurrent_reads: Flag,
+	pub(crate) single_mailbox:   Flag,
+	pub(crate) inspect:          Flag,
+	/// Roles whose performance is written for any state type meeting some bounds, rather than in the actor
+	pub(crate) performs:         Option<NameArgs<Vec<Path>>>,
+}
+
 pub(crate) struct ActorDecl {
 	pub(crate) actor_name:   ActorName,
+	pub(crate) options:      ActorAttribute,
 	pub(crate) attributes:   Vec<Attribute>,
 	pub(crate) actor_vis:    Visibility,
 	pub(crate) data_item:    DataItem,
@@ -21,7 +34,7 @@ pub(crate) struct ActorDecl {
 }
 
 impl ActorDecl {
-	pub(crate) fn new(module: ItemMod) -> Result<ActorDecl> {
+	pub(crate) fn new(options: ActorAttribute, module: ItemMod) -> Result<ActorDecl> {
 		let module_span = module.span();
 		let ItemMod {
 			attrs,
@@ -35,6 +48,10 @@ impl ActorDecl {
 		let mut roles = vec![];
 		let mut data_items = vec![];
 		let mut misc = vec![];
+		// Methods marked `#[message]` in inherent impls
+		let mut messages = vec![];
+		// The types of the inherent impls that `#[message]` or `#[handler]` methods were taken from
+		let mut marked_types = vec![];
 
 		let mut handlers = HandlerFunctions::new();
 
@@ -46,15 +63,28 @@ impl ActorDecl {
 			match &item {
 				Item::Impl(imp) => {
 					if let Some((perf, role)) = read_performance(imp)? {
-						performances.push(perf);
+						add_performance(&mut performances, perf)?;
 						if let Some(role) = role {
 							roles.push(role);
 						}
 						continue;
 					}
+					if imp.trait_.is_none() {
+						let mut imp = imp.clone();
+						let before = imp.items.len();
+						messages.extend(take_marked(&mut imp, "message"));
+						for handler in take_marked(&mut imp, "handler") {
+							add_handler(&mut performances, handler)?;
+						}
+						if imp.items.len() < before {
+							marked_types.push(imp.self_ty.clone());
+							misc.push(Item::Impl(imp));
+							continue;
+						}
+					}
 				}
 				Item::Fn(item) => {
-					if handlers.add(item) {
+					if handlers.add(item)? {
 						continue;
 					}
 				}
@@ -69,38 +99,30 @@ impl ActorDecl {
 			misc.push(item);
 		}
 
-		let data_item = match data_items.into_iter().at_most_one() {
-			Ok(Some(item)) => item,
-			Ok(None) => {
-				return Err(Error::new(
-					module_span,
-					"Actor declaration must contain one struct, enum or union",
-				));
-			}
-			Err(extras) => {
-				let errors = extras.map(|d| {
-					Error::new_spanned(d, "Only one data item allowed in actor declaration")
-				});
-				return Err(errors.reduce(combine_errors).unwrap());
-			}
-		};
+		let data_item = single_data_item(data_items, module_span)?;
 
 		handlers.set_data_name(data_item.name());
 
-		let actor_path = TypePath {
-			qself: None,
-			path:  Path::from(ident),
-		};
-		let actor_name = ActorName::new(actor_path);
+		for ty in &marked_types {
+			check_state_type(ty, &data_item)?;
+		}
+
+		let actor_name = shell_name(ident, &data_item)?;
+
+		if !messages.is_empty() {
+			let (perf, role) = read_messages(&actor_name, messages)?;
+			add_performance(&mut performances, perf)?;
+			roles.push(role);
+		}
 
-		if performances.is_empty() {
-			return Err(Error::new(
-				module_span,
-				"Actor must have at least one performance, even if it's externally defined",
-			));
+		for role_name in options.performs.iter().flat_map(|performs| &performs.args) {
+			add_performance(
+				&mut performances,
+				PerformanceDecl::blanket(role_name.clone()),
+			)?;
 		}
-		// [SpawningFunction] might fall over otherwise
-		// And also doesn't make much sense
+
+		check_performances(&options, &performances, module_span)?;
 
 		let attributes = attrs
 			.iter()
@@ -110,6 +132,7 @@ impl ActorDecl {
 
 		Ok(ActorDecl {
 			actor_name,
+			options,
 			attributes,
 			actor_vis,
 			data_item,
@@ -136,15 +159,21 @@ fn read_performance(imp: &ItemImpl) -> Result<Option<(PerformanceDecl, Option<Ro
 	};
 
 	let (_, role_name, _) = &imp.trait_.as_ref().unwrap();
-	let perf = PerformanceDecl::new(role_name.clone(), imp.clone())?;
 
-	let args: Option<PerformanceAttribute> = attr.parse_args().ok();
-	let canonical = args.is_some_and(|args| args.canonical.value());
+	let args: Option<PerformanceAttribute> = match attr.meta {
+		Meta::List(_) => Some(attr.parse_args()?),
+		_ => None,
+	};
+	let canonical = args.as_ref().is_some_and(|args| args.canonical.value());
+	let (when, priority) = args.map_or((None, None), |args| {
+		(args.when.map(|phase| phase.0), args.priority)
+	});
+
+	let perf = PerformanceDecl::new(role_name.clone(), imp.clone(), when, priority)?;
 
 	if canonical {
-		let signatures = filter_unwrap!(&imp.items, ImplItem::Fn)
-			.map(|f| &f.sig)
-			.cloned();
+		perf.check_canonical()?;
+		let signatures = perf.methods().into_iter().map(|f| f.sig);
 
 		let attributes = imp
 			.attrs
@@ -158,13 +187,177 @@ fn read_performance(imp: &ItemImpl) -> Result<Option<(PerformanceDecl, Option<Ro
 			attributes,
 			Visibility::Public(syn::token::Pub::default()),
 			signatures,
+			perf.conflating.clone(),
 		);
 		Ok(Some((perf, Some(role))))
+	} else if let Some(method) = perf.conflating.first() {
+		Err(Error::new_spanned(
+			method,
+			"`#[conflate]` belongs on the method in the role, or a canonical performance",
+		))
 	} else {
 		Ok(Some((perf, None)))
 	}
 }
 
+/// Checks that the actor performs something, and that `single_mailbox` isn't given any priorities it would ignore
+fn check_performances(
+	options: &ActorAttribute,
+	performances: &[PerformanceDecl],
+	module_span: Span,
+) -> Result<()> {
+	// An actor without a role could never be sent anything, and its event loop would have no queues to wait on
+	if performances.is_empty() {
+		return Err(Error::new(
+			module_span,
+			"Actor must have at least one performance, even if it's externally defined",
+		));
+	}
+
+	if options.single_mailbox.value()
+		&& let Some(priority) = performances.iter().find_map(|p| p.priority.as_ref())
+	{
+		return Err(Error::new_spanned(
+			priority,
+			"Roles cannot have priorities in an actor with `single_mailbox`, as they share one queue",
+		));
+	}
+	Ok(())
+}
+
+/// The shell is named after the module, and has the same generic parameters as the state
+fn shell_name(ident: Ident, data_item: &DataItem) -> Result<ActorName> {
+	let generics = data_item.generics().clone();
+	if let Some(lifetime) = generics.lifetimes().next() {
+		return Err(Error::new_spanned(
+			lifetime,
+			"An actor's state cannot borrow, as it is moved into a task that may outlive anything it borrows",
+		));
+	}
+	let mut actor_path = TypePath {
+		qself: None,
+		path:  Path::from(ident),
+	};
+	if !generics.params.is_empty() {
+		let (_, ty_generics, _) = generics.split_for_impl();
+		actor_path.path.segments[0].arguments =
+			PathArguments::AngleBracketed(parse_quote! { #ty_generics });
+	}
+	Ok(ActorName::new(actor_path, generics))
+}
+
+fn single_data_item(data_items: Vec<DataItem>, module_span: Span) -> Result<DataItem> {
+	match data_items.into_iter().at_most_one() {
+		Ok(Some(item)) => Ok(item),
+		Ok(None) => Err(Error::new(
+			module_span,
+			"Actor declaration must contain one struct, enum or union",
+		)),
+		Err(extras) => {
+			let errors = extras
+				.map(|d| Error::new_spanned(d, "Only one data item allowed in actor declaration"));
+			Err(errors.reduce(combine_errors).unwrap())
+		}
+	}
+}
+
+/// The `#[message]` methods are performed for a role that is the shell itself, so no trait is declared for them
+fn read_messages(
+	actor_name: &ActorName,
+	methods: Vec<ImplItem>,
+) -> Result<(PerformanceDecl, RoleDecl)> {
+	let methods = filter_unwrap!(methods, ImplItem::Fn).collect_vec();
+	if let Some(duplicate) = methods
+		.iter()
+		.duplicates_by(|method| &method.sig.ident)
+		.next()
+	{
+		return Err(Error::new_spanned(
+			&duplicate.sig.ident,
+			"Only one `#[message]` method can have each name",
+		));
+	}
+
+	let perf = PerformanceDecl::messages(parse_quote!(#actor_name), methods)?;
+	let mut role = RoleDecl::new(
+		Path::from(actor_name.ident().clone()),
+		vec![],
+		Visibility::Inherited,
+		perf.methods().into_iter().map(|f| f.sig),
+		perf.conflating.clone(),
+	);
+	role.generics = actor_name.generics().clone();
+	role.shell = true;
+	Ok((perf, role))
+}
+
+/// `#[message]` and `#[handler]` methods are handled with the actor's state, so they can't be on any other type
+fn check_state_type(ty: &Type, data_item: &DataItem) -> Result<()> {
+	let state = data_item.ident();
+	match ty {
+		Type::Path(TypePath { qself: None, path })
+			if path.segments.len() == 1 && path.segments[0].ident == *state =>
+		{
+			Ok(())
+		}
+		_ => Err(Error::new_spanned(
+			ty,
+			format!(
+				"`#[message]` and `#[handler]` methods must be in an impl of the actor's state, `{state}`"
+			),
+		)),
+	}
+}
+
+/// Takes the methods marked with `marker`, e.g. `#[message]`, out of an inherent impl, without the marker
+fn take_marked(imp: &mut ItemImpl, marker: &str) -> Vec<ImplItem> {
+	let (taken, rest) = std::mem::take(&mut imp.items)
+		.into_iter()
+		.partition(|item| match item {
+			ImplItem::Fn(fun) => fun.attrs.iter().any(|a| a.path().is_ident(marker)),
+			_ => false,
+		});
+	imp.items = rest;
+	taken
+		.into_iter()
+		.map(|mut item| {
+			if let ImplItem::Fn(fun) = &mut item {
+				fun.attrs.retain(|a| !a.path().is_ident(marker));
+			}
+			item
+		})
+		.collect()
+}
+
+/// Each `#[handler]` is the only performance of its `Handles<M>` role
+fn add_handler(performances: &mut Vec<PerformanceDecl>, handler: ImplItem) -> Result<()> {
+	let ImplItem::Fn(handler) = handler else {
+		unreachable!("Only methods are taken as handlers")
+	};
+	let perf = PerformanceDecl::handler(handler)?;
+	if performances.iter().any(|p| p.role_name == perf.role_name) {
+		return Err(Error::new_spanned(
+			&perf.handlers[0].sig,
+			"Only one `#[handler]` can handle each message type",
+		));
+	}
+	performances.push(perf);
+	Ok(())
+}
+
+/// Performances of the same role are combined, so that each role has one set of phases
+fn add_performance(performances: &mut Vec<PerformanceDecl>, perf: PerformanceDecl) -> Result<()> {
+	if let Some(existing) = performances
+		.iter_mut()
+		.find(|p| p.role_name == perf.role_name)
+	{
+		existing.merge(perf)
+	} else {
+		performances.push(perf);
+		Ok(())
+	}
+}
+
 #[allow(clippy::trivially_copy_pass_by_ref)]
 fn is_not_internal_attribute(a: &&Attribute) -> bool {
 	let Some(last) = a.path().segments.last() else {
@@ -2,6 +2,6 @@ mod actor;
 mod performance;
 mod role;
 
-pub(crate) use actor::ActorDecl;
-pub(crate) use performance::PerformanceDecl;
-pub(crate) use role::RoleDecl;
+pub(crate) use actor::{ActorAttribute, ActorDecl};
+pub(crate) use performance::{PerformanceAttribute, PerformanceDecl, vec_item_type};
+pub(crate) use role::{RoleDecl, take_conflate_attribute};
@@ -1,30 +1,115 @@
 use itertools::Itertools;
+use quote::quote;
 use structmeta::{Flag, StructMeta};
-use syn::{Error, ImplItem, ItemImpl, Path, Result};
+use syn::fold::Fold;
+use syn::parse::{Parse, ParseStream};
+use syn::{
+	Attribute, Error, FnArg, GenericArgument, Ident, ImplItem, ItemImpl, LitInt, Pat, Path,
+	PathArguments, Result, ReturnType, Type, parse_quote,
+};
 
-use crate::data::{FunctionItem, RoleName};
+use crate::data::{FunctionItem, RoleName, SignatureExt};
+use crate::declarations::take_conflate_attribute;
+use crate::interfacerewriter::AssocTypeReplacer;
 use crate::macros::filter_unwrap;
 
 #[derive(StructMeta)]
 pub(crate) struct PerformanceAttribute {
 	pub(crate) canonical: Flag,
+	pub(crate) when:      Option<PhasePattern>,
+	pub(crate) priority:  Option<LitInt>,
+}
+
+/// The pattern in `#[performance(when = ...)]`, which is matched against the actor's state
+pub(crate) struct PhasePattern(pub(crate) Pat);
+
+impl Parse for PhasePattern {
+	fn parse(input: ParseStream) -> Result<Self> {
+		Pat::parse_multi_with_leading_vert(input).map(PhasePattern)
+	}
+}
+
+/// The arguments of `#[batch(limit = ...)]` on a performance method
+#[derive(StructMeta)]
+pub(crate) struct BatchAttribute {
+	pub(crate) limit: LitInt,
+}
+
+/// A method whose handler is given every consecutive queued call at once
+pub(crate) struct BatchDecl {
+	pub(crate) method: Ident,
+	pub(crate) limit:  LitInt,
+}
+
+/// A performance that only handles messages while the state matches `pattern`
+pub(crate) struct PhaseDecl {
+	pub(crate) pattern:  Pat,
+	pub(crate) handlers: Vec<FunctionItem>,
 }
 
 pub(crate) struct PerformanceDecl {
-	pub(crate) role_name: RoleName,
-	pub(crate) handlers:  Vec<FunctionItem>,
+	pub(crate) role_name:  RoleName,
+	/// The handlers used when none of the phases apply
+	pub(crate) handlers:   Vec<FunctionItem>,
+	pub(crate) phases:     Vec<PhaseDecl>,
+	pub(crate) batches:    Vec<BatchDecl>,
+	/// Methods marked `#[conflate]`, which only a canonical performance can do
+	pub(crate) conflating: Vec<Ident>,
+	/// Methods marked `#[stashable]`, whose handlers can stash the message they are handling
+	pub(crate) stashable:  Vec<Ident>,
+	/// How early the role's queue is checked for messages, relative to the actor's other roles
+	pub(crate) priority:   Option<LitInt>,
+	/// The message type, for a `#[handler]`, whose role is `Handles<message>` and whose payload is the message itself
+	pub(crate) message:    Option<Type>,
+	/// Whether the role is named in `#[actor(performs(...))]`, so the state's blanket performance of it handles its messages
+	pub(crate) blanket:    bool,
 }
 
 impl PerformanceDecl {
-	pub(crate) fn new(role_name: Path, imp: ItemImpl) -> Result<PerformanceDecl> {
+	pub(crate) fn new(
+		role_name: Path,
+		imp: ItemImpl,
+		when: Option<Pat>,
+		priority: Option<LitInt>,
+	) -> Result<PerformanceDecl> {
 		assert!(!role_name.segments.is_empty());
 
-		let handlers = filter_unwrap!(imp.items, ImplItem::Fn).collect_vec();
-		for handler in &handlers {
+		if let Some(priority) = &priority {
+			priority.base10_parse::<i32>()?;
+		}
+
+		// The handlers end up outside of the trait impl, where `Self::Item` means nothing
+		let assoc_types = filter_unwrap!(&imp.items, ImplItem::Type)
+			.map(|ty| (ty.ident.clone(), ty.ty.clone()))
+			.collect_vec();
+		let mut replacer = AssocTypeReplacer::new(&assoc_types);
+		let handlers = filter_unwrap!(imp.items, ImplItem::Fn)
+			.map(|handler| replacer.fold_impl_item_fn(handler))
+			.collect_vec();
+		let role_name = RoleName::new(with_assoc_types(role_name, &assoc_types));
+
+		PerformanceDecl::with_handlers(role_name, handlers, when, priority)
+	}
+
+	/// The `#[message]` methods of an actor, which are performed for the role that is its shell
+	pub(crate) fn messages(shell: Path, methods: Vec<FunctionItem>) -> Result<PerformanceDecl> {
+		PerformanceDecl::with_handlers(RoleName::shell(shell), methods, None, None)
+	}
+
+	fn with_handlers(
+		role_name: RoleName,
+		mut handlers: Vec<FunctionItem>,
+		when: Option<Pat>,
+		priority: Option<LitInt>,
+	) -> Result<PerformanceDecl> {
+		let mut batches = vec![];
+		let mut conflating = vec![];
+		let mut stashable = vec![];
+		for handler in &mut handlers {
 			if handler.sig.generics.type_params().next().is_some() {
 				Err(Error::new_spanned(
 					&handler.sig,
-					"Generic performances are not supported",
+					"Performance methods cannot have type parameters, as role methods cannot",
 				))?;
 			}
 			if !matches!(handler.sig.inputs.first(), Some(syn::FnArg::Receiver(_))) {
@@ -33,17 +118,347 @@ impl PerformanceDecl {
 					"Performance method must have self-receiver",
 				))?;
 			}
+			if let Some(batch) = read_batch(handler)? {
+				batches.push(batch);
+			}
+			if take_conflate_attribute(&mut handler.attrs) {
+				conflating.push(handler.sig.ident.clone());
+			}
+			if let Some(attr) = take_stashable_attribute(&mut handler.attrs) {
+				if batches.iter().any(|b| b.method == handler.sig.ident) {
+					Err(Error::new_spanned(
+						attr,
+						"A batched method cannot be `#[stashable]`",
+					))?;
+				}
+				stashable.push(handler.sig.ident.clone());
+			}
 		}
 
-		let role_name = RoleName::new(role_name);
+		if let Some(pattern) = when {
+			Ok(PerformanceDecl {
+				role_name,
+				handlers: vec![],
+				phases: vec![PhaseDecl { pattern, handlers }],
+				batches,
+				conflating,
+				stashable,
+				priority,
+				message: None,
+				blanket: false,
+			})
+		} else {
+			Ok(PerformanceDecl {
+				role_name,
+				handlers,
+				phases: vec![],
+				batches,
+				conflating,
+				stashable,
+				priority,
+				message: None,
+				blanket: false,
+			})
+		}
+	}
+
+	/// A `#[handler]` method, which performs `Handles<M>` for the type `M` of its message parameter
+	pub(crate) fn handler(mut handler: FunctionItem) -> Result<PerformanceDecl> {
+		let sig = &handler.sig;
+		if sig.generics.type_params().next().is_some() {
+			return Err(Error::new_spanned(
+				sig,
+				"Generic handlers are not supported",
+			));
+		}
+		if !matches!(sig.inputs.first(), Some(FnArg::Receiver(_))) {
+			return Err(Error::new_spanned(sig, "Handler must have self-receiver"));
+		}
+		let inputs = sig.extract_input_type_vector();
+		let parameters = if sig.has_context_input() {
+			&inputs[1..]
+		} else {
+			&inputs[..]
+		};
+		let [message] = parameters else {
+			return Err(Error::new_spanned(
+				sig,
+				"Handler must take exactly one parameter, the message",
+			));
+		};
+		let message = (*message).clone();
+
+		// `M::Reply` can't be written for a concrete `M`, so it's taken to mean the reply type, and anything else has to be the reply type already
+		if let ReturnType::Type(_, ty) = &mut handler.sig.output
+			&& is_reply_shorthand(ty, &message)
+		{
+			**ty = parse_quote!(<#message as ::shakespeare::Request>::Reply);
+		}
 
 		Ok(PerformanceDecl {
-			role_name,
-			handlers,
+			role_name:  RoleName::new(parse_quote!(::shakespeare::Handles<#message>)),
+			handlers:   vec![handler],
+			phases:     vec![],
+			batches:    vec![],
+			conflating: vec![],
+			stashable:  vec![],
+			priority:   None,
+			message:    Some(message),
+			blanket:    false,
 		})
 	}
 
+	/// A role named in `#[actor(performs(...))]`, whose performance was written for any state type meeting some bounds
+	pub(crate) fn blanket(role_name: Path) -> PerformanceDecl {
+		PerformanceDecl {
+			role_name:  RoleName::new(role_name),
+			handlers:   vec![],
+			phases:     vec![],
+			batches:    vec![],
+			conflating: vec![],
+			stashable:  vec![],
+			priority:   None,
+			message:    None,
+			blanket:    true,
+		}
+	}
+
+	/// A canonical performance declares its role, which it can only do for a role that has no parameters or associated types
+	pub(crate) fn check_canonical(&self) -> Result<()> {
+		if self.role_name.is_generic() {
+			return Err(Error::new_spanned(
+				&self.role_name,
+				"A role with parameters or associated types must be declared with `#[role]`, rather than by a canonical performance",
+			));
+		}
+		Ok(())
+	}
+
 	pub(crate) fn get_role_name(&self) -> &RoleName {
 		&self.role_name
 	}
+
+	/// Whether there are no handlers at all, as with the placeholder for a performance defined outside the actor
+	pub(crate) fn is_empty(&self) -> bool {
+		self.handlers.is_empty() && self.phases.is_empty()
+	}
+
+	/// Combines another performance of the same role into this one
+	pub(crate) fn merge(&mut self, other: PerformanceDecl) -> Result<()> {
+		assert_eq!(self.role_name, other.role_name);
+		if self.blanket || other.blanket {
+			return Err(Error::new_spanned(
+				&self.role_name,
+				"A role named in `performs` is handled by its blanket performance, so cannot also be performed in the actor",
+			));
+		}
+		for method in other.methods() {
+			let ident = &method.sig.ident;
+			let defined_by_both = self.defines(ident) && other.defines(ident);
+			if defined_by_both
+				&& self.batch_limit(ident).is_some() != other.batch_limit(ident).is_some()
+			{
+				return Err(Error::new_spanned(
+					ident,
+					"A method must be `#[batch]` in all of the performances that define it, or none",
+				));
+			}
+		}
+		if let Some(priority) = &other.priority {
+			if self
+				.priority
+				.as_ref()
+				.is_some_and(|existing| existing.base10_digits() != priority.base10_digits())
+			{
+				return Err(Error::new_spanned(
+					priority,
+					"The performances of a role cannot be given different priorities",
+				));
+			}
+			self.priority = Some(priority.clone());
+		}
+		if !other.handlers.is_empty() {
+			if !self.handlers.is_empty() {
+				return Err(Error::new_spanned(
+					&other.handlers[0].sig,
+					"Only one performance of a role can be without `when`",
+				));
+			}
+			self.handlers = other.handlers;
+		}
+		self.phases.extend(other.phases);
+		for batch in other.batches {
+			if self.batch_limit(&batch.method).is_none() {
+				self.batches.push(batch);
+			}
+		}
+		for method in other.stashable {
+			if !self.is_stashable(&method) {
+				self.stashable.push(method);
+			}
+		}
+		Ok(())
+	}
+
+	/// One handler for each method that any of the performances define, preferring the fallback's, with the signature the role gives it
+	pub(crate) fn methods(&self) -> Vec<FunctionItem> {
+		self.handlers
+			.iter()
+			.chain(self.phases.iter().flat_map(|p| &p.handlers))
+			.unique_by(|h| &h.sig.ident)
+			.map(|h| {
+				let mut h = h.clone();
+				if self.batch_limit(&h.sig.ident).is_some() {
+					unbatch_signature(&mut h);
+				}
+				h
+			})
+			.collect_vec()
+	}
+
+	/// The role's priority, where roles without one have priority 0
+	pub(crate) fn priority(&self) -> i32 {
+		self.priority
+			.as_ref()
+			.map_or(0, |p| p.base10_parse().expect("Checked when parsed"))
+	}
+
+	/// The most calls that will be given to the handler for `method` at once, if it is batched
+	pub(crate) fn batch_limit(&self, method: &Ident) -> Option<&LitInt> {
+		self.batches
+			.iter()
+			.find(|b| &b.method == method)
+			.map(|b| &b.limit)
+	}
+
+	/// Whether any of the performances marked `method` as `#[stashable]`
+	pub(crate) fn is_stashable(&self, method: &Ident) -> bool {
+		self.stashable.contains(method)
+	}
+
+	fn defines(&self, method: &Ident) -> bool {
+		self.handlers
+			.iter()
+			.chain(self.phases.iter().flat_map(|p| &p.handlers))
+			.any(|h| &h.sig.ident == method)
+	}
+}
+
+/// Whether `ty` is written `M::Reply`, where `M` is the message type
+fn is_reply_shorthand(ty: &Type, message: &Type) -> bool {
+	let (Type::Path(ty), Type::Path(message)) = (ty, message) else {
+		return false;
+	};
+	let segments = ty.path.segments.iter().collect_vec();
+	let Some((reply, owner)) = segments.split_last() else {
+		return false;
+	};
+	ty.qself.is_none()
+		&& message.qself.is_none()
+		&& reply.ident == "Reply"
+		&& reply.arguments.is_none()
+		&& owner.len() == message.path.segments.len()
+		&& owner
+			.iter()
+			.zip(&message.path.segments)
+			.all(|(a, b)| *a == b)
+}
+
+/// Adds the associated types a performance chooses to its role, as in `Sink<Item = u32>`
+fn with_assoc_types(mut role_name: Path, assoc_types: &[(Ident, Type)]) -> Path {
+	if assoc_types.is_empty() {
+		return role_name;
+	}
+	let leaf = role_name.segments.last_mut().unwrap();
+	let bindings = assoc_types.iter().map(|(ident, ty)| quote!(#ident = #ty));
+	leaf.arguments = match &leaf.arguments {
+		PathArguments::AngleBracketed(args) => {
+			let args = args.args.iter();
+			PathArguments::AngleBracketed(parse_quote! { <#(#args,)* #(#bindings),*> })
+		}
+		_ => PathArguments::AngleBracketed(parse_quote! { <#(#bindings),*> }),
+	};
+	role_name
+}
+
+/// Removes the `#[stashable]` attribute from a handler, giving it back if there was one
+fn take_stashable_attribute(attrs: &mut Vec<Attribute>) -> Option<Attribute> {
+	let index = attrs.iter().position(|a| a.path().is_ident("stashable"))?;
+	Some(attrs.remove(index))
+}
+
+/// Removes the `#[batch]` attribute from the handler and checks its signature is suitable
+fn read_batch(handler: &mut FunctionItem) -> Result<Option<BatchDecl>> {
+	let Some(position) = handler
+		.attrs
+		.iter()
+		.position(|a| a.path().is_ident("batch"))
+	else {
+		return Ok(None);
+	};
+	let attr = handler.attrs.remove(position);
+	let BatchAttribute { limit } = attr.parse_args()?;
+	if limit.base10_parse::<usize>()? == 0 {
+		return Err(Error::new_spanned(limit, "Batch limit must be at least 1"));
+	}
+
+	let sig = &handler.sig;
+	let inputs = sig.extract_input_type_vector();
+	let parameters = if sig.has_context_input() {
+		&inputs[1..]
+	} else {
+		&inputs[..]
+	};
+	if !matches!(parameters, [ty] if vec_item_type(ty).is_some()) {
+		return Err(Error::new_spanned(
+			sig,
+			"A batched method must take a single `Vec` of the role method's parameter",
+		));
+	}
+	if let ReturnType::Type(_, ty) = &sig.output
+		&& vec_item_type(ty).is_none()
+		&& !matches!(&**ty, Type::Tuple(t) if t.elems.is_empty())
+	{
+		return Err(Error::new_spanned(
+			ty,
+			"A batched method must return a `Vec` with a value for each call, or nothing",
+		));
+	}
+
+	Ok(Some(BatchDecl {
+		method: sig.ident.clone(),
+		limit,
+	}))
+}
+
+/// Gets `T` from `Vec<T>`
+pub(crate) fn vec_item_type(ty: &Type) -> Option<&Type> {
+	let Type::Path(path) = ty else {
+		return None;
+	};
+	let last = path.path.segments.last()?;
+	if last.ident != "Vec" {
+		return None;
+	}
+	let PathArguments::AngleBracketed(args) = &last.arguments else {
+		return None;
+	};
+	match args.args.first() {
+		Some(GenericArgument::Type(item)) if args.args.len() == 1 => Some(item),
+		_ => None,
+	}
+}
+
+/// Turns the signature of a batched handler into the signature of the role method it handles, by taking the `Vec`s off
+fn unbatch_signature(handler: &mut FunctionItem) {
+	if let Some(FnArg::Typed(param)) = handler.sig.inputs.last_mut()
+		&& let Some(item) = vec_item_type(&param.ty)
+	{
+		*param.ty = item.clone();
+	}
+	if let ReturnType::Type(_, ty) = &mut handler.sig.output
+		&& let Some(item) = vec_item_type(ty)
+	{
+		**ty = item.clone();
+	}
 }
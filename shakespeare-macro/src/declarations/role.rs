@@ -1,13 +1,22 @@
 use itertools::Itertools;
-use syn::{Attribute, Path, Signature, Visibility};
+use syn::{Attribute, Generics, Ident, Path, Signature, TraitItemType, TypeParamBound, Visibility};
 
 use crate::data::SignatureExt;
 
 pub(crate) struct RoleDecl {
-	pub(crate) name:       Path,
-	pub(crate) attributes: Vec<Attribute>,
-	pub(crate) vis:        Visibility,
-	pub(crate) signatures: Vec<Signature>,
+	pub(crate) name:        Path,
+	pub(crate) attributes:  Vec<Attribute>,
+	pub(crate) vis:         Visibility,
+	pub(crate) signatures:  Vec<Signature>,
+	/// Methods marked `#[conflate]`
+	pub(crate) conflating:  Vec<Ident>,
+	/// The role's own parameters, which only a `#[role]` trait can have
+	pub(crate) generics:    Generics,
+	pub(crate) assoc_types: Vec<TraitItemType>,
+	/// The roles this one extends, which every performer of it must also perform
+	pub(crate) supertraits: Vec<TypeParamBound>,
+	/// Whether the role is an actor's shell, for its `#[message]` methods, rather than a trait
+	pub(crate) shell:       bool,
 }
 
 impl RoleDecl {
@@ -16,6 +25,7 @@ impl RoleDecl {
 		attributes: Vec<Attribute>,
 		vis: Visibility,
 		signatures: impl Iterator<Item = Signature>,
+		conflating: Vec<Ident>,
 	) -> RoleDecl {
 		let mut signatures = signatures.collect_vec();
 
@@ -28,6 +38,18 @@ impl RoleDecl {
 			attributes,
 			vis,
 			signatures,
+			conflating,
+			generics: Generics::default(),
+			assoc_types: vec![],
+			supertraits: vec![],
+			shell: false,
 		}
 	}
 }
+
+/// Removes the `#[conflate]` attribute from a role method, returning whether it was there
+pub(crate) fn take_conflate_attribute(attrs: &mut Vec<Attribute>) -> bool {
+	let before = attrs.len();
+	attrs.retain(|a| !a.path().is_ident("conflate"));
+	attrs.len() != before
+}
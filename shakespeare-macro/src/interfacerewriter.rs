@@ -1,5 +1,6 @@
+use proc_macro2::Ident;
 use syn::fold::Fold;
-use syn::{Block, Receiver, ReturnType, parse_quote};
+use syn::{Block, Pat, Receiver, ReturnType, Type, TypePath, parse_quote};
 
 use crate::data::{RoleName, SignatureExt};
 
@@ -35,7 +36,7 @@ impl Fold for InterfaceRewriter<'_> {
 		sig.remove_context_param();
 
 		sig.output = parse_quote!(
-			-> ::shakespeare::Envelope<dyn #role_name, #old_return>
+			-> ::shakespeare::Envelope<#role_name, #old_return>
 		);
 
 		sig
@@ -45,3 +46,56 @@ impl Fold for InterfaceRewriter<'_> {
 		i // Don't recurse because we don't want to modify the contents
 	}
 }
+
+/// Replaces the associated types of a role, written `Self::Item`, with the types given for them, either the ones a performance chooses or the payload enums' parameters
+pub(crate) struct AssocTypeReplacer<'a> {
+	types: &'a [(Ident, Type)],
+}
+
+impl AssocTypeReplacer<'_> {
+	pub(crate) fn new(types: &[(Ident, Type)]) -> AssocTypeReplacer<'_> {
+		AssocTypeReplacer { types }
+	}
+}
+
+impl Fold for AssocTypeReplacer<'_> {
+	fn fold_type(&mut self, ty: Type) -> Type {
+		if let Type::Path(TypePath { qself: None, path }) = &ty
+			&& let [first, second] = &path.segments.iter().collect::<Vec<_>>()[..]
+			&& first.ident == "Self"
+			&& let Some((_, replacement)) =
+				self.types.iter().find(|(name, _)| *name == second.ident)
+		{
+			return replacement.clone();
+		}
+		syn::fold::fold_type(self, ty)
+	}
+}
+
+/// Turns the signature of a method in a performance for any state type `S` into its declaration in a trait implemented for `S`, where `S` is `Self` and parameters can't be patterns
+pub(crate) struct BlanketSignature<'a> {
+	state_param: &'a Ident,
+}
+
+impl BlanketSignature<'_> {
+	pub(crate) fn new(state_param: &Ident) -> BlanketSignature<'_> {
+		BlanketSignature { state_param }
+	}
+}
+
+impl Fold for BlanketSignature<'_> {
+	fn fold_type_path(&mut self, ty: TypePath) -> TypePath {
+		let mut ty = syn::fold::fold_type_path(self, ty);
+		if ty.qself.is_none()
+			&& let Some(first) = ty.path.segments.first_mut()
+			&& first.ident == *self.state_param
+		{
+			first.ident = Ident::new("Self", first.ident.span());
+		}
+		ty
+	}
+
+	fn fold_pat(&mut self, _: Pat) -> Pat {
+		parse_quote!(_)
+	}
+}
@@ -22,26 +22,45 @@ mod macros;
 mod outputs;
 
 use data::DataName;
-use declarations::{ActorDecl, PerformanceDecl, RoleDecl};
+use declarations::{
+	ActorAttribute, ActorDecl, PerformanceAttribute, PerformanceDecl, RoleDecl,
+	take_conflate_attribute,
+};
+use itertools::Itertools;
 use macros::filter_unwrap;
-use outputs::{ActorOutput, PerfDispatch, RoleOutput};
+use outputs::{ActorOutput, FunctionOutput, PerfDispatch, RoleOutput};
 use proc_macro::TokenStream;
 use quote::ToTokens;
 use syn::parse::Parse;
-use syn::{ItemImpl, ItemMod, ItemTrait, Result, TraitItem, Type, parse_quote};
+use syn::{
+	Item, ItemFn, ItemImpl, ItemMod, ItemTrait, Result, TraitBoundModifier, TraitItem, Type,
+	TypeParamBound, parse_quote,
+};
 use visibility as _;
 
-// The following three functions exist as entry points to the macros that can be called outside of a proc-macro context.
+// The following four functions exist as entry points to the macros that can be called outside of a proc-macro context.
 // This is so that the xtask expand script can call them to manually do code expansion.
 // They must be public so that the other module can see them, but cannot be public if this being built as a proc-macro crate because they have the wrong signatures.
 
 #[cfg_attr(not(proc_macro), visibility::make(pub(crate)))]
-fn make_actor(module: ItemMod) -> Result<ActorOutput> {
-	ActorOutput::new(ActorDecl::new(module)?)
+fn make_actor(attr: proc_macro2::TokenStream, module: ItemMod) -> Result<ActorOutput> {
+	let options: ActorAttribute = syn::parse2(attr)?;
+	ActorOutput::new(ActorDecl::new(options, module)?)
 }
 
 #[cfg_attr(not(proc_macro), visibility::make(pub(crate)))]
-fn make_performance(imp: ItemImpl) -> Result<PerfDispatch> {
+fn make_actor_fn(attr: proc_macro2::TokenStream, fun: ItemFn) -> Result<FunctionOutput> {
+	if !attr.is_empty() {
+		return Err(syn::Error::new_spanned(
+			attr,
+			"An actor function does not take any options",
+		));
+	}
+	FunctionOutput::new(fun)
+}
+
+#[cfg_attr(not(proc_macro), visibility::make(pub(crate)))]
+fn make_performance(imp: ItemImpl, canonical: bool) -> Result<(PerfDispatch, Option<RoleOutput>)> {
 	let empty_perf_error = syn::Error::new_spanned(&imp, "Standalone performance needs methods");
 
 	let Some((_, role_name, _)) = &imp.trait_ else {
@@ -58,13 +77,62 @@ fn make_performance(imp: ItemImpl) -> Result<PerfDispatch> {
 		));
 	};
 
-	let data_name = DataName::new(typath.clone());
+	// A performance for any state type that meets some bounds, which actors opt into with `performs`
+	let state_param = typath
+		.path
+		.get_ident()
+		.filter(|ident| {
+			imp.generics
+				.type_params()
+				.any(|param| param.ident == **ident)
+		})
+		.cloned();
+
+	// The shell is only known through the state type, which can't say which of its parameters the shell has
+	if state_param.is_none() && !imp.generics.params.is_empty() {
+		return Err(syn::Error::new_spanned(
+			&imp.generics,
+			"Performances of a generic actor must be declared inside the actor's module",
+		));
+	}
+
+	let data_name = DataName::new(typath.clone(), imp.generics.clone());
 	let actor_path = data_name.get_shell_type_path();
 
-	let decl = PerformanceDecl::new(role_name.clone(), imp)?;
+	let role_name = role_name.clone();
+	let attrs = imp.attrs.clone();
+	let generics = imp.generics.clone();
+	let decl = PerformanceDecl::new(role_name.clone(), imp, None, None)?;
+	let role = if canonical {
+		decl.check_canonical()?;
+		let signatures = decl.methods().into_iter().map(|f| f.sig);
+		let decl = RoleDecl::new(
+			role_name,
+			attrs,
+			parse_quote! { pub },
+			signatures,
+			decl.conflating.clone(),
+		);
+		Some(RoleOutput::new(decl)?)
+	} else if let Some(method) = decl.conflating.first() {
+		return Err(syn::Error::new_spanned(
+			method,
+			"`#[conflate]` belongs on the method in the role, or a canonical performance",
+		));
+	} else {
+		None
+	};
+
+	if let Some(state_param) = state_param {
+		return if decl.is_empty() {
+			Err(empty_perf_error)
+		} else {
+			Ok((PerfDispatch::blanket(&decl, &generics, &state_param)?, role))
+		};
+	}
 
 	match PerfDispatch::new(&decl, &actor_path, &data_name)? {
-		Some(pd) => Ok(pd),
+		Some(pd) => Ok((pd, role)),
 		None => Err(empty_perf_error),
 	}
 }
@@ -76,12 +144,58 @@ fn make_role(imp: ItemTrait) -> Result<RoleOutput> {
 		attrs,
 		items,
 		vis,
+		generics,
+		supertraits,
 		..
 	} = imp;
 
-	let signatures = filter_unwrap!(items, TraitItem::Fn).map(|f| f.sig);
+	if let Some(lifetime) = generics.lifetimes().next() {
+		return Err(syn::Error::new_spanned(
+			lifetime,
+			"A role cannot have lifetime parameters, as its messages are sent to another task",
+		));
+	}
+	// Roles only extend other roles, named by path
+	if let Some(bound) = supertraits.iter().find(|bound| {
+		!matches!(bound, TypeParamBound::Trait(bound) if matches!(bound.modifier, TraitBoundModifier::None))
+	}) {
+		return Err(syn::Error::new_spanned(
+			bound,
+			"A role can only extend other roles",
+		));
+	}
+	let assoc_types = filter_unwrap!(&items, TraitItem::Type)
+		.cloned()
+		.collect_vec();
+	if let Some(ty) = assoc_types
+		.iter()
+		.find(|ty| !ty.generics.params.is_empty() || ty.default.is_some())
+	{
+		return Err(syn::Error::new_spanned(
+			ty,
+			"A role's associated types cannot have parameters or defaults",
+		));
+	}
 
-	let decl = RoleDecl::new(parse_quote! { #name }, attrs, vis, signatures);
+	let mut signatures = vec![];
+	let mut conflating = vec![];
+	for mut method in filter_unwrap!(items, TraitItem::Fn) {
+		if take_conflate_attribute(&mut method.attrs) {
+			conflating.push(method.sig.ident.clone());
+		}
+		signatures.push(method.sig);
+	}
+
+	let mut decl = RoleDecl::new(
+		parse_quote! { #name },
+		attrs,
+		vis,
+		signatures.into_iter(),
+		conflating,
+	);
+	decl.generics = generics;
+	decl.assoc_types = assoc_types;
+	decl.supertraits = supertraits.into_iter().collect();
 
 	RoleOutput::new(decl)
 }
@@ -99,13 +213,63 @@ fn parse_macro_input<T: Parse>(
 ///
 /// This macro attaches to an inline `mod` block that must contain the following items:
 /// 1. exactly one `struct`, `enum` *or* `union` definition representing the actor's state type. Call this `S`
-/// 2. at least one [`macro@performance`] block.
+/// 2. at least one [`macro@performance`] block, or at least one method marked `#[message]` or `#[handler]` as below.
 ///
 /// The `mod` can also optionally contain any of:
 /// 1. a function called `stop` that consumes `self` and has any return type, so long as that type is concrete (i.e. not `impl Trait` or with unbound generic types) and `Sized + 'static`. This function will be called with the actor's state value (of type `S`) when the actor drops or when the `Context` is explicitly called to do so.
 /// 2. a function called `catch` that consumes `self` and also consumes a `Box<dyn Any + Send>`, with a return type with the same conditions as `stop`. This function will be called with the state value and any value provided to the `panic!` call if any of the actor's performance methods panic.
 ///
-/// Other items, including inherent `impl S` blocks, will be passed through unmodified into the surrounding module.
+/// Other items, including inherent `impl S` blocks, will be passed through unmodified into the surrounding module, except for any methods in those blocks marked `#[message]`. Each of these becomes a method on the shell that sends a message to the actor and returns an [`Envelope`][1], exactly as if it were in a `canonical` performance, but without having to name a Role. The methods can take a `Context` and be marked `#[batch]`, `#[conflate]` or `#[stashable]` in the same way. The shell itself is the Role of these messages, so the envelopes are e.g. `Envelope<MyActor, u32>`, and no trait is declared for them. The impl blocks have to be for the actor's state type.
+/// ```
+/// # use shakespeare::actor;
+/// #[actor]
+/// mod Counter {
+/// 	struct State(u32);
+/// 	impl State {
+/// 		#[message]
+/// 		fn increment(&mut self) -> u32 {
+/// 			self.0 += 1;
+/// 			self.0
+/// 		}
+/// 	}
+/// }
+/// ```
+///
+/// A method in those blocks can instead be marked `#[handler]`, in which case it takes a single message of a type `M` that implements [`Request`][4] (after an optional `Context`), and returns `M::Reply`, written either as the type itself or as `M::Reply` with the name of the message type. The shell then implements [`Handles<M>`][5], which is a Role of its own, so the message is sent with `handle(msg)` on the shell or on an `Arc<dyn Handles<M>>`. Unlike the methods of a Role, the message types do not have to be known in one place, so other crates can define new messages for an actor to handle. An actor can only have one handler for each message type.
+/// ```
+/// # use shakespeare::{actor, Request};
+/// struct Add(u32);
+/// impl Request for Add {
+/// 	type Reply = u32;
+/// }
+///
+/// #[actor]
+/// mod Counter {
+/// 	struct State(u32);
+/// 	impl State {
+/// 		#[handler]
+/// 		fn add(&mut self, msg: Add) -> u32 {
+/// 			self.0 += msg.0;
+/// 			self.0
+/// 		}
+/// 	}
+/// }
+/// ```
+///
+/// For an actor that only needs a state and a single kind of message, such as a counter or a sink for a stream, the macro can instead go on a function taking `&mut` the state and the message, which can be `async`. The function is replaced by one with the same name that takes the initial state and starts the actor with [`spawn_fn`][6], so the actor's shell is an [`FnActor`][7] that is sent messages with its `send` method.
+/// ```
+/// # use shakespeare::actor;
+/// #[actor]
+/// async fn accumulate(total: &mut u64, n: u64) -> u64 {
+/// 	*total += n;
+/// 	*total
+/// }
+/// # #[tokio::main]
+/// # async fn main() {
+/// let accumulator = accumulate(0).message_handle;
+/// assert_eq!(accumulator.send(5).await.unwrap(), 5);
+/// # }
+/// ```
 ///
 /// The macro then generates a new proxy type, the "actor shell," with the same name as the module. This new type:
 /// 1. has a constructor function `start(state: S) -> ActorHandles<Self>`. (This function is currently *always* private to the parent module containing the `#[actor]` block - for now, you will need to write a wrapper to access it from a wider scope)
@@ -113,20 +277,56 @@ fn parse_macro_input<T: Parse>(
 ///
 /// The `ActorHandles` contains an `Arc` that refers to the actor object. This value is the interface for sending the actor messages and controls its lifetime. When the last `Arc` goes out of scope, the actor will finish processing any messages it has already received, call its `stop` function if one exists, and then drop its state. If a method handler inside a performance panics, the `catch` function will be called *instead of* `stop`.
 ///
+/// If `S` has generic parameters, the shell has the same ones, along with their bounds and any `where` clause, and `start` is generic over them too. The performances inside the module are written for every instantiation, with the same parameter names (`impl<T: Clone + Send + 'static> MyRole for S<T>`). The parameters should be `Send + 'static`, as the state is moved into the actor's task, and the roles themselves cannot mention them, but messages for a `#[handler]` can (`Handles<Insert<T>>`). Performances of a generic actor have to be declared inside its module.
+/// ```
+/// # use shakespeare::actor;
+/// #[actor]
+/// mod Recent {
+/// 	struct RecentState<T: Clone + Send + 'static, const N: usize> {
+/// 		items: Vec<T>,
+/// 	}
+/// 	#[performance(canonical)]
+/// 	impl<T: Clone + Send + 'static, const N: usize> Capacity for RecentState<T, N> {
+/// 		fn is_full(&self) -> bool {
+/// 			self.items.len() == N
+/// 		}
+/// 	}
+/// }
+/// # #[tokio::main]
+/// # async fn main() {
+/// let recent = Recent::start(RecentState::<u64, 2> { items: vec![1, 2] }).message_handle;
+/// assert!(recent.is_full().await.unwrap());
+/// # }
+/// ```
+///
 /// The actor `Arc` can be upcast to a `Arc<dyn MyRole>` (for an actor with a performance of `MyRole`) to allow for code that works generically over a given role.
 ///
 /// The `ActorHandles` also contains a `ExitHandle`, which is a future that will yield the value produced by the actor stopping, either successfully or by panic. It  is not necessary to implement `stop` or `catch` as above to use the `ExitHandle`.
 ///
 /// ## Mailbox
 ///
-/// Each method defined in the actor shell will produce an [`Envelope`][1] value that contains all of the method's parameters. When this [`Envelope`][1] is consumed (see that type's documentation) then this bundle of values is sent to a queue inside the actor (currently always an [tokio unbounded MPSC queue](https://docs.rs/tokio/latest/tokio/sync/mpsc/)), which is the actor's "mailbox." When the actor's `start` method is called, a new task is spawned that continually awaits for new messages arriving into the mailbox, and when one does arrive, the method from the corresponding [`macro@performance`] block is called. If this method is async, it is polled to completion before the task looks for new entries in the queue. (Be aware this may cause deadlocks if it is awaiting a Future that itself is waiting for the same actor to respond.) Once the method does run to completion, the return value may need to be passed back to the original caller of the Envelope value or dropped, but once the return value is handled, the actor's task then awaits for the next queue entry.
+/// Each method defined in the actor shell will produce an [`Envelope`][1] value that contains all of the method's parameters. When this [`Envelope`][1] is consumed (see that type's documentation) then this bundle of values is sent to a queue inside the actor (currently always an [tokio unbounded MPSC queue](https://docs.rs/tokio/latest/tokio/sync/mpsc/)), which is the actor's "mailbox." Messages are taken from the mailbox in the order they arrived, unless they were given a priority with [`Envelope::with_priority`][1]. When the actor's `start` method is called, a new task is spawned that continually awaits for new messages arriving into the mailbox, and when one does arrive, the method from the corresponding [`macro@performance`] block is called. If this method is async, it is polled to completion before the task looks for new entries in the queue. (Be aware this may cause deadlocks if it is awaiting a Future that itself is waiting for the same actor to respond.) Once the method does run to completion, the return value may need to be passed back to the original caller of the Envelope value or dropped, but once the return value is handled, the actor's task then awaits for the next queue entry.
 ///
 /// Internally, each method translates to a variant of an enum defined by the overall Role, which contains all of the method parameters inline. This means similar warnings to those about large enum variants apply - avoid methods which have parameters that take up a large number of bytes between them. If large types are involved, roles can take heap allocation pointers (`Box`, `Arc`, etc) in exactly the same way as they take the original value types.
 ///
 /// As explained in more detail in the [crate index documentation][2], the event loop "spuriously" wakes up occasionally to check whether the actor is still alive even with no messages pending.
 ///
+/// ## Options
+///
+/// The attribute accepts a comma-separated list of options that change how the generated actor behaves:
+///
+/// * `concurrent_reads` - by default, each message is handled to completion before the next is started. With this flag, a run of consecutive messages whose handlers take `&self` (and, if they take a `Context`, take it as `&Context<Self>`) are handled *concurrently* with each other. Any other handler waits until all of the running `&self` handlers have finished before it starts, and no further messages are handled until it is done, so the state is effectively behind a reader-writer lock that is scheduled by the event loop. As the state is shared between the concurrently running handlers, this requires the state type to be `Sync`. Note that a handler that awaits a message to its own actor still deadlocks in this mode if that message needs exclusive access.
+/// * `single_mailbox` - by default, each role has its own queue, and the actor picks between the queues that have messages waiting, so there is no ordering between calls made through different roles. With this flag, the calls made through all of the actor's roles go into a single queue and are handled in the order they arrive, as described in the [crate index documentation][2]. Roles cannot be given a `priority` in this mode, and batches of calls to a `#[batch]` method and superseded calls to a `#[conflate]` method only include calls that are waiting directly after each other, with no call through another role between them.
+/// * `inspect` - gives the shell a method `with_state(f)`, which sends the closure `f: FnOnce(&mut S) -> T` to the actor to be run on its state like any other message, and returns a [`StateQuery<T>`][3] future of its result. This is meant for tests, admin consoles and debugging, so is only generated when asked for. The closure goes into its own queue, or into the single queue with `single_mailbox`, in which case it is run in order with the calls made through the actor's roles.
+/// * `performs(Role, ...)` - the actor performs each of the listed roles with a performance written for any state type meeting some bounds, such as `impl<S: HasHealth> Health for S`, rather than one inside the module. See [the `performance` documentation](macro@performance#blanket-performances).
+///
 /// [1]: https://docs.rs/shakespeare/latest/shakespeare/struct.Envelope.html
 /// [2]: https://docs.rs/shakespeare/latest/shakespeare/
+/// [3]: https://docs.rs/shakespeare/latest/shakespeare/struct.StateQuery.html
+/// [4]: https://docs.rs/shakespeare/latest/shakespeare/trait.Request.html
+/// [5]: https://docs.rs/shakespeare/latest/shakespeare/trait.Handles.html
+/// [6]: https://docs.rs/shakespeare/latest/shakespeare/fn.spawn_fn.html
+/// [7]: https://docs.rs/shakespeare/latest/shakespeare/struct.FnActor.html
 #[proc_macro_attribute]
 pub fn actor(attr: TokenStream, item: TokenStream) -> TokenStream {
 	actor_internal(attr.into(), item.into()).into()
@@ -137,14 +337,16 @@ fn actor_internal(
 	attr: proc_macro2::TokenStream,
 	item: proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
-	std::mem::drop(attr); // <-- Removes a clippy warning, because we need this exact signature for tests
-	match parse_macro_input(item) {
-		Ok(module) => match make_actor(module) {
-			Ok(actor_output) => actor_output.to_token_stream(),
-			Err(e) => e.into_compile_error().into_token_stream(),
-		},
-		Err(err) => err,
-	}
+	let output = match parse_macro_input(item) {
+		Ok(Item::Mod(module)) => make_actor(attr, module).map(|actor| actor.to_token_stream()),
+		Ok(Item::Fn(fun)) => make_actor_fn(attr, fun).map(|actor| actor.to_token_stream()),
+		Ok(other) => Err(syn::Error::new_spanned(
+			other,
+			"An actor must be declared on a module or a function",
+		)),
+		Err(err) => return err,
+	};
+	output.unwrap_or_else(|e| e.into_compile_error().into_token_stream())
 }
 
 /// Defines an actor's implementation of a Role.
@@ -215,7 +417,7 @@ fn actor_internal(
 ///
 /// ## Canonical performances
 ///
-/// Currently, all external method calls into an actor must be defined by some Role that the actor performs. However, it is expected that some Roles will have a single "primary" implementation, with other implementations (if any exist) being conceptually subsidiary to that one, e.g. an actor would have some interface as required by the domain logic, but a mock implementation of that same interface (for testing outside interactions with the actor) would be subsidiary, because the mock interface's only responsibility is to match the domain-logic original, and the mock will never drive changes in the original's interfaces. Conversely, some cases will involve multiple "equal" implementations, such as differing implementations for a database connection - in these cases, it's advisable to define the Role using the [macro](`macro@role`), and then define separate `#[performance]` blocks.
+/// Apart from `#[message]` methods (see the [`macro@actor`] documentation), all external method calls into an actor must be defined by some Role that the actor performs. However, it is expected that some Roles will have a single "primary" implementation, with other implementations (if any exist) being conceptually subsidiary to that one, e.g. an actor would have some interface as required by the domain logic, but a mock implementation of that same interface (for testing outside interactions with the actor) would be subsidiary, because the mock interface's only responsibility is to match the domain-logic original, and the mock will never drive changes in the original's interfaces. Conversely, some cases will involve multiple "equal" implementations, such as differing implementations for a database connection - in these cases, it's advisable to define the Role using the [macro](`macro@role`), and then define separate `#[performance]` blocks.
 ///
 /// For cases that do have a single primary implementation, the Role can be defined *implicitly* by the performance, by passing the `canonical` flag to the `#[performance]` attribute. The previous example can be equivalently written:
 /// ```
@@ -233,7 +435,160 @@ fn actor_internal(
 /// ```
 /// In addition to defining the implementation for how `MyActor` implements `MyRole` as with the `#[performance]` examples seen so far, the above *also* defines the overall Role called `MyRole`. It is defined to match the signatures that `MyActor` implements - it contains a single method, `a_method`, which in turn takes a single `usize` as its parameter. Methods inside a canonical performance *are* allowed to use `Context` parameters as described previously, and the generated Role will remove the `Context` parameters automatically. As a result, if a second actor implements a Role defined by a canonical performance, then that actor's performances of the methods may use (or not use) a `Context` independently of the canonical one.
 ///
-/// Currently, a performance must be included inside the `#[actor]` module in order to be `canonical`.
+/// A `canonical` performance can also be outside the `#[actor]` module, in the same way as any other performance, in which case the Role is defined where the performance is and the empty `#[performance]` block inside the module names it as usual.
+///
+/// ## Phases
+///
+/// Actors that follow a protocol often need to respond to the same message differently depending on what has happened so far. Rather than matching on the state at the top of every method, an actor can have several performances of the same role, each given a pattern with `when`:
+/// ```
+/// use shakespeare::{actor, performance, role};
+/// #[role]
+/// trait Connection {
+/// 	fn connect(&self);
+/// 	fn send(&self, data: Vec<u8>) -> bool;
+/// }
+/// #[actor]
+/// mod MyActor {
+/// 	enum State {
+/// 		Disconnected,
+/// 		Connected { sent: usize },
+/// 	}
+/// 	#[performance]
+/// 	impl Connection for State {
+/// 		fn connect(&mut self) {
+/// 			*self = State::Connected { sent: 0 };
+/// 		}
+///
+/// 		fn send(&self, _data: Vec<u8>) -> bool {
+/// 			false
+/// 		}
+/// 	}
+/// 	#[performance(when = State::Connected { .. })]
+/// 	impl Connection for State {
+/// 		fn send(&mut self, data: Vec<u8>) -> bool {
+/// 			// ...
+/// 			true
+/// 		}
+/// 	}
+/// }
+/// ```
+/// When a message arrives, the actor's state is matched against the pattern of each performance with `when` that has a method for that message, in the order they are written, and the first that matches handles it. If none of them match, the message is handled by the performance *without* `when`, which acts as the fallback. Each of these performances only needs the methods that it handles, and there can be at most one fallback for each role. If there is no fallback method for a message and no pattern matches, the message is not handled, and awaiting its [`Envelope`](https://docs.rs/shakespeare/latest/shakespeare/struct.Envelope.html) gives `Err(EnvelopeErr::OutOfPhase)`, so callers can tell a message that arrived too early or too late from the actor having stopped.
+///
+/// The pattern is matched against `self`, so the state type is usually an `enum`, and a handler moves the actor to another phase by assigning to `*self`. A struct can also be matched on one of its fields with e.g. `when = State { phase: Phase::Connected, .. }`. Performances with `when` must be inside the `#[actor]` module.
+///
+/// ## Batching
+///
+/// Some actors, such as ones writing to a database or a log, can handle many calls to the same method much more efficiently together than one at a time. Marking a method with `#[batch(limit = N)]` means its handler is given all the calls to that method that are waiting consecutively in the actor's queue, up to `N` of them, at once:
+/// ```
+/// use shakespeare::{actor, performance};
+/// #[actor]
+/// mod MyActor {
+/// 	struct State(Vec<String>);
+/// 	#[performance(canonical)]
+/// 	impl Logger for State {
+/// 		#[batch(limit = 100)]
+/// 		fn write(&mut self, lines: Vec<String>) -> Vec<usize> {
+/// 			lines
+/// 				.into_iter()
+/// 				.map(|line| {
+/// 					self.0.push(line);
+/// 					self.0.len()
+/// 				})
+/// 				.collect()
+/// 		}
+/// 	}
+/// }
+/// ```
+/// The role method that this handles takes a single `String` and returns a single `usize` - callers are not aware of the batching, and each receives its own return value. To make this work, the handler of a batched method takes a single `Vec` parameter (after the receiver and `Context`, if any) holding the parameter of each call in the order they were sent, and must either return a `Vec` with exactly one value for each call, in the same order, or return nothing. If the `Vec` is the wrong length, every caller in the batch gets [`EnvelopeErr::Unmatched`](https://docs.rs/shakespeare/latest/shakespeare/enum.EnvelopeErr.html#variant.Unmatched) instead of a value, and the actor carries on. A role method with several parameters cannot be batched, so should take a tuple or struct instead. A batch never includes calls to other methods, so the order in which the actor handles messages is unchanged.
+///
+/// ## Stashing
+///
+/// An actor that isn't ready for a message yet, such as one still waiting for a connection, can set it aside and later replay every message it set aside with `Context::unstash_all`. Only methods marked `#[stashable]` can do this. Their handler can take a [`StashContext`](https://docs.rs/shakespeare/latest/shakespeare/struct.StashContext.html) in place of the `Context`, naming the method's parameters, and give those parameters back to its `stash` method:
+/// ```
+/// use shakespeare::{Context, StashContext, actor, performance};
+/// #[actor]
+/// mod MyActor {
+/// 	struct State {
+/// 		connected: bool,
+/// 	}
+/// 	#[performance(canonical)]
+/// 	impl Data for State {
+/// 		#[stashable]
+/// 		fn send(&mut self, ctx: &mut StashContext<'_, Self, Vec<u8>>, data: Vec<u8>) -> bool {
+/// 			if !self.connected {
+/// 				ctx.stash(data);
+/// 				return false;
+/// 			}
+/// 			// ...
+/// 			true
+/// 		}
+/// 	}
+/// 	#[performance(canonical)]
+/// 	impl Control for State {
+/// 		fn connected(&mut self, ctx: &mut Context<Self>) {
+/// 			self.connected = true;
+/// 			ctx.unstash_all();
+/// 		}
+/// 	}
+/// }
+/// ```
+/// The caller of a stashed call is only answered once it has been handled again, and the return value from the handler that stashed it is thrown away. A method that isn't `#[stashable]` is given a plain `Context`, so it cannot stash. A `#[batch]` method cannot be `#[stashable]`, and a `#[stashable]` method taking `&self` is never handled alongside other messages by an actor with `concurrent_reads`.
+///
+/// ## Priority
+///
+/// By default, when messages are waiting for more than one of an actor's roles, the actor picks which role to take the next message from at random, so that no role can starve the others. If a role such as one for control messages should instead always be served first, its performance can be given a priority with `#[performance(priority = N)]`:
+/// ```
+/// use shakespeare::{actor, performance};
+/// #[actor]
+/// mod Worker {
+/// 	struct State;
+/// 	#[performance(canonical, priority = 10)]
+/// 	impl Control for State {
+/// 		fn health_check(&self) -> bool {
+/// 			true
+/// 		}
+/// 	}
+/// 	#[performance(canonical)]
+/// 	impl Data for State {
+/// 		fn process(&self, item: u64) {}
+/// 	}
+/// }
+/// ```
+/// If any of an actor's performances has a priority, the actor always takes its next message from the role with the highest priority that has one waiting, including ahead of calls it has already taken out of a lower priority role's queue, such as unstashed calls or those taken while looking for calls to batch or supersede, so a flood of messages to a lower priority role can delay the others indefinitely. Roles without a priority have priority 0, and roles with the same priority are checked in the order their performances are written. If a role has several performances, at most one value can be given between them, and `priority` can only be used on a performance inside the `#[actor]` module. To let single messages overtake others waiting for the *same* role, see [`Envelope::with_priority`](https://docs.rs/shakespeare/latest/shakespeare/struct.Envelope.html#method.with_priority).
+///
+/// ## Blanket performances
+///
+/// When many actors perform a role in the same way, the performance can be written once, for any state type that meets some bounds, and each actor opts into it by naming the role in `#[actor(performs(...))]`:
+/// ```
+/// use shakespeare::{actor, performance, role};
+/// trait HasHealth {
+/// 	fn healthy(&self) -> bool;
+/// }
+///
+/// #[role]
+/// trait Health {
+/// 	fn check(&self) -> bool;
+/// }
+///
+/// #[performance]
+/// impl<S: HasHealth> Health for S {
+/// 	fn check(&self) -> bool {
+/// 		self.healthy()
+/// 	}
+/// }
+///
+/// #[actor(performs(Health))]
+/// mod Worker {
+/// 	struct State(bool);
+///
+/// 	impl HasHealth for State {
+/// 		fn healthy(&self) -> bool {
+/// 			self.0
+/// 		}
+/// 	}
+/// }
+/// ```
+/// A blanket performance is written outside of any actor and can't use `when` or `priority`, but is otherwise the same as any other performance, including using a `Context<S>`, having `#[batch]` methods and being `canonical`. The role, which can have parameters of its own as in `performs(Store<K, V>)`, is then performed by any actor that names it, provided its state meets the bounds. The actor can't also have its own performance of a role it names in `performs`, and its shell does not get inherent methods for the role, so calls are made through the role trait.
 #[proc_macro_attribute]
 pub fn performance(attr: TokenStream, item: TokenStream) -> TokenStream {
 	performance_internal(attr.into(), item.into()).into()
@@ -243,10 +598,36 @@ fn performance_internal(
 	attr: proc_macro2::TokenStream,
 	item: proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
-	std::mem::drop(attr); // <-- Removes a clippy warning, because we need this exact signature for tests
+	let canonical = match syn::parse2::<PerformanceAttribute>(attr) {
+		Ok(PerformanceAttribute {
+			when: Some(phase), ..
+		}) => {
+			return syn::Error::new_spanned(
+				phase.0,
+				"A performance with `when` must be inside the `#[actor]` module",
+			)
+			.into_compile_error();
+		}
+		Ok(PerformanceAttribute {
+			priority: Some(priority),
+			..
+		}) => {
+			return syn::Error::new_spanned(
+				priority,
+				"A performance with `priority` must be inside the `#[actor]` module",
+			)
+			.into_compile_error();
+		}
+		Ok(args) => args.canonical.value(),
+		Err(e) => return e.into_compile_error(),
+	};
 	match parse_macro_input(item) {
-		Ok(imp) => match make_performance(imp) {
-			Ok(perf) => perf.to_token_stream(),
+		Ok(imp) => match make_performance(imp, canonical) {
+			Ok((perf, role)) => {
+				let mut tokens = perf.to_token_stream();
+				role.to_tokens(&mut tokens);
+				tokens
+			}
 			Err(e) => e.into_compile_error().into_token_stream(),
 		},
 		Err(err) => err,
@@ -255,13 +636,13 @@ fn performance_internal(
 
 /// Defines an interface that an actor may implement.
 ///
-/// This macro applies to a `trait` definition, and for now has no attributes.
+/// This macro applies to a `trait` definition, and for now has no attributes of its own, though its methods may be marked `#[conflate]` as described below.
 ///
 /// The trait has the following restrictions:
-/// 1. it cannot have any associated constants or types
+/// 1. it cannot have any associated constants or lifetime parameters, and its associated types cannot have parameters or defaults
 /// 2. all functions must be methods and should take `&self` as receiver. (But see the documentation for [macro@performance]) Currently, `&mut self` is allowed but redundant - other receiver types are not allowed at all.
 /// 3. all other parameters and all return types must be `Send`, `Sized` and have a lifetime of `'static`
-/// 4. Neither methods nor parameters can have "unbound" generic parameters, nor use `impl Trait` in either parameter or return position. (`Option<u32>` is allowed, `Option<T>` is not) Role methods *are* allowed to be `async`, but it is not allowed to have the function return `impl Future`.
+/// 4. Neither methods nor parameters can have "unbound" generic parameters, nor use `impl Trait` in either parameter or return position. (`Option<u32>` is allowed, as is `Option<T>` for a parameter `T` of the role or `Option<Self::Item>` for one of its associated types, but a method cannot have generic parameters of its own) Role methods *are* allowed to be `async`, but it is not allowed to have the function return `impl Future`.
 ///
 /// Role methods may be async, and if they are, may `await` other futures. However, be aware that the actor's message loop will be blocked while awaiting - this risks deadlocks if other actors have sent it messages and are waiting for the return values. [`Envelope::forward_to`](https://docs.rs/shakespeare/latest/shakespeare/struct.Envelope.html#method.forward_to) may be useful to avoid this situation.
 ///
@@ -287,6 +668,101 @@ fn performance_internal(
 /// ```
 ///
 /// In addition, calling `a_method` won't immediately do any work - see the documentation for [`Envelope`](https://docs.rs/shakespeare/latest/shakespeare/struct.Envelope.html)
+///
+/// ## Generic roles
+///
+/// A role can have type and const parameters, and associated types, so that one role can describe, say, a store for any type of key and value. Every instantiation is a role of its own, so `Arc<dyn Store<u64, String>>` and `Arc<dyn Sink<Item = u32>>` can be used as any other role is, and a role's parameters and associated types are required to be `Send + 'static`. A performance chooses the instantiation it performs, which can use the parameters of a generic actor, and gives its associated types in the usual way.
+/// ```
+/// # use shakespeare::{actor, role};
+/// # use std::collections::HashMap;
+/// #[role]
+/// trait Store<K, V> {
+/// 	fn get(&self, key: K) -> Option<V>;
+/// }
+///
+/// #[role]
+/// trait Sink {
+/// 	type Item;
+/// 	fn push(&mut self, item: Self::Item);
+/// }
+///
+/// #[actor]
+/// mod Names {
+/// 	struct NamesState(HashMap<u64, String>);
+///
+/// 	#[performance]
+/// 	impl Store<u64, String> for NamesState {
+/// 		fn get(&self, key: u64) -> Option<String> {
+/// 			self.0.get(&key).cloned()
+/// 		}
+/// 	}
+///
+/// 	#[performance]
+/// 	impl Sink for NamesState {
+/// 		type Item = (u64, String);
+///
+/// 		fn push(&mut self, item: Self::Item) {
+/// 			self.0.insert(item.0, item.1);
+/// 		}
+/// 	}
+/// }
+/// ```
+/// The `Accepts` and `Emits` implementations are generated for every instantiation, except for types that could be the same as another method's for some choice of the parameters, such as the parameters of two methods taking `K` and `V`. A role with parameters or associated types can't be declared by a `canonical` performance.
+///
+/// ## Extending roles
+///
+/// A role can extend other roles by naming them as supertraits, so that anything with the richer role can be used where the narrower one is expected. Every supertrait must itself be a `#[role]` trait, so other bounds such as lifetimes or `?Sized` are rejected:
+/// ```
+/// # use std::sync::Arc;
+/// # use shakespeare::{actor, role};
+/// #[role]
+/// trait Reader {
+/// 	fn read(&self) -> u32;
+/// }
+///
+/// #[role]
+/// trait Admin: Reader {
+/// 	fn write(&self, value: u32);
+/// }
+///
+/// #[actor]
+/// mod Db {
+/// 	struct State(u32);
+///
+/// 	#[performance]
+/// 	impl Reader for State {
+/// 		fn read(&self) -> u32 {
+/// 			self.0
+/// 		}
+/// 	}
+///
+/// 	#[performance]
+/// 	impl Admin for State {
+/// 		fn write(&mut self, value: u32) {
+/// 			self.0 = value;
+/// 		}
+/// 	}
+/// }
+///
+/// fn reader(admin: Arc<dyn Admin>) -> Arc<dyn Reader> {
+/// 	admin
+/// }
+/// ```
+/// An actor that performs the subrole must also perform each of the roles it extends, and the performance of the subrole only has the subrole's own methods. Calling one of the parent's methods through the subrole, as in `admin.read()`, sends the call to the parent role as if it were made through `Arc<dyn Reader>`, so it is handled by the parent's performance and, unless the actor has a `single_mailbox`, isn't ordered with calls made through the subrole's own methods.
+///
+/// ## Conflating methods
+///
+/// For some methods, such as one that sets a position or updates a configuration, an earlier call is obsolete as soon as a later one is made. Marking such a method with `#[conflate]` means that, when the actor takes a call to it from its queue, any earlier calls that have a later call to the same method waiting behind them are *superseded* - they are not handled, and awaiting their [`Envelope`](https://docs.rs/shakespeare/latest/shakespeare/struct.Envelope.html) gives `Err(EnvelopeErr::Superseded)`. Only the most recent of the waiting calls is handled, at the position in the queue of that call, so the handler sees the newest arguments, and calls to other methods are still handled in the order they were sent.
+/// ```
+/// use shakespeare::role;
+/// #[role]
+/// trait Cursor {
+/// 	#[conflate]
+/// 	fn set_position(&self, x: u32, y: u32);
+/// 	fn click(&self);
+/// }
+/// ```
+/// A call is only superseded by another call that is already waiting when the actor takes it from the queue, so a call that arrives afterwards does not stop an earlier one from being handled. Only the 64 calls at the front of the queue are looked at each time, so that taking a call is never unbounded work, and a call with more than that waiting behind it can still be handled even if a later call supersedes it. A superseded call that nothing is waiting on, such as one sent by dropping its `Envelope`, is given to the actor's dead-letter sink. The same attribute can also be used on a method in a `canonical` performance, which then applies to the Role it defines.
 #[proc_macro_attribute]
 pub fn role(attr: TokenStream, item: TokenStream) -> TokenStream {
 	role_internal(attr.into(), item.into()).into()
@@ -296,7 +772,10 @@ fn role_internal(
 	attr: proc_macro2::TokenStream,
 	item: proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
-	std::mem::drop(attr); // <-- Removes a clippy warning, because we need this exact signature for tests
+	if !attr.is_empty() {
+		return syn::Error::new_spanned(attr, "`#[role]` does not take any arguments")
+			.into_compile_error();
+	}
 	match parse_macro_input(item) {
 		Ok(imp) => match make_role(imp) {
 			Ok(role) => role.to_token_stream(),
@@ -314,6 +793,8 @@ mod tests {
 	use std::{env, fs};
 
 	use runtime_macros::emulate_attributelike_macro_expansion;
+	// Only the integration tests use these, and xtask doesn't depend on all of them
+	use {futures as _, shakespeare as _, tokio as _, tokio_stream as _, tokio_util as _}; // EXPANDER EXCLUDE
 
 	#[test] // EXPANDER EXCLUDE
 	fn expand_actor() {
@@ -328,11 +809,11 @@ mod tests {
 		expand_for_dir(dir);
 	}
 
+	type Expander =
+		fn(proc_macro2::TokenStream, proc_macro2::TokenStream) -> proc_macro2::TokenStream;
+
 	fn expand_for_dir(dir: fs::ReadDir) {
-		let macros: &[(
-			&str,
-			fn(proc_macro2::TokenStream, proc_macro2::TokenStream) -> proc_macro2::TokenStream,
-		); 3] = &[
+		let macros: &[(&str, Expander); 3] = &[
 			("actor", crate::actor_internal),
 			("performance", crate::performance_internal),
 			("role", crate::role_internal),
@@ -0,0 +1,339 @@
+use itertools::Itertools;
+use quote::{ToTokens, quote};
+use syn::{Ident, ItemEnum, ItemImpl, Result, Visibility, parse_quote};
+
+use crate::data::{ActorName, DataName, RoleName};
+use crate::declarations::{ActorDecl, PerformanceDecl};
+use crate::macros::{fallible_quote, map_or_bail};
+
+/// An enum with a variant for each role the actor performs, so that a message for any of them can be handled as a single value, and another for each role holding a batch of calls to one of its batched methods.
+#[derive(Debug)]
+pub(crate) struct ActorPayload {
+	definition:   ItemEnum,
+	from_impls:   Vec<ItemImpl>,
+	unpack_impls: Vec<ItemImpl>,
+	forward:      ItemImpl,
+	bury:         ItemImpl,
+	priority:     ItemImpl,
+	roles_ranked: Option<ItemImpl>,
+	dispatch:     ItemImpl,
+}
+
+impl ActorPayload {
+	pub(crate) fn new(actor: &ActorDecl) -> Result<ActorPayload> {
+		let ActorDecl {
+			actor_name,
+			actor_vis,
+			data_item,
+			performances,
+			options,
+			..
+		} = actor;
+
+		let payload_name = actor_name.payload_name();
+		let role_names = performances
+			.iter()
+			.map(PerformanceDecl::get_role_name)
+			.collect_vec();
+		let variants = role_names.iter().map(|r| r.variant_name()).collect_vec();
+		let batch_variants = role_names
+			.iter()
+			.map(|r| r.batch_variant_name())
+			.collect_vec();
+
+		let data_name = data_item.name();
+		let inspect = options.inspect.value();
+		let inspect_variant =
+			inspect.then(|| quote! { Inspect(::shakespeare::Inspection<#data_name>), });
+
+		let payload_ident = actor_name.payload_ident();
+		let generics = actor_name.generics();
+		let (impl_generics, _, where_clause) = generics.split_for_impl();
+		// The state type's parameters might not be used by any of the roles, and a variant that can't be made uses them regardless
+		let generic = !generics.params.is_empty();
+		let phantom_variant = generic.then(|| {
+			quote! { Phantom(::std::convert::Infallible, ::std::marker::PhantomData<fn() -> #data_name>), }
+		});
+		let phantom_arm =
+			generic.then(|| quote! { #payload_name::Phantom(never, _) => match never {}, });
+
+		let definition = fallible_quote! {
+			#[doc(hidden)]
+			#actor_vis enum #payload_ident #generics #where_clause {
+				#(#variants(::shakespeare::ReturnEnvelope<#role_names>),)*
+				#(#batch_variants(Vec<::shakespeare::ReturnEnvelope<#role_names>>),)*
+				#inspect_variant
+				#phantom_variant
+			}
+		}?;
+
+		let mut from_impls = map_or_bail!(&role_names, |role| create_from_impl(actor_name, role));
+		if inspect {
+			from_impls.push(fallible_quote! {
+				impl #impl_generics ::std::convert::From<::shakespeare::Inspection<#data_name>> for #payload_name #where_clause {
+					fn from(inspection: ::shakespeare::Inspection<#data_name>) -> Self {
+						#payload_name::Inspect(inspection)
+					}
+				}
+			}?);
+		}
+		let unpack_impls = map_or_bail!(&role_names, |role| create_unpack_impl(actor_name, role));
+
+		// A closure over this actor's state cannot run on a successor, so its caller sees the actor hang up
+		let drop_inspection = inspect.then(|| quote! { #payload_name::Inspect(_) => {} });
+
+		// A successor has to perform every role, so that any message can be forwarded to it
+		let mut forward_generics = generics.clone();
+		forward_generics.params.insert(0, parse_quote! { S });
+		forward_generics
+			.make_where_clause()
+			.predicates
+			.push(parse_quote! {
+				S: #(::shakespeare::Performs<#role_names> +)* ?Sized + 'static
+			});
+		let (forward_impl_generics, _, forward_where_clause) = forward_generics.split_for_impl();
+		let forward = fallible_quote! {
+			impl #forward_impl_generics ::shakespeare::ForwardTo<S> for #payload_name #forward_where_clause
+			{
+				fn forward_to(self, dest: &::std::sync::Arc<S>) {
+					match self {
+						#(#payload_name::#variants(msg) => ::shakespeare::forward::<#role_names, S>(dest, msg),)*
+						#(#payload_name::#batch_variants(msgs) => {
+							for msg in msgs {
+								::shakespeare::forward::<#role_names, S>(dest, msg);
+							}
+						})*
+						#drop_inspection
+						#phantom_arm
+					}
+				}
+			}
+		}?;
+
+		let dispatch = create_dispatch_impl(
+			actor_name,
+			&data_name,
+			&role_names,
+			options.concurrent_reads.value(),
+			inspect,
+			actor_vis,
+		)?;
+
+		Ok(ActorPayload {
+			definition,
+			from_impls,
+			unpack_impls,
+			forward,
+			bury: create_bury_impl(actor_name, &role_names, inspect)?,
+			priority: create_priority_impl(actor_name, &variants)?,
+			roles_ranked: create_role_priority_impl(actor_name, performances, actor_vis)?,
+			dispatch,
+		})
+	}
+}
+
+impl ToTokens for ActorPayload {
+	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+		self.definition.to_tokens(tokens);
+		for i in &self.from_impls {
+			i.to_tokens(tokens);
+		}
+		for i in &self.unpack_impls {
+			i.to_tokens(tokens);
+		}
+		self.forward.to_tokens(tokens);
+		self.bury.to_tokens(tokens);
+		self.priority.to_tokens(tokens);
+		self.roles_ranked.to_tokens(tokens);
+		self.dispatch.to_tokens(tokens);
+	}
+}
+
+/// Gives what the actor had not handled when it stopped to its dead-letter sink, except for a closure over its state, which is dropped as it is by a successor
+fn create_bury_impl(
+	actor_name: &ActorName,
+	role_names: &[&RoleName],
+	inspect: bool,
+) -> Result<ItemImpl> {
+	let payload_name = actor_name.payload_name();
+	let generics = actor_name.generics();
+	let (impl_generics, _, where_clause) = generics.split_for_impl();
+	let phantom_arm = (!generics.params.is_empty())
+		.then(|| quote! { #payload_name::Phantom(never, _) => match never {}, });
+	let drop_inspection = inspect.then(|| quote! { #payload_name::Inspect(_) => {} });
+	let variants = role_names.iter().map(|r| r.variant_name()).collect_vec();
+	let batch_variants = role_names
+		.iter()
+		.map(|r| r.batch_variant_name())
+		.collect_vec();
+
+	fallible_quote! {
+		impl #impl_generics ::shakespeare::Bury for #payload_name #where_clause {
+			fn bury(self, target: &::shakespeare::Lifecycle) {
+				match self {
+					#(#payload_name::#variants(msg) => ::shakespeare::Bury::bury(msg, target),)*
+					#(#payload_name::#batch_variants(msgs) => {
+						for msg in msgs {
+							::shakespeare::Bury::bury(msg, target);
+						}
+					})*
+					#drop_inspection
+					#phantom_arm
+				}
+			}
+		}
+	}
+}
+
+/// Gives a message's own priority, which is only used to order the messages in a `single_mailbox`, which never holds batches
+fn create_priority_impl(actor_name: &ActorName, variants: &[Ident]) -> Result<ItemImpl> {
+	let payload_name = actor_name.payload_name();
+	let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
+	fallible_quote! {
+		impl #impl_generics ::shakespeare::Prioritised for #payload_name #where_clause {
+			fn priority(&self) -> i32 {
+				match self {
+					#(#payload_name::#variants(msg) => msg.priority,)*
+					_ => 0,
+				}
+			}
+		}
+	}
+}
+
+/// Gives the priority of the role a message was sent through, which is only needed if any role has one, so that a queue with a higher priority can overtake messages that have already been taken out of the queues
+fn create_role_priority_impl(
+	actor_name: &ActorName,
+	performances: &[PerformanceDecl],
+	actor_vis: &Visibility,
+) -> Result<Option<ItemImpl>> {
+	if performances.iter().all(|perf| perf.priority.is_none()) {
+		return Ok(None);
+	}
+	let payload_name = actor_name.payload_name();
+	let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
+	let variants = performances
+		.iter()
+		.map(|perf| perf.role_name.variant_name())
+		.collect_vec();
+	let batch_variants = performances
+		.iter()
+		.map(|perf| perf.role_name.batch_variant_name())
+		.collect_vec();
+	let priorities = performances.iter().map(PerformanceDecl::priority);
+	fallible_quote! {
+		impl #impl_generics #payload_name #where_clause {
+			#[doc(hidden)]
+			#actor_vis fn role_priority(&self) -> i32 {
+				// Closures from `with_state` have no role, so have priority 0
+				#[allow(unreachable_patterns)]
+				match self {
+					#(#payload_name::#variants(_) | #payload_name::#batch_variants(_) => #priorities,)*
+					_ => 0,
+				}
+			}
+		}
+	}
+	.map(Some)
+}
+
+fn create_from_impl(actor_name: &ActorName, role_name: &RoleName) -> Result<ItemImpl> {
+	let payload_name = actor_name.payload_name();
+	let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
+	let variant = role_name.variant_name();
+	fallible_quote! {
+		impl #impl_generics ::std::convert::From<::shakespeare::ReturnEnvelope<#role_name>> for #payload_name #where_clause {
+			fn from(msg: ::shakespeare::ReturnEnvelope<#role_name>) -> Self {
+				#payload_name::#variant(msg)
+			}
+		}
+	}
+}
+
+fn create_unpack_impl(actor_name: &ActorName, role_name: &RoleName) -> Result<ItemImpl> {
+	let payload_name = actor_name.payload_name();
+	let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
+	let variant = role_name.variant_name();
+	let batch_variant = role_name.batch_variant_name();
+	fallible_quote! {
+		impl #impl_generics ::shakespeare::PayloadFor<#role_name> for #payload_name #where_clause {
+			fn into_messages(self) -> Result<Vec<::shakespeare::ReturnEnvelope<#role_name>>, Self> {
+				// An actor performing only one role has nothing else to give back
+				#[allow(unreachable_patterns)]
+				match self {
+					#payload_name::#variant(msg) => Ok(vec![msg]),
+					#payload_name::#batch_variant(msgs) => Ok(msgs),
+					other => Err(other),
+				}
+			}
+		}
+	}
+}
+
+fn create_dispatch_impl(
+	actor_name: &ActorName,
+	data_name: &DataName,
+	role_names: &[&RoleName],
+	concurrent_reads: bool,
+	inspect: bool,
+	actor_vis: &Visibility,
+) -> Result<ItemImpl> {
+	let payload_name = actor_name.payload_name();
+	let generics = actor_name.generics();
+	let (impl_generics, _, where_clause) = generics.split_for_impl();
+	let phantom_arm = (!generics.params.is_empty())
+		.then(|| quote! { #payload_name::Phantom(never, _) => match never {}, });
+	let run_inspection =
+		inspect.then(|| quote! { #payload_name::Inspect(inspection) => inspection.run(state), });
+
+	let variants = role_names.iter().map(|r| r.variant_name()).collect_vec();
+	let dispatch_names = role_names.iter().map(|r| r.method_name()).collect_vec();
+	let batch_variants = role_names
+		.iter()
+		.map(|r| r.batch_variant_name())
+		.collect_vec();
+	let batch_names = role_names
+		.iter()
+		.map(|r| r.batch_method_name())
+		.collect_vec();
+
+	let shared_methods = if concurrent_reads {
+		let check_names = role_names.iter().map(|r| r.shared_check_name());
+		let shared_names = role_names.iter().map(|r| r.shared_method_name());
+		Some(quote! {
+			#[doc(hidden)]
+			#actor_vis fn is_shared(&self) -> bool {
+				match self {
+					#(#payload_name::#variants(msg) => #data_name::#check_names(msg),)*
+					_ => false
+				}
+			}
+
+			#[doc(hidden)]
+			#actor_vis async fn dispatch_shared(self, state: &#data_name, context: &::shakespeare::Context<#data_name>) {
+				match self {
+					#(#payload_name::#variants(msg) => state.#shared_names(context, msg).await,)*
+					_ => unreachable!("Batch dispatched as shared")
+				}
+			}
+		})
+	} else {
+		None
+	};
+
+	fallible_quote! {
+		impl #impl_generics #payload_name #where_clause {
+			#[doc(hidden)]
+			#actor_vis async fn dispatch(self, state: &mut #data_name, context: &mut ::shakespeare::Context<#data_name>) {
+				match self {
+					#(#payload_name::#variants(msg) => state.#dispatch_names(context, msg).await,)*
+					#(#payload_name::#batch_variants(msgs) => state.#batch_names(context, msgs).await,)*
+					#run_inspection
+					#phantom_arm
+				}
+			}
+
+			#shared_methods
+		}
+	}
+}
@@ -1,7 +1,8 @@
 use itertools::Itertools;
+use proc_macro2::TokenStream;
 use quote::{ToTokens, quote};
 use syn::parse::Parser;
-use syn::{Field, ImplItem, ItemImpl, ItemStruct, Result, Visibility};
+use syn::{Field, ItemImpl, ItemStruct, Result, TypePath, Visibility};
 
 use crate::data::{ActorName, DataName, FuncReturnType, RoleName};
 use crate::declarations::{ActorDecl, PerformanceDecl};
@@ -11,7 +12,7 @@ use crate::macros::{fallible_quote, map_or_bail};
 pub(crate) struct ActorStruct {
 	strukt:                  ItemStruct,
 	sender_method_name_impl: ItemImpl,
-	meta_traits:             [ItemImpl; 2],
+	meta_traits:             [ItemImpl; 3],
 }
 
 impl ActorStruct {
@@ -23,16 +24,49 @@ impl ActorStruct {
 			actor_vis,
 			handlers,
 			data_item,
+			options,
 			..
 		} = actor;
 
-		let fields = map_or_bail!(performances, shell_field_from_performance);
+		let payload_name = actor_name.payload_name();
+		let single_mailbox = options.single_mailbox.value();
 
+		let mut fields = if single_mailbox {
+			vec![Field::parse_named.parse2(quote! {
+				#[doc(hidden)]
+				mailbox: <::shakespeare::PriorityUnbounded<#payload_name> as ::shakespeare::Channel>::Sender
+			})?]
+		} else {
+			map_or_bail!(performances, shell_field_from_performance)
+		};
+		// With a single mailbox, closures from `with_state` go into the same queue as everything else
+		if options.inspect.value() && !single_mailbox {
+			let data_name = data_item.name();
+			fields.push(Field::parse_named.parse2(quote! {
+				#[doc(hidden)]
+				inspections: <::shakespeare::TokioUnbounded<::shakespeare::Inspection<#data_name>> as ::shakespeare::Channel>::Sender
+			})?);
+		}
+
+		// The state type's parameters are otherwise only used by some of the fields
+		if !actor_name.generics().params.is_empty() {
+			let data_name = data_item.name();
+			fields.push(Field::parse_named.parse2(quote! {
+				#[doc(hidden)]
+				state_type: ::std::marker::PhantomData<fn() -> #data_name>
+			})?);
+		}
+
+		let actor_ident = actor_name.ident();
+		let generics = actor_name.generics();
+		let where_clause = &generics.where_clause;
 		let strukt = fallible_quote! {
 			#(#attributes)*
-			#actor_vis struct #actor_name {
+			#actor_vis struct #actor_ident #generics #where_clause {
 				#[doc(hidden)]
 				this: ::std::sync::Weak<Self>,
+				#[doc(hidden)]
+				lifecycle: ::shakespeare::Lifecycle,
 				#(#fields),*
 			}
 		}?;
@@ -42,7 +76,12 @@ impl ActorStruct {
 			.map(PerformanceDecl::get_role_name)
 			.collect_vec();
 
-		let sender_method_name_impl = create_inherent_impl(&role_names, actor_vis, actor_name)?;
+		let sender_method_name_impl = create_inherent_impl(
+			&role_names,
+			actor_vis,
+			actor_name,
+			single_mailbox.then_some(&payload_name),
+		)?;
 
 		let meta_traits = create_meta_trait_impl(
 			handlers.panic_return(),
@@ -72,46 +111,94 @@ fn create_meta_trait_impl(
 	exit_return: FuncReturnType,
 	actor_name: &ActorName,
 	data_name: &DataName,
-) -> Result<[ItemImpl; 2]> {
+) -> Result<[ItemImpl; 3]> {
+	let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
 	let actor_trait = fallible_quote! {
-		impl ::shakespeare::ActorShell for #actor_name {
+		impl #impl_generics ::shakespeare::ActorShell for #actor_name #where_clause {
 			type StateType = #data_name;
 			type ExitType = #exit_return;
 			type PanicType = #panic_return;
 		}
 	}?;
 
+	let monitorable_trait = fallible_quote! {
+		impl #impl_generics ::shakespeare::Monitorable for #actor_name #where_clause {
+			fn lifecycle(&self) -> &::shakespeare::Lifecycle {
+				&self.lifecycle
+			}
+		}
+	}?;
+
+	let payload_name = actor_name.payload_name();
 	let state_trait = fallible_quote! {
-		impl ::shakespeare::ActorState for #data_name {
+		impl #impl_generics ::shakespeare::ActorState for #data_name #where_clause {
 			type ShellType = #actor_name;
+			type Payload = #payload_name;
+
+			fn start_actor(self) -> ::shakespeare::ActorHandles<#actor_name> {
+				<#actor_name>::start(self)
+			}
 		}
 	}?;
 
-	Ok([actor_trait, state_trait])
+	Ok([actor_trait, monitorable_trait, state_trait])
 }
 
 fn create_inherent_impl(
 	role_names: &Vec<&RoleName>,
 	actor_vis: &Visibility,
 	actor_name: &ActorName,
+	mailbox_payload: Option<&TypePath>,
 ) -> Result<ItemImpl> {
-	let make_methods = |role_name: &&RoleName| -> Result<ImplItem> {
+	let make_methods = |role_name: &&RoleName| -> Result<TokenStream> {
 		let field_name = role_name.queue_name();
 		let accessor_name = role_name.sender_method_name();
+		let try_accessor_name = role_name.try_sender_method_name();
+
+		if let Some(payload_name) = mailbox_payload {
+			let variant = role_name.variant_name();
+			// The message comes back in the error if the actor has stopped, and has to be taken out of the actor's payload again
+			return fallible_quote! {
+				#[doc(hidden)]
+				#actor_vis fn #accessor_name(&self, payload: ::shakespeare::ReturnEnvelope<#role_name>) -> Result<(), ::shakespeare::Role2SendError<#role_name>>
+				{
+					// The mailbox is unbounded, so is never full
+					self.#try_accessor_name(payload).map_err(|(::shakespeare::TrySendError::Full(msg) | ::shakespeare::TrySendError::Closed(msg))| {
+						::shakespeare::tokio_export::sync::mpsc::error::SendError(msg)
+					})
+				}
+
+				#[doc(hidden)]
+				#actor_vis fn #try_accessor_name(&self, payload: ::shakespeare::ReturnEnvelope<#role_name>) -> Result<(), ::shakespeare::TrySendError<::shakespeare::ReturnEnvelope<#role_name>>>
+				{
+					::shakespeare::RoleSender::try_send(&self.mailbox, #payload_name::from(payload)).map_err(|err| err.map(|mail| match mail {
+						#payload_name::#variant(msg) => msg,
+						_ => unreachable!("Message changed role while being sent"),
+					}))
+				}
+			};
+		}
 
 		fallible_quote! {
 			#[doc(hidden)]
-			#actor_vis async fn #accessor_name(&self, payload: ::shakespeare::ReturnEnvelope<dyn #role_name>) -> Result<(), ::shakespeare::Role2SendError<dyn #role_name>>
+			#actor_vis fn #accessor_name(&self, payload: ::shakespeare::ReturnEnvelope<#role_name>) -> Result<(), ::shakespeare::Role2SendError<#role_name>>
 			{
 				self.#field_name.send(payload)
 			}
+
+			#[doc(hidden)]
+			#actor_vis fn #try_accessor_name(&self, payload: ::shakespeare::ReturnEnvelope<#role_name>) -> Result<(), ::shakespeare::TrySendError<::shakespeare::ReturnEnvelope<#role_name>>>
+			{
+				::shakespeare::RoleSender::try_send(&self.#field_name, payload)
+			}
 		}
 	};
 
 	let methods = map_or_bail!(role_names, make_methods);
 
+	let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
 	fallible_quote! {
-		impl #actor_name {
+		impl #impl_generics #actor_name #where_clause {
 			#(#methods)*
 		}
 	}
@@ -1,12 +1,16 @@
 use quote::ToTokens;
 use syn::{Item, Result};
 
+mod actor_payload;
 mod actor_struct;
 mod self_getter;
+mod shell_methods;
 mod spawning_function;
 
+use actor_payload::ActorPayload;
 use actor_struct::ActorStruct;
 use self_getter::SelfGetter;
+use shell_methods::ShellMethods;
 use spawning_function::SpawningFunction;
 
 use super::performance::PerfDispatch;
@@ -19,7 +23,9 @@ use crate::macros::map_or_bail;
 pub(crate) struct ActorOutput {
 	data_item:         DataItem,
 	actor_struct:      ActorStruct,
+	actor_payload:     ActorPayload,
 	getter:            SelfGetter,
+	shell_methods:     ShellMethods,
 	spawning_function: SpawningFunction,
 	handlers:          HandlerFunctions,
 	performances:      Vec<PerfDispatch>,
@@ -30,9 +36,12 @@ pub(crate) struct ActorOutput {
 impl ActorOutput {
 	pub(crate) fn new(actor_node: ActorDecl) -> Result<ActorOutput> {
 		let actor_struct = ActorStruct::new(&actor_node)?;
+		let actor_payload = ActorPayload::new(&actor_node)?;
+		let shell_methods = ShellMethods::new(&actor_node)?;
 
 		let ActorDecl {
 			actor_name,
+			options,
 			data_item,
 			performances,
 			roles,
@@ -42,8 +51,6 @@ impl ActorOutput {
 		} = actor_node;
 
 		let data_name = data_item.name();
-		let panic_name = handlers.panic_name();
-		let exit_name = handlers.exit_name();
 
 		let getter = SelfGetter::new(&actor_name)?;
 
@@ -52,8 +59,10 @@ impl ActorOutput {
 			&actor_name,
 			&data_name,
 			&performances,
-			panic_name,
-			exit_name,
+			&handlers,
+			options.concurrent_reads.value(),
+			options.single_mailbox.value(),
+			options.inspect.value(),
 		)?;
 
 		let roles = map_or_bail!(roles, RoleOutput::new);
@@ -70,9 +79,11 @@ impl ActorOutput {
 			data_item,
 			performances,
 			getter,
+			shell_methods,
 			spawning_function: sf,
 			roles,
 			actor_struct,
+			actor_payload,
 			handlers,
 			misc,
 		})
@@ -82,8 +93,10 @@ impl ActorOutput {
 impl ToTokens for ActorOutput {
 	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
 		self.actor_struct.to_tokens(tokens);
+		self.actor_payload.to_tokens(tokens);
 		self.data_item.to_tokens(tokens);
 		self.getter.to_tokens(tokens);
+		self.shell_methods.to_tokens(tokens);
 		self.spawning_function.to_tokens(tokens);
 		self.handlers.to_tokens(tokens);
 		for p in &self.performances {
@@ -11,8 +11,9 @@ pub(crate) struct SelfGetter {
 
 impl SelfGetter {
 	pub(crate) fn new(actor_name: &ActorName) -> Result<SelfGetter> {
+		let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
 		let actor_getter: Item = fallible_quote! {
-			impl #actor_name {
+			impl #impl_generics #actor_name #where_clause {
 				#[doc(hidden)]
 				// Used internally for creating Envelopes
 				pub fn get_shell(&self) -> ::std::sync::Arc<#actor_name> {
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use itertools::{Itertools, izip};
+use quote::{ToTokens, format_ident, quote};
+use syn::{Error, Ident, ItemImpl, Result};
+
+use super::super::performance::create_sending_method;
+use crate::declarations::ActorDecl;
+use crate::macros::{fallible_quote, map_or_bail};
+
+/// Inherent methods on the shell that mirror its roles' methods, named with a `_direct` suffix, but produce an [`Envelope`] that sends to the shell directly, without dynamic dispatch
+#[derive(Debug)]
+pub(crate) struct ShellMethods {
+	imp: ItemImpl,
+}
+
+impl ShellMethods {
+	pub(crate) fn new(actor: &ActorDecl) -> Result<ShellMethods> {
+		let ActorDecl {
+			actor_name,
+			actor_vis,
+			data_item,
+			performances,
+			options,
+			..
+		} = actor;
+		let inspect = options.inspect.value();
+
+		// Handlers are sent through `Handles::handle` instead
+		let methods = performances
+			.iter()
+			.filter(|perf| perf.message.is_none())
+			.flat_map(|perf| perf.methods().into_iter().map(move |m| (perf, m)))
+			.collect_vec();
+		let reserved = |ident: &Ident| {
+			ident == "start" || ident == "get_shell" || (inspect && ident == "with_state")
+		};
+		// `#[message]` methods can only be called on the shell, so they can't give way to anything
+		if let Some((_, method)) = methods
+			.iter()
+			.find(|(perf, m)| perf.role_name.is_shell() && reserved(&m.sig.ident))
+		{
+			return Err(Error::new_spanned(
+				&method.sig.ident,
+				"The shell already has a method with this name",
+			));
+		}
+		// A role's methods are given names of their own, so that they don't hide the role trait's methods, which give an `Envelope` holding `dyn Role`
+		let names = methods
+			.iter()
+			.map(|(perf, m)| {
+				if perf.role_name.is_shell() {
+					m.sig.ident.clone()
+				} else {
+					format_ident!("{}_direct", m.sig.ident)
+				}
+			})
+			.collect_vec();
+		// A name that two methods would have, or that is already in use, is left to the role traits, which can be disambiguated
+		let taken = methods
+			.iter()
+			.map(|(_, m)| &m.sig.ident)
+			.collect::<HashSet<_>>();
+		let counts = names.iter().counts();
+		let unique = izip!(&methods, &names).filter(|((perf, _), name)| {
+			perf.role_name.is_shell()
+				|| (counts[name] == 1 && !taken.contains(name) && !reserved(name))
+		});
+
+		let sending_methods = map_or_bail!(unique, |((perf, method), name)| create_sending_method(
+			&perf.role_name.payload_path(),
+			method,
+			name,
+			&perf.role_name,
+			actor_name,
+			actor_vis
+		));
+
+		let with_state = inspect.then(|| {
+			let data_name = data_item.name();
+			let queue = if options.single_mailbox.value() {
+				quote! { mailbox }
+			} else {
+				quote! { inspections }
+			};
+			quote! {
+				/// Runs `f` on the actor's state, in order with the other messages sent through the same queue, and gives back a future of the value it returns.
+				///
+				/// The closure is sent straight away, and runs even if the returned [`StateQuery`](::shakespeare::StateQuery) is dropped.
+				#actor_vis fn with_state<Output, Inspector>(&self, f: Inspector) -> ::shakespeare::StateQuery<Output>
+				where
+					Output: ::std::marker::Send + 'static,
+					Inspector: ::std::ops::FnOnce(&mut #data_name) -> Output + ::std::marker::Send + 'static,
+				{
+					let (inspection, query) = ::shakespeare::Inspection::new(f);
+					// If the actor has stopped, the closure drops and the query reports it
+					let _ = ::shakespeare::RoleSender::try_send(&self.#queue, inspection.into());
+					query
+				}
+			}
+		});
+
+		let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
+		let imp = fallible_quote! {
+			impl #impl_generics #actor_name #where_clause {
+				#(#sending_methods)*
+				#with_state
+			}
+		}?;
+
+		Ok(ShellMethods { imp })
+	}
+}
+
+impl ToTokens for ShellMethods {
+	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+		self.imp.to_tokens(tokens);
+	}
+}
@@ -1,10 +1,12 @@
+use std::cmp::Reverse;
+
 use itertools::{Itertools, izip};
 use proc_macro2::TokenStream;
-use quote::{ToTokens, format_ident};
+use quote::{ToTokens, format_ident, quote};
 use syn::parse::Parser;
-use syn::{Expr, Field, Ident, ItemImpl, Result, Stmt};
+use syn::{Expr, Field, Ident, ItemImpl, Result, Stmt, TypePath};
 
-use crate::data::{ActorName, DataName, RoleName};
+use crate::data::{ActorName, DataName, HandlerFunctions, RoleName};
 use crate::declarations::PerformanceDecl;
 use crate::macros::{fallible_quote, map_or_bail};
 
@@ -18,132 +20,121 @@ impl SpawningFunction {
 		actor_name: &ActorName,
 		data_name: &DataName,
 		performances: &[PerformanceDecl],
-		panic_name: Option<&Ident>,
-		exit_name: Option<&Ident>,
+		handlers: &HandlerFunctions,
+		concurrent_reads: bool,
+		single_mailbox: bool,
+		inspect: bool,
 	) -> Result<SpawningFunction> {
-		let field_names = performances
-			.iter()
-			.map(PerformanceDecl::get_role_name)
-			.map(RoleName::queue_name)
-			.collect_vec();
-
-		let input_field_names = field_names
-			.iter()
-			.map(|name| format_ident!("{}_input", name))
-			.collect_vec();
-
-		let output_field_names = field_names
-			.iter()
-			.map(|name| format_ident!("{}_output", name))
-			.collect_vec();
-
-		let queue_constructions = map_or_bail!(
-			izip!(performances, &input_field_names, &output_field_names),
-			|(role, inn, out)| -> Result<Stmt> {
-				let role_name = &role.role_name;
-				fallible_quote! { let (#inn, mut #out) = <dyn #role_name as ::shakespeare::Role>::Channel::new(Default::default()); }
-			}
-		);
-
-		let actor_fields =
-			map_or_bail!(
-				izip!(performances, &input_field_names),
-				|(role, input)| -> Result<Field> {
-					let field_name = role.role_name.queue_name();
-					Field::parse_named.parse2(fallible_quote! {#field_name : #input}?)
-				}
-			);
+		let payload_name = actor_name.payload_name();
 
 		assert!(!performances.is_empty());
-		assert!(!output_field_names.is_empty());
-
-		let select_branches = map_or_bail!(
-			izip!(performances, &output_field_names),
-			|(perf, output)| -> Result<TokenStream> {
-				let fn_name = perf.role_name.method_name();
-				fallible_quote! { Some(msg) = #output.recv(), if !(#output.is_empty()) || context.is_running() => {
-					timeout_sleep.as_mut().reset(Instant::now() + IDLE_TIMEOUT);
-					state.#fn_name(&mut context, msg).await
-				} }
-			}
-		);
+
+		let Queues {
+			constructions: queue_constructions,
+			fields: actor_fields,
+			select_branches,
+			receivers,
+			priorities,
+		} = Queues::new(
+			data_name,
+			&payload_name,
+			performances,
+			single_mailbox,
+			inspect,
+		)?;
 		assert!(!select_branches.is_empty());
 
-		let constructor: Expr = fallible_quote! {
-			#actor_name {
-				this: weak.clone(),
-				#(#actor_fields),*
-			}
-		}?;
+		// With priorities, the queues are checked in order of priority rather than at random
+		let bias = performances
+			.iter()
+			.any(|perf| perf.priority.is_some())
+			.then(|| quote! { biased; });
+
+		let (take_leftover, take_pending) =
+			create_take_pending(&payload_name, &receivers, &priorities, bias.is_some());
+		let handle_mail = create_mail_handler(
+			&select_branches,
+			bias.as_ref(),
+			&take_leftover,
+			concurrent_reads,
+		)?;
 
-		let run_panic_handler: syn::Expr = panic_name
-			.map(|handler| fallible_quote! { state.#handler(panic) })
-			.unwrap_or(fallible_quote! { panic })?;
+		let constructor = create_constructor(actor_name, &actor_fields)?;
 
-		let run_exit_handler: syn::Expr = exit_name
-			.map(|handler| fallible_quote! { state.#handler()})
-			.unwrap_or(fallible_quote! { () })?;
+		let finish = create_finish(data_name, &payload_name, &receivers, handlers)?;
 
+		let (impl_generics, _, where_clause) = actor_name.generics().split_for_impl();
 		let fun: ItemImpl = fallible_quote! {
-			impl #actor_name {
+			impl #impl_generics #actor_name #where_clause {
 				/// Creates a new Actor
 				fn start(mut state: #data_name) -> shakespeare::ActorHandles<#actor_name> {
-					use ::shakespeare::{ActorHandles, Channel, Context, catch_future, tokio_export as tokio};
+					use ::shakespeare::{ActorHandles, Channel, Context, IDLE_TIMEOUT, IdleTimer, RoleReceiver, Wait, close_and_drain, discard_superseded, run_until_killed, split_batch, wait_for_mail, tokio_export as tokio};
+					#[allow(unused_imports)]
+					use ::shakespeare::futures_export::stream::{FuturesUnordered, StreamExt};
+					use ::std::collections::VecDeque;
 					use ::std::sync::Arc;
-					use tokio::{select, pin};
-					use tokio::time::{sleep, Duration, Instant};
+					use tokio::select;
+					use tokio::time::sleep;
 
-					const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+					// The most calls taken from a queue at once to find those that supersede each other
+					const CONFLATE_WINDOW: usize = 64;
 
 					#(#queue_constructions)*
 					let actor = Arc::new_cyclic(|weak| { #constructor });
 					let stored_actor = Arc::clone(&actor);
 
 					let mut context = Context::new(stored_actor);
+					let stop_signal = context.stop_signal();
+					let outside_stop_signal = context.stop_signal();
 
 					let event_loop = async move {
+						// Messages that were received along with a batch, but were not part of it
+						let mut leftovers = VecDeque::new();
 						let loop_lambda = async {
-							let timeout_sleep = sleep(IDLE_TIMEOUT);
-							pin!(timeout_sleep);
+							let mut idle = IdleTimer::default();
 							loop {
-								select! {
-									#(#select_branches),*
-									_ = &mut timeout_sleep, if context.is_running() => {
-										// Check is_running so that if it is false at the point we check, the whole loop breaks even without checking the timer
-										if !context.is_running() {
-											break;
-										}
-										else {
-											timeout_sleep.as_mut().reset(Instant::now() + IDLE_TIMEOUT)
-										}
-									},
-									else => { break; }
+								// Messages that have been unstashed go before anything still waiting in the queues
+								let mail = if let Some(mail) = #take_pending {
+									mail
+								} else {
+									// Made again each time round, so that whether the actor is still running is checked again
+									let queues = async {
+										Some(select! {
+											#bias
+											#(#select_branches,)*
+											else => { return None; }
+										})
+									};
+									match wait_for_mail(&context, &stop_signal, &mut idle, queues).await {
+										Wait::Mail(mail) => mail,
+										Wait::Woken => continue,
+										Wait::Finished => break,
+									}
 								};
+								#handle_mail
+								// Messages still waiting are only handled if the actor is stopping because its handles dropped
+								if context.is_stopped() {
+									break;
+								}
 							}
 						};
 
-
-
 						// SAFETY: The receive handles inside the branches are not safe to unwind
-						// But they're consumed by the closure, so we can never see them
-						// The senders might interact with a dead receiver though.
-						// If we assume that a panic will not happen **during** an operation on the receiver,
-						// then the control block will still be consistent at any point the sender looks at it
-						// even if the receiver was destroyed
-						let guarded_future = catch_future(loop_lambda);
-
-						let result = guarded_future.await;
-
-						match result {
-							Ok(_) => { Ok(#run_exit_handler) },
-							Err(panic) => Err(#run_panic_handler)
-						}
+						// But a panic can only happen inside a handler, never **during** an operation on a receiver,
+						// so the control block is still consistent at any point the sender looks at it,
+						// and at the point the receivers are drained for the exit handler
+						//
+						// Being killed abandons the running handler, but still runs the exit handler as normal
+						let mut result = run_until_killed(&stop_signal, loop_lambda).await;
 
+						// The actor's children have to stop before it does
+						context.stop_children().await;
 
+						#finish
 					};
 
 					let join_handle = tokio::task::spawn(event_loop);
-					ActorHandles::new(actor, join_handle)
+					ActorHandles::new(actor, join_handle, outside_stop_signal)
 				}
 			}
 		}?;
@@ -152,6 +143,460 @@ impl SpawningFunction {
 	}
 }
 
+/// The queues an actor's messages arrive in
+struct Queues {
+	/// The statements that create the queues
+	constructions:   Vec<Stmt>,
+	/// The actor fields that hold the sending ends
+	fields:          Vec<Field>,
+	/// The `select!` branches that take the next message, or batch of messages, from each queue
+	select_branches: Vec<TokenStream>,
+	/// The receiving ends
+	receivers:       Vec<Ident>,
+	/// The priority of the role each receiving end is for
+	priorities:      Vec<i32>,
+}
+
+impl Queues {
+	fn new(
+		data_name: &DataName,
+		payload_name: &TypePath,
+		performances: &[PerformanceDecl],
+		single_mailbox: bool,
+		inspect: bool,
+	) -> Result<Queues> {
+		// A single mailbox can't be used with priorities, so its messages all have priority 0
+		let mut priorities = if single_mailbox {
+			vec![0]
+		} else {
+			performances.iter().map(PerformanceDecl::priority).collect()
+		};
+		let (mut constructions, mut fields, mut select_branches, mut receivers) = if single_mailbox
+		{
+			let (constructions, fields) = create_mailbox(payload_name)?;
+			let branch = create_mailbox_branch(data_name, payload_name, performances)?;
+			(
+				constructions,
+				fields,
+				vec![branch],
+				vec![format_ident!("mailbox")],
+			)
+		} else {
+			let output_field_names = performances
+				.iter()
+				.map(|perf| format_ident!("{}_output", perf.role_name.queue_name()))
+				.collect_vec();
+			let (constructions, fields) = create_queues(performances, &output_field_names)?;
+			let branches =
+				create_select_branches(data_name, payload_name, performances, &output_field_names)?;
+			(constructions, fields, branches, output_field_names)
+		};
+		// A single mailbox already carries the closures from `with_state`
+		if inspect && !single_mailbox {
+			let (construction, field, branch) = create_inspections(data_name, payload_name)?;
+			constructions.push(construction);
+			fields.push(field);
+			select_branches.push(branch);
+			receivers.push(format_ident!("inspections_output"));
+			priorities.push(0);
+		}
+		Ok(Queues {
+			constructions,
+			fields,
+			select_branches,
+			receivers,
+			priorities,
+		})
+	}
+}
+
+/// Builds the code that closes the actor's queues and collects everything left in them, and in the stash, into `unhandled`, in the order it would have been handled.
+///
+/// Whatever the `stop` or `catch` function does not take out, or all of it if they do not take `unhandled`, goes to the actor's dead-letter sink when `unhandled` drops.
+fn create_collect_unhandled(
+	data_name: &DataName,
+	payload_name: &TypePath,
+	receivers: &[Ident],
+) -> TokenStream {
+	quote! {
+		let mut unhandled = ::shakespeare::Unhandled::<#data_name>::new(context.get_shell());
+		unhandled.extend(::std::iter::from_fn(|| context.take_unstashed()));
+		unhandled.extend(leftovers);
+		unhandled.extend(context.take_stash());
+		#(unhandled.extend(close_and_drain(&mut #receivers).map(#payload_name::from));)*
+	}
+}
+
+/// Builds the code that starts the actor's successor, if it has handed off to one, and passes on everything it had not handled and everything sent to it afterwards, until all of its handles have dropped or it is told to stop.
+///
+/// The successor is a child of the actor, so is stopped afterwards, and its panic, if it panics, becomes the actor's own.
+fn create_hand_over(payload_name: &TypePath, receivers: &[Ident]) -> TokenStream {
+	quote! {
+		// Being told to stop from outside takes precedence over handing off
+		let successor = if result.is_ok() && !stop_signal.is_stopping() {
+			context.start_successor(&mut state)
+		} else {
+			None
+		};
+		if let Some(pass_on) = successor {
+			let stash = context.take_stash();
+			let pending = ::std::iter::from_fn(|| context.take_unstashed())
+				.chain(leftovers.drain(..))
+				.chain(stash);
+			for mail in pending {
+				pass_on(mail);
+			}
+			loop {
+				let mail = select! {
+					biased;
+					() = stop_signal.stopping() => break,
+					#(Some(msg) = #receivers.recv() => #payload_name::from(msg),)*
+					() = sleep(IDLE_TIMEOUT) => {
+						if context.has_handles() {
+							continue;
+						}
+						break;
+					}
+				};
+				pass_on(mail);
+			}
+			// Anything that arrived just as the last handle dropped
+			if !stop_signal.is_stopping() {
+				#(
+					while let Some(msg) = RoleReceiver::try_recv(&mut #receivers) {
+						pass_on(#payload_name::from(msg));
+					}
+				)*
+			}
+			drop(pass_on);
+			context.stop_children().await;
+			if let Some(panic) = context.take_successor_panic() {
+				result = Err(panic);
+			}
+		}
+	}
+}
+
+/// Builds the shell, which only has a field for the state's type if the state is generic
+fn create_constructor(actor_name: &ActorName, actor_fields: &[Field]) -> Result<Expr> {
+	let state_type = (!actor_name.generics().params.is_empty())
+		.then(|| quote! { state_type: ::std::marker::PhantomData, });
+	fallible_quote! {
+		#actor_name {
+			this: weak.clone(),
+			lifecycle: Default::default(),
+			#state_type
+			#(#actor_fields),*
+		}
+	}
+}
+
+/// Builds the code that runs the actor's exit or panic handler, depending on whether the event loop in `result` panicked, and produces the actor's final output.
+///
+/// An actor that has handed off to a successor first passes on its messages until its handles have dropped.
+fn create_finish(
+	data_name: &DataName,
+	payload_name: &TypePath,
+	receivers: &[Ident],
+	handlers: &HandlerFunctions,
+) -> Result<TokenStream> {
+	let hand_over = create_hand_over(payload_name, receivers);
+	let collect_unhandled = create_collect_unhandled(data_name, payload_name, receivers);
+	let bury_unhandled = (!handlers.exit_takes_unhandled() && !handlers.panic_takes_unhandled())
+		.then(|| quote! { drop(unhandled); });
+
+	// Hooks that declare an extra argument are given the messages left unhandled
+	let panic_args = if handlers.panic_takes_unhandled() {
+		quote! { panic, unhandled }
+	} else {
+		quote! { panic }
+	};
+	let exit_args = handlers
+		.exit_takes_unhandled()
+		.then(|| quote! { unhandled });
+
+	let run_panic_handler: syn::Expr = handlers
+		.panic_name()
+		.map(|handler| fallible_quote! { state.#handler(#panic_args) })
+		.unwrap_or(fallible_quote! { panic })?;
+
+	let run_exit_handler: syn::Expr = handlers
+		.exit_name()
+		.map(|handler| fallible_quote! { state.#handler(#exit_args) })
+		.unwrap_or(fallible_quote! { () })?;
+
+	fallible_quote! {
+		#hand_over
+
+		#collect_unhandled
+		#bury_unhandled
+
+		let outcome = match result {
+			Ok(_) => { Ok(#run_exit_handler) },
+			Err(panic) => Err(#run_panic_handler)
+		};
+
+		// Anything monitoring the actor is told once it has completely finished
+		context.terminate(outcome.is_err());
+		outcome
+	}
+}
+
+/// Builds the statements that create each role's queue, and the actor fields that hold the sending ends.
+fn create_queues(
+	performances: &[PerformanceDecl],
+	output_field_names: &[Ident],
+) -> Result<(Vec<Stmt>, Vec<Field>)> {
+	let input_field_names = performances
+		.iter()
+		.map(|perf| format_ident!("{}_input", perf.role_name.queue_name()))
+		.collect_vec();
+
+	let queue_constructions = map_or_bail!(
+		izip!(performances, &input_field_names, output_field_names),
+		|(role, inn, out)| -> Result<Stmt> {
+			let role_name = &role.role_name;
+			fallible_quote! { let (#inn, mut #out) = <#role_name as ::shakespeare::Role>::Channel::new(Default::default()); }
+		}
+	);
+
+	let actor_fields = map_or_bail!(
+		izip!(performances, &input_field_names),
+		|(role, input)| -> Result<Field> {
+			let field_name = role.role_name.queue_name();
+			Field::parse_named.parse2(fallible_quote! {#field_name : #input}?)
+		}
+	);
+
+	Ok((queue_constructions, actor_fields))
+}
+
+/// Builds the statement that creates the queue shared by all of the roles, and the actor field that holds its sending end.
+fn create_mailbox(payload_name: &TypePath) -> Result<(Vec<Stmt>, Vec<Field>)> {
+	let construction = fallible_quote! {
+		let (mailbox_input, mut mailbox) = <::shakespeare::PriorityUnbounded<#payload_name> as Channel>::new(());
+	}?;
+	let field = Field::parse_named.parse2(fallible_quote! { mailbox: mailbox_input }?)?;
+	Ok((vec![construction], vec![field]))
+}
+
+/// Builds the statement that creates the queue for closures sent with `with_state`, the actor field that holds its sending end, and the `select!` branch that takes closures from it.
+fn create_inspections(
+	data_name: &DataName,
+	payload_name: &TypePath,
+) -> Result<(Stmt, Field, TokenStream)> {
+	let construction = fallible_quote! {
+		let (inspections_input, mut inspections_output) = <::shakespeare::TokioUnbounded<::shakespeare::Inspection<#data_name>> as Channel>::new(());
+	}?;
+	let field = Field::parse_named.parse2(fallible_quote! { inspections: inspections_input }?)?;
+	let branch = fallible_quote! {
+		Some(inspection) = inspections_output.recv(), if !(inspections_output.is_empty()) || context.is_running() => {
+			#payload_name::from(inspection)
+		}
+	}?;
+	Ok((construction, field, branch))
+}
+
+/// Builds a `select!` branch for each role's queue, which produces the next message, or batch of messages, from it.
+///
+/// The branches are in order of their role's priority, which is the order they are checked in if any role has one.
+fn create_select_branches(
+	data_name: &DataName,
+	payload_name: &TypePath,
+	performances: &[PerformanceDecl],
+	output_field_names: &[Ident],
+) -> Result<Vec<TokenStream>> {
+	let branches = map_or_bail!(
+		izip!(performances, output_field_names),
+		|(perf, output)| -> Result<TokenStream> {
+			let take_run = receive_run(data_name, payload_name, perf.get_role_name(), |wanted| {
+				quote! {
+					if !#output.is_empty() {
+						#output.recv_many(&mut received, #wanted).await;
+					}
+				}
+			});
+			fallible_quote! {
+				Some(msg) = #output.recv(), if !(#output.is_empty()) || context.is_running() => {
+					#take_run
+				}
+			}
+		}
+	);
+	Ok(izip!(performances, branches)
+		.sorted_by_key(|(perf, _)| Reverse(perf.priority()))
+		.map(|(_, branch)| branch)
+		.collect())
+}
+
+/// Builds the `select!` branch for an actor whose roles share one queue, which produces the next message, or batch of messages, from it.
+fn create_mailbox_branch(
+	data_name: &DataName,
+	payload_name: &TypePath,
+	performances: &[PerformanceDecl],
+) -> Result<TokenStream> {
+	let variants = performances
+		.iter()
+		.map(|perf| perf.role_name.variant_name())
+		.collect_vec();
+	let take_runs = performances.iter().map(|perf| {
+		let variant = perf.role_name.variant_name();
+		// A batch can only continue while the waiting messages are for the same role
+		receive_run(data_name, payload_name, perf.get_role_name(), |wanted| {
+			quote! {
+				mailbox.recv_run(&mut received, #wanted, |mail| match mail {
+					#payload_name::#variant(msg) => Ok(msg),
+					other => Err(other),
+				});
+			}
+		})
+	});
+	fallible_quote! {
+		Some(mail) = mailbox.recv(), if !(mailbox.is_empty()) || context.is_running() => {
+			match mail {
+				#(#payload_name::#variants(msg) => { #take_runs })*
+				batch => batch,
+			}
+		}
+	}
+}
+
+/// Builds the code that takes any further calls that should be handled along with `msg`, just received for the given role, and produces the payload to handle next.
+///
+/// `take_more` builds the code that receives up to the given number of further calls for the role into `received`, if they are waiting.
+fn receive_run(
+	data_name: &DataName,
+	payload_name: &TypePath,
+	role_name: &RoleName,
+	take_more: impl Fn(TokenStream) -> TokenStream,
+) -> TokenStream {
+	let batch_limit_name = role_name.batch_limit_name();
+	let batch_variant = role_name.batch_variant_name();
+	let take_window = take_more(quote! { CONFLATE_WINDOW - 1 });
+	let take_batch = take_more(quote! { limit - 1 });
+	quote! {
+		let conflates = <#role_name as ::shakespeare::Role>::conflates(&msg.payload);
+		if !conflates && #data_name::#batch_limit_name(&msg).is_none() {
+			// Most calls are handled on their own, which needs nothing more from the queue
+			#payload_name::from(msg)
+		} else {
+			let mut received = vec![msg];
+			if conflates {
+				// Any later call to the same method that is already waiting supersedes this one
+				#take_window
+				discard_superseded(&mut received, &*context.get_shell());
+			} else if let Some(limit) = #data_name::#batch_limit_name(&received[0]) {
+				if limit > 1 {
+					#take_batch
+				}
+			}
+			// Anything received beyond the next message or batch is handled straight afterwards
+			match #data_name::#batch_limit_name(&received[0]) {
+				Some(limit) => {
+					let rest = split_batch(&mut received, limit);
+					leftovers.extend(rest.into_iter().map(#payload_name::from));
+					#payload_name::#batch_variant(received)
+				}
+				None => {
+					let rest = received.split_off(1);
+					leftovers.extend(rest.into_iter().map(#payload_name::from));
+					#payload_name::from(received.remove(0))
+				}
+			}
+		}
+	}
+}
+
+/// Builds the expressions that take the next message that has already been taken out of the queues, if there is one: the first from the leftovers, and the second from the unstashed messages and then the leftovers.
+///
+/// With priorities, a message waiting in the queue of a role with a higher priority than the next message's role goes first, so nothing is taken while there is one.
+fn create_take_pending(
+	payload_name: &TypePath,
+	receivers: &[Ident],
+	priorities: &[i32],
+	ranked: bool,
+) -> (TokenStream, TokenStream) {
+	let overtakes = izip!(receivers, priorities)
+		.map(|(receiver, priority)| quote! { (#priority > priority && !#receiver.is_empty()) })
+		.collect_vec();
+	let take_pending = |peek: TokenStream, take: TokenStream| {
+		if ranked {
+			quote! {
+				match #peek {
+					Some(priority) if #(#overtakes)||* => None,
+					_ => #take,
+				}
+			}
+		} else {
+			take
+		}
+	};
+	(
+		take_pending(
+			quote! { leftovers.front().map(#payload_name::role_priority) },
+			quote! { leftovers.pop_front() },
+		),
+		take_pending(
+			quote! { context.peek_unstashed(#payload_name::role_priority).or_else(|| leftovers.front().map(#payload_name::role_priority)) },
+			quote! { context.take_unstashed().or_else(|| leftovers.pop_front()) },
+		),
+	)
+}
+
+/// Builds the code that handles a single message after it has been taken out of its queue, using `take_leftover` to take the next of any leftovers while shared handlers are running.
+fn create_mail_handler(
+	select_branches: &[TokenStream],
+	bias: Option<&TokenStream>,
+	take_leftover: &TokenStream,
+	concurrent_reads: bool,
+) -> Result<TokenStream> {
+	if concurrent_reads {
+		fallible_quote! {
+			if mail.is_shared() {
+				// Shared handlers run alongside each other until a message arrives that needs exclusive access,
+				// which waits for all of them to finish before it is handled
+				let exclusive = {
+					let mut readers = FuturesUnordered::new();
+					readers.push(mail.dispatch_shared(&state, &context));
+					loop {
+						// Unstashed messages have to be taken out of the context, which needs the readers to finish first
+						if context.has_unstashed() {
+							while readers.next().await.is_some() {}
+							break None;
+						}
+						let mail = if let Some(mail) = #take_leftover { mail } else { select! {
+							#bias
+							// Polling this is what runs the readers, so it has to be a branch, and is checked before the queues if they are checked in order
+							Some(()) = readers.next() => {
+								if readers.is_empty() {
+									break None;
+								}
+								continue;
+							},
+							#(#select_branches,)*
+							else => { break None; }
+						}};
+						idle.reset();
+						if mail.is_shared() {
+							readers.push(mail.dispatch_shared(&state, &context));
+						} else {
+							while readers.next().await.is_some() {}
+							break Some(mail);
+						}
+					}
+				};
+				if let Some(mail) = exclusive {
+					mail.dispatch(&mut state, &mut context).await;
+				}
+			} else {
+				mail.dispatch(&mut state, &mut context).await;
+			}
+		}
+	} else {
+		fallible_quote! { mail.dispatch(&mut state, &mut context).await; }
+	}
+}
+
 impl ToTokens for SpawningFunction {
 	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
 		self.fun.to_tokens(tokens);
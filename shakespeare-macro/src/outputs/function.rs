@@ -0,0 +1,72 @@
+use quote::{ToTokens, format_ident, quote};
+use syn::{Error, FnArg, ItemFn, PatType, Result, Type, TypeReference};
+
+use crate::data::SignatureExt;
+use crate::macros::fallible_quote;
+
+/// An actor made from a single function, which becomes a function that takes the initial state and starts the actor with `spawn_fn`
+#[derive(Debug)]
+pub(crate) struct FunctionOutput {
+	fun: ItemFn,
+}
+
+impl FunctionOutput {
+	pub(crate) fn new(mut handler: ItemFn) -> Result<FunctionOutput> {
+		let sig = &handler.sig;
+		if !sig.generics.params.is_empty() {
+			return Err(Error::new_spanned(
+				&sig.generics,
+				"Generic actor functions are not supported",
+			));
+		}
+		let (state_type, message_type) = match sig.inputs.iter().collect::<Vec<_>>()[..] {
+			[
+				FnArg::Typed(PatType { ty: state, .. }),
+				FnArg::Typed(PatType { ty: message, .. }),
+			] => match &**state {
+				Type::Reference(TypeReference {
+					mutability: Some(_),
+					elem,
+					..
+				}) => ((**elem).clone(), (**message).clone()),
+				_ => {
+					return Err(Error::new_spanned(
+						state,
+						"The state must be taken as `&mut`",
+					));
+				}
+			},
+			_ => {
+				return Err(Error::new_spanned(
+					sig,
+					"An actor function must take `&mut` its state and a message",
+				));
+			}
+		};
+		let reply_type = sig.extract_return_type();
+		let asyncness = sig.asyncness.is_some().then_some(quote!(.await));
+
+		let name = std::mem::replace(&mut handler.sig.ident, format_ident!("handler"));
+		let vis = std::mem::replace(&mut handler.vis, syn::Visibility::Inherited);
+		let attrs = std::mem::take(&mut handler.attrs);
+
+		let fun = fallible_quote! {
+			#(#attrs)*
+			#vis fn #name(state: #state_type) -> ::shakespeare::ActorHandles<::shakespeare::FnActor<#state_type, #message_type, #reply_type>> {
+				#handler
+				::shakespeare::spawn_fn(state, |mut state: #state_type, msg: #message_type| async move {
+					let reply = handler(&mut state, msg)#asyncness;
+					(reply, state)
+				})
+			}
+		}?;
+
+		Ok(FunctionOutput { fun })
+	}
+}
+
+impl ToTokens for FunctionOutput {
+	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+		self.fun.to_tokens(tokens);
+	}
+}
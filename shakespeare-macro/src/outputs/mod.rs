@@ -1,7 +1,9 @@
 mod actor;
+mod function;
 mod performance;
 mod role;
 
 pub(crate) use actor::ActorOutput;
+pub(crate) use function::FunctionOutput;
 pub(crate) use performance::PerfDispatch;
 pub(crate) use role::RoleOutput;
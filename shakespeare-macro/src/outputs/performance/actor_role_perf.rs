@@ -1,67 +1,102 @@
 use itertools::Itertools;
-use quote::ToTokens;
-use syn::{FnArg, ItemImpl, Path, Result, ReturnType, Signature, parse_quote};
+use quote::{ToTokens, quote};
+use syn::{
+	FnArg, Ident, ItemImpl, Path, Result, ReturnType, Signature, Type, Visibility, parse_quote,
+};
 
 use crate::data::{ActorName, FunctionItem, RoleName, SignatureExt};
-use crate::macros::{fallible_quote, filter_unwrap, map_or_bail};
+use crate::macros::{fallible_quote, filter_unwrap};
 
 #[derive(Debug)]
 pub(crate) struct ActorPerf {
-	imp: ItemImpl,
+	imp:      ItemImpl,
+	performs: ItemImpl,
 }
 
 impl ActorPerf {
 	pub(crate) fn new(
 		actor_path: &ActorName,
-		payload_type: &Path,
 		role_name: &RoleName,
-		handlers: &[FunctionItem],
+		message: Option<&Type>,
 	) -> Result<ActorPerf> {
-		let sending_methods = map_or_bail!(handlers, |fun| create_sending_method(
-			payload_type,
-			fun,
-			role_name
-		));
+		// The role's own methods are provided by the role, in terms of `get_role`
+		let role_methods: FunctionItem = if let Some(message) = message {
+			fallible_quote! {
+				fn handle(&self, msg: #message) -> ::shakespeare::Envelope<#role_name, <#message as ::shakespeare::Request>::Reply> {
+					::shakespeare::Envelope::new(msg, self.get_shell())
+				}
+			}?
+		} else {
+			fallible_quote! {
+				#[doc(hidden)]
+				fn get_role(&self) -> ::std::sync::Arc<#role_name> {
+					self.get_shell()
+				}
+			}?
+		};
 
 		let sender_name = role_name.sender_method_name();
+		let try_sender_name = role_name.try_sender_method_name();
+
+		let trait_path = role_name.trait_path();
+		let assoc_types = role_name
+			.assoc_bindings()
+			.into_iter()
+			.map(|(ident, ty)| quote!(type #ident = #ty;));
 
+		let (impl_generics, _, where_clause) = actor_path.generics().split_for_impl();
 		let imp = fallible_quote! {
-			#[::shakespeare::async_trait_export::async_trait] // Can't be removed because it makes the trait not obj-safe
-			impl #role_name for #actor_path {
-				#(#sending_methods)*
+			impl #impl_generics #trait_path for #actor_path #where_clause {
+				#(#assoc_types)*
+				#role_methods
 				#[doc(hidden)]
-				async fn enqueue(&self, val: ::shakespeare::ReturnEnvelope<dyn #role_name>) -> Result<(), ::shakespeare::Role2SendError<dyn #role_name>>{
-					self.#sender_name(val).await
+				fn enqueue(&self, val: ::shakespeare::ReturnEnvelope<#role_name>) -> Result<(), ::shakespeare::Role2SendError<#role_name>>{
+					self.#sender_name(val)
+				}
+				#[doc(hidden)]
+				fn try_enqueue(&self, val: ::shakespeare::ReturnEnvelope<#role_name>) -> Result<(), ::shakespeare::TrySendError<::shakespeare::ReturnEnvelope<#role_name>>> {
+					self.#try_sender_name(val)
 				}
 			}
 		}?;
 
-		Ok(ActorPerf { imp })
+		let performs = fallible_quote! {
+			impl #impl_generics ::shakespeare::Performs<#role_name> for #actor_path #where_clause {
+				fn into_role(self: ::std::sync::Arc<Self>) -> ::std::sync::Arc<#role_name> {
+					self
+				}
+				fn try_enqueue(&self, val: ::shakespeare::ReturnEnvelope<#role_name>) -> Result<(), ::shakespeare::TrySendError<::shakespeare::ReturnEnvelope<#role_name>>> {
+					self.#try_sender_name(val)
+				}
+			}
+		}?;
+
+		Ok(ActorPerf { imp, performs })
 	}
 }
 
 impl ToTokens for ActorPerf {
 	fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
 		self.imp.to_tokens(tokens);
+		self.performs.to_tokens(tokens);
 	}
 }
 
-fn create_sending_method(
+/// Builds an inherent method of the actor shell, called `name`, that packs a call into an [`Envelope`] that sends to the shell directly
+pub(crate) fn create_sending_method(
 	payload_type: &Path,
 	fun: &FunctionItem,
+	name: &Ident,
 	role_name: &RoleName,
+	actor_name: &ActorName,
+	vis: &Visibility,
 ) -> Result<FunctionItem> {
 	let attributes = fun.attrs.iter();
 
 	let mut sig = fun.sig.clone();
 
 	sig.remove_context_param();
-	let Signature {
-		ident,
-		inputs,
-		output,
-		..
-	} = &sig;
+	let Signature { inputs, output, .. } = &sig;
 	let params = filter_unwrap!(inputs, FnArg::Typed).collect_vec();
 	let patterns = params.iter().map(|t| &(*t.pat)).collect_vec();
 	let variant_name = fun.sig.enum_variant_name();
@@ -72,16 +107,14 @@ fn create_sending_method(
 		parse_quote!(())
 	};
 
-	let fn_block = fallible_quote! {
+	fallible_quote! {
 		#[allow(unused_parens)]
 		#[allow(dead_code)]
 		#(#attributes)*
-		fn #ident(&self, #(#params),*) -> ::shakespeare::Envelope<dyn #role_name, #return_type> {
+		#vis fn #name(self: &::std::sync::Arc<Self>, #(#params),*) -> ::shakespeare::Envelope<#role_name, #return_type, #actor_name> {
 			let msg = (#(#patterns),*);
 			let payload = #payload_type::#variant_name(msg);
-			::shakespeare::Envelope::new(payload, self.get_shell())
+			::shakespeare::Envelope::new(payload, ::std::sync::Arc::clone(self))
 		}
-	}?;
-
-	Ok(fn_block)
+	}
 }
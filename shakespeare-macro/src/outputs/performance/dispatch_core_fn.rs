@@ -2,16 +2,23 @@ use convert_case::Case::Snake;
 use convert_case::Casing;
 use itertools::Itertools;
 use proc_macro2::TokenStream;
-use quote::{ToTokens, format_ident, quote};
+use quote::{ToTokens, format_ident, quote, quote_spanned};
+use syn::fold::Fold;
 use syn::parse::Parser;
-use syn::{Arm, Attribute, Expr, Ident, ItemImpl, Path, Result};
+use syn::spanned::Spanned;
+use syn::{
+	Arm, Attribute, Expr, GenericParam, Generics, Ident, ImplItem, Item, ItemImpl, LitInt, Pat,
+	Path, Result, ReturnType, Type, Visibility, WherePredicate, parse_quote,
+};
 
 use crate::data::{DataName, FunctionItem, MethodName, PayloadPath, RoleName, SignatureExt};
-use crate::macros::{fallible_quote, map_or_bail};
+use crate::declarations::{PerformanceDecl, vec_item_type};
+use crate::interfacerewriter::BlanketSignature;
+use crate::macros::{fallible_quote, filter_unwrap, map_or_bail};
 
 #[derive(Debug)]
 pub(crate) struct DispatchFunction {
-	fun: ItemImpl,
+	fun: Item,
 }
 
 impl DispatchFunction {
@@ -20,44 +27,147 @@ impl DispatchFunction {
 		role_name: &RoleName,
 		payload_type: &PayloadPath,
 		dispatch_method_name: &MethodName,
-		handlers: &[FunctionItem],
+		perf: &PerformanceDecl,
 	) -> Result<DispatchFunction> {
-		let hide_doc: Attribute = Attribute::parse_outer
-			.parse2(quote!(#[doc(hidden)]))?
-			.pop()
-			.unwrap();
+		let methods = dispatch_methods(role_name, payload_type, dispatch_method_name, perf)?;
 
-		let dispatch_with_payload = |fun| dispatch_case(role_name, payload_type, fun);
+		let (impl_generics, _, where_clause) = data_name.generics().split_for_impl();
+		let fun = fallible_quote! {
+			impl #impl_generics #data_name #where_clause {
+				#(#methods)*
+			}
+		}?;
 
-		let arms: Vec<_> = map_or_bail!(&handlers, dispatch_with_payload);
+		Ok(DispatchFunction { fun })
+	}
 
-		let renamed_handlers = handlers
+	/// For a performance written for any state type `S` meeting some bounds, the same functions are given to `S` through a private trait, and handed to actors through the role's [`Performance`] for `S`
+	pub(crate) fn blanket(
+		generics: &Generics,
+		state_param: &Ident,
+		role_name: &RoleName,
+		payload_type: &PayloadPath,
+		dispatch_method_name: &MethodName,
+		perf: &PerformanceDecl,
+	) -> Result<DispatchFunction> {
+		let mut methods = dispatch_methods(role_name, payload_type, dispatch_method_name, perf)?;
+		for method in &mut methods {
+			method.vis = Visibility::Inherited;
+		}
+		let mut rewriter = BlanketSignature::new(state_param);
+		let declarations = methods
 			.iter()
-			.map(|h| {
-				let mut h = h.clone();
-				h.sig.ident = make_method_name(role_name, &h.sig.ident);
-				h.attrs.push(hide_doc.clone());
-				h
-			})
-			.collect_vec();
+			.map(|method| rewriter.fold_signature(method.sig.clone()));
+
+		// The trait has the performance's parameters other than the state, which is `Self`
+		let mut trait_generics = generics.clone();
+		trait_generics.params = generics
+			.params
+			.iter()
+			.filter(|param| !matches!(param, GenericParam::Type(ty) if &ty.ident == state_param))
+			.cloned()
+			.collect();
+		// Bounds on the state itself are left to the impl, and anywhere else it is named it becomes `Self`
+		if let Some(where_clause) = &mut trait_generics.where_clause {
+			where_clause.predicates = where_clause
+				.predicates
+				.iter()
+				.filter(|pred| !matches!(pred, WherePredicate::Type(ty) if matches!(&ty.bounded_ty, Type::Path(p) if p.path.is_ident(state_param))))
+				.map(|pred| BlanketSignature::new(state_param).fold_where_predicate(pred.clone()))
+				.collect();
+		}
+		let (_, trait_args, trait_where) = trait_generics.split_for_impl();
+
+		// Stashing needs the actor's payload to be able to hold the role's messages, which any actor performing the role can
+		let mut impl_generics = generics.clone();
+		impl_generics.make_where_clause().predicates.extend::<[WherePredicate; 2]>([
+			parse_quote!(#state_param: ::shakespeare::ActorState),
+			parse_quote!(<#state_param as ::shakespeare::ActorState>::Payload: ::std::convert::From<::shakespeare::ReturnEnvelope<#role_name>>),
+		]);
+		let (impl_generics, _, impl_where) = impl_generics.split_for_impl();
+
+		let batch_method_name = role_name.batch_method_name();
+		let batch_limit_name = role_name.batch_limit_name();
+		let shared_method_name = role_name.shared_method_name();
+		let shared_check_name = role_name.shared_check_name();
+		let dispatch = quote!(<#state_param as Dispatch #trait_args>);
+
+		let fun = fallible_quote! {
+			const _: () = {
+				trait Dispatch #trait_generics: ::shakespeare::ActorState + Sized #trait_where {
+					#(#declarations;)*
+				}
+
+				impl #impl_generics Dispatch #trait_args for #state_param #impl_where {
+					#(#methods)*
+				}
+
+				impl #impl_generics ::shakespeare::Performance<#state_param> for #role_name #impl_where {
+					fn dispatch(state: &mut #state_param, context: &mut ::shakespeare::Context<#state_param>, msg: ::shakespeare::ReturnEnvelope<Self>) -> impl ::std::future::Future<Output = ()> {
+						#dispatch::#dispatch_method_name(state, context, msg)
+					}
+					fn is_shared(msg: &::shakespeare::ReturnEnvelope<Self>) -> bool {
+						#dispatch::#shared_check_name(msg)
+					}
+					fn dispatch_shared(state: &#state_param, context: &::shakespeare::Context<#state_param>, msg: ::shakespeare::ReturnEnvelope<Self>) -> impl ::std::future::Future<Output = ()> {
+						#dispatch::#shared_method_name(state, context, msg)
+					}
+					fn batch_limit(msg: &::shakespeare::ReturnEnvelope<Self>) -> Option<usize> {
+						#dispatch::#batch_limit_name(msg)
+					}
+					fn dispatch_batch(state: &mut #state_param, context: &mut ::shakespeare::Context<#state_param>, msgs: Vec<::shakespeare::ReturnEnvelope<Self>>) -> impl ::std::future::Future<Output = ()> {
+						#dispatch::#batch_method_name(state, context, msgs)
+					}
+				}
+			};
+		}?;
+
+		Ok(DispatchFunction { fun })
+	}
+
+	/// An actor that `performs` a role hands its messages to the role's [`Performance`] for its state, under the names the rest of the actor uses for any performance
+	pub(crate) fn forwarding(
+		data_name: &DataName,
+		role_name: &RoleName,
+		dispatch_method_name: &MethodName,
+	) -> Result<DispatchFunction> {
+		let batch_method_name = role_name.batch_method_name();
+		let batch_limit_name = role_name.batch_limit_name();
+		let shared_method_name = role_name.shared_method_name();
+		let shared_check_name = role_name.shared_check_name();
+		let performance = quote!(<#role_name as ::shakespeare::Performance<Self>>);
 
+		let (impl_generics, _, where_clause) = data_name.generics().split_for_impl();
 		let fun = fallible_quote! {
-			impl #data_name {
+			impl #impl_generics #data_name #where_clause {
+				#[doc(hidden)]
+				pub async fn #dispatch_method_name(&mut self, context: &mut ::shakespeare::Context<Self>, msg: ::shakespeare::ReturnEnvelope<#role_name>) {
+					#performance::dispatch(self, context, msg).await;
+				}
+
+				#[doc(hidden)]
+				#[allow(dead_code)]
+				pub fn #shared_check_name(msg: &::shakespeare::ReturnEnvelope<#role_name>) -> bool {
+					#performance::is_shared(msg)
+				}
+
+				#[doc(hidden)]
+				#[allow(dead_code)]
+				pub async fn #shared_method_name(&self, context: &::shakespeare::Context<Self>, msg: ::shakespeare::ReturnEnvelope<#role_name>) {
+					#performance::dispatch_shared(self, context, msg).await;
+				}
+
 				#[doc(hidden)]
-				pub async fn #dispatch_method_name(&mut self, #[allow(unused_variables)] context: &mut ::shakespeare::Context<Self>, msg: ::shakespeare::ReturnEnvelope<dyn #role_name>)  {
-					#[allow(unused_variables)]
-					let ::shakespeare::ReturnEnvelope { payload, return_path } = msg;
-
-					#[allow(unused_variables)]
-					#[allow(unused_parens)]
-					#[allow(unreachable_code)]
-					let return_val = match payload {
-						#(#arms),*
-					};
-					return_path.send(return_val).await;
+				#[allow(dead_code)]
+				pub fn #batch_limit_name(msg: &::shakespeare::ReturnEnvelope<#role_name>) -> Option<usize> {
+					#performance::batch_limit(msg)
 				}
 
-				#(#renamed_handlers)*
+				#[doc(hidden)]
+				#[allow(dead_code)]
+				pub async fn #batch_method_name(&mut self, context: &mut ::shakespeare::Context<Self>, msgs: Vec<::shakespeare::ReturnEnvelope<#role_name>>) {
+					#performance::dispatch_batch(self, context, msgs).await;
+				}
 			}
 		}?;
 
@@ -71,25 +181,400 @@ impl ToTokens for DispatchFunction {
 	}
 }
 
+/// The dispatch function, with the shared and batch functions and the renamed handlers that it calls
+fn dispatch_methods(
+	role_name: &RoleName,
+	payload_type: &PayloadPath,
+	dispatch_method_name: &MethodName,
+	perf: &PerformanceDecl,
+) -> Result<Vec<FunctionItem>> {
+	let mut arms = vec![];
+	let mut shared_arms = vec![];
+	let mut shared_patterns = vec![];
+
+	let mut batch_arms = vec![];
+	let mut batched_variants = vec![];
+	let mut batch_limits = vec![];
+
+	for method in perf.methods() {
+		let candidates = method_candidates(role_name, perf, &method.sig.ident);
+		if let Some(limit) = perf.batch_limit(&method.sig.ident) {
+			batch_arms.push(batch_case(role_name, payload_type, &method, &candidates)?);
+			batched_variants.push(method.sig.enum_variant_name());
+			batch_limits.push(limit);
+			continue;
+		}
+		let stashable = perf.is_stashable(&method.sig.ident);
+		let method_arms = map_or_bail!(&candidates, |(guard, fun, fn_name)| dispatch_case(
+			role_name,
+			payload_type,
+			fun,
+			fn_name,
+			*guard,
+			perf.message.as_ref(),
+			stashable
+		));
+		// A message that arrives in a phase without a handler for it is turned down
+		let rejection: Option<Arm> = if candidates.iter().any(|(guard, ..)| guard.is_none()) {
+			None
+		} else {
+			let variant_name = method.sig.enum_variant_name();
+			let arm: Arm = fallible_quote! {
+				#payload_type::#variant_name(..) => return return_path.out_of_phase()
+			}?;
+			Some(arm)
+		};
+
+		// Stashing needs the exclusive `Context`
+		if !stashable
+			&& candidates
+				.iter()
+				.all(|(_, fun, _)| fun.sig.has_shared_receiver())
+		{
+			// A handler's payload is the message itself, so every message is shared
+			if perf.message.is_some() {
+				shared_patterns.push(quote!(_));
+			} else {
+				let variant_name = method.sig.enum_variant_name();
+				shared_patterns.push(quote!(#payload_type::#variant_name(..)));
+			}
+			shared_arms.extend(method_arms.iter().cloned());
+			shared_arms.extend(rejection.clone());
+		}
+		arms.extend(method_arms);
+		arms.extend(rejection);
+	}
+
+	let batch_method_name = role_name.batch_method_name();
+	let batch_limit_name = role_name.batch_limit_name();
+
+	// Batched calls are always handled by the batch function, even if there is only one waiting
+	let redirect_batches = (!batched_variants.is_empty()).then_some(quote! {
+		if Self::#batch_limit_name(&msg).is_some() {
+			return self.#batch_method_name(context, vec![msg]).await;
+		}
+	});
+	if !batched_variants.is_empty() {
+		arms.push(fallible_quote! { _ => unreachable!() }?);
+	}
+
+	let renamed_handlers = rename_handlers(role_name, perf)?;
+	let shared_functions = shared_functions(role_name, &shared_arms, &shared_patterns);
+	let batch_functions = batch_functions(
+		role_name,
+		payload_type,
+		&batch_arms,
+		&batched_variants,
+		&batch_limits,
+	);
+
+	// Parsed as an impl only to split them into methods
+	let methods: ItemImpl = fallible_quote! {
+		impl Methods {
+			#[doc(hidden)]
+			pub async fn #dispatch_method_name(&mut self, #[allow(unused_variables)] context: &mut ::shakespeare::Context<Self>, msg: ::shakespeare::ReturnEnvelope<#role_name>)  {
+				#redirect_batches
+				#[allow(unused_variables)]
+				let ::shakespeare::ReturnEnvelope { payload, return_path, priority } = msg;
+
+				#[allow(unused_variables)]
+				#[allow(unused_parens)]
+				#[allow(unreachable_code)]
+				let return_val = match payload {
+					#(#arms),*
+				};
+				return_path.send(return_val);
+			}
+
+			#shared_functions
+
+			#batch_functions
+
+			#(#renamed_handlers)*
+		}
+	}?;
+
+	Ok(filter_unwrap!(methods.items, ImplItem::Fn).collect())
+}
+
+/// The handlers from each of the performances, renamed so that they cannot clash with each other or the state's own methods
+fn rename_handlers(role_name: &RoleName, perf: &PerformanceDecl) -> Result<Vec<FunctionItem>> {
+	let hide_doc: Attribute = Attribute::parse_outer
+		.parse2(quote!(#[doc(hidden)]))?
+		.pop()
+		.unwrap();
+
+	let phase_handlers = perf.phases.iter().enumerate().flat_map(|(n, phase)| {
+		phase
+			.handlers
+			.iter()
+			.map(move |h| (make_phase_method_name(role_name, &h.sig.ident, n), h))
+	});
+	Ok(perf
+		.handlers
+		.iter()
+		.map(|h| (make_method_name(role_name, &h.sig.ident), h))
+		.chain(phase_handlers)
+		.map(|(name, h)| {
+			let mut h = h.clone();
+			h.sig.ident = name;
+			h.attrs.push(hide_doc.clone());
+			h
+		})
+		.collect_vec())
+}
+
+/// The functions used by the event loop in `concurrent_reads` mode to find out whether a message only needs shared access and to handle it
+fn shared_functions(
+	role_name: &RoleName,
+	shared_arms: &[Arm],
+	shared_patterns: &[TokenStream],
+) -> TokenStream {
+	let shared_method_name = role_name.shared_method_name();
+	let shared_check_name = role_name.shared_check_name();
+	quote! {
+		#[doc(hidden)]
+		#[allow(dead_code)]
+		pub fn #shared_check_name(msg: &::shakespeare::ReturnEnvelope<#role_name>) -> bool {
+			#[allow(unreachable_patterns)]
+			match &msg.payload {
+				#(#shared_patterns => true,)*
+				_ => false
+			}
+		}
+
+		#[doc(hidden)]
+		#[allow(dead_code)]
+		#[allow(unreachable_code)]
+		pub async fn #shared_method_name(&self, #[allow(unused_variables)] context: &::shakespeare::Context<Self>, msg: ::shakespeare::ReturnEnvelope<#role_name>)  {
+			#[allow(unused_variables)]
+			let ::shakespeare::ReturnEnvelope { payload, return_path, .. } = msg;
+
+			#[allow(unused_variables)]
+			#[allow(unused_parens)]
+			#[allow(unreachable_patterns)]
+			let return_val = match payload {
+				#(#shared_arms,)*
+				_ => unreachable!("Exclusive message dispatched as shared in role {}", ::std::any::type_name::<#role_name>())
+			};
+			return_path.send(return_val);
+		}
+	}
+}
+
+/// The functions used by the event loop to find out whether a message can be batched and to handle a batch
+fn batch_functions(
+	role_name: &RoleName,
+	payload_type: &Path,
+	batch_arms: &[Arm],
+	batched_variants: &[Ident],
+	batch_limits: &[&LitInt],
+) -> TokenStream {
+	let batch_method_name = role_name.batch_method_name();
+	let batch_limit_name = role_name.batch_limit_name();
+	quote! {
+		#[doc(hidden)]
+		#[allow(dead_code)]
+		pub fn #batch_limit_name(msg: &::shakespeare::ReturnEnvelope<#role_name>) -> Option<usize> {
+			#[allow(unreachable_patterns)]
+			match &msg.payload {
+				#(#payload_type::#batched_variants(..) => Some(#batch_limits),)*
+				_ => None
+			}
+		}
+
+		#[doc(hidden)]
+		#[allow(dead_code)]
+		#[allow(unreachable_code)]
+		pub async fn #batch_method_name(&mut self, #[allow(unused_variables)] context: &mut ::shakespeare::Context<Self>, msgs: Vec<::shakespeare::ReturnEnvelope<#role_name>>) {
+			let Some(first) = msgs.first() else {
+				return;
+			};
+
+			#[allow(unreachable_patterns)]
+			match &first.payload {
+				#(#batch_arms,)*
+				_ => unreachable!("Unbatched message dispatched as batch in role {}", ::std::any::type_name::<#role_name>())
+			}
+		}
+	}
+}
+
 fn make_method_name(role_name: &RoleName, method_name: &Ident) -> Ident {
-	let new = format!("{}_{}", role_name.path_leaf(), method_name).to_case(Snake);
+	let new = format!(
+		"{}_{}",
+		role_name.snake_leaf(),
+		method_name.to_string().to_case(Snake)
+	);
 	format_ident!("{}", new)
 }
 
-fn dispatch_case(role_name: &RoleName, payload_type: &Path, fun: &FunctionItem) -> Result<Arm> {
+fn make_phase_method_name(role_name: &RoleName, method_name: &Ident, phase: usize) -> Ident {
+	let method_name = method_name.to_string().to_case(Snake);
+	let new = format!("{}_{method_name}_phase_{phase}", role_name.snake_leaf());
+	format_ident!("{}", new)
+}
+
+/// The handlers that might handle a message for the given method, in the order they should be tried, with the pattern the state must match for each
+fn method_candidates<'a>(
+	role_name: &RoleName,
+	perf: &'a PerformanceDecl,
+	method_name: &Ident,
+) -> Vec<(Option<&'a Pat>, &'a FunctionItem, Ident)> {
+	let phases = perf.phases.iter().enumerate().flat_map(|(n, phase)| {
+		phase
+			.handlers
+			.iter()
+			.filter(|h| &h.sig.ident == method_name)
+			.map(move |h| {
+				let name = make_phase_method_name(role_name, method_name, n);
+				(Some(&phase.pattern), h, name)
+			})
+	});
+	let fallback = perf
+		.handlers
+		.iter()
+		.filter(|h| &h.sig.ident == method_name)
+		.map(|h| (None, h, make_method_name(role_name, method_name)));
+	phases.chain(fallback).collect()
+}
+
+/// Hands the inputs of every call in a batch to the handler at once, then sends each return value back to its own caller
+fn batch_case(
+	role_name: &RoleName,
+	payload_type: &Path,
+	method: &FunctionItem,
+	candidates: &[(Option<&Pat>, &FunctionItem, Ident)],
+) -> Result<Arm> {
+	let variant_name = method.sig.enum_variant_name();
+
+	let calls = candidates.iter().map(|(guard, fun, fn_name)| {
+		let context = fun.sig.has_context_input().then_some(quote!(context,));
+		let asyncness = fun.sig.asyncness.is_some().then_some(quote!(.await));
+		let guard = guard.map(|pattern| quote!(if matches!(self, #pattern)));
+		quote!(_ #guard => self.#fn_name(#context inputs)#asyncness)
+	});
+	let rejection = candidates
+		.iter()
+		.all(|(guard, ..)| guard.is_some())
+		.then_some(quote! {
+			_ => {
+				for return_path in return_paths {
+					return_path.out_of_phase();
+				}
+				return;
+			}
+		});
+
+	let returns_values = candidates.iter().any(
+		|(_, fun, _)| matches!(&fun.sig.output, ReturnType::Type(_, ty) if vec_item_type(ty).is_some()),
+	);
+	let (outputs, send) = if returns_values {
+		(
+			quote!(outputs),
+			quote! {
+				// Without one value for each call, no caller can be sure which value is its own
+				if outputs.len() == return_paths.len() {
+					for (output, return_path) in outputs.into_iter().zip(return_paths) {
+						return_path.send(<#role_name as ::shakespeare::Role>::Return::#variant_name(output));
+					}
+				} else {
+					for return_path in return_paths {
+						return_path.unmatched();
+					}
+				}
+			},
+		)
+	} else {
+		(
+			quote!(()),
+			quote! {
+				for return_path in return_paths {
+					return_path.send(<#role_name as ::shakespeare::Role>::Return::#variant_name(()));
+				}
+			},
+		)
+	};
+
+	fallible_quote! {
+		#payload_type::#variant_name(..) => {
+			let (inputs, return_paths): (Vec<_>, Vec<_>) = msgs
+				.into_iter()
+				.map(|msg| match msg.payload {
+					#payload_type::#variant_name(input) => (input, msg.return_path),
+					_ => unreachable!("Batch contains calls to more than one method"),
+				})
+				.unzip();
+			let #outputs = match () {
+				#(#calls,)*
+				#rejection
+			};
+			#send
+		}
+	}
+}
+
+fn dispatch_case(
+	role_name: &RoleName,
+	payload_type: &Path,
+	fun: &FunctionItem,
+	fn_name: &Ident,
+	guard: Option<&Pat>,
+	message: Option<&Type>,
+	stashable: bool,
+) -> Result<Arm> {
 	let payload_pattern = fun.sig.payload_pattern();
 	let method_call_pattern = fun.sig.method_call_pattern();
 
 	let variant_name = fun.sig.enum_variant_name();
 
-	let fn_name = make_method_name(role_name, &fun.sig.ident);
 	let asyncness: Option<TokenStream> = fun.sig.asyncness.is_some().then_some(quote!(.await));
 
+	let call: Expr = fallible_quote! { self.#fn_name(#(#method_call_pattern),*)#asyncness }?;
+
+	let guard = guard.map(|pattern| quote!(if matches!(self, #pattern)));
+
+	// A handler's payload is the message and its return is the reply, so neither is wrapped in an enum
+	if let Some(message) = message {
+		// Checked where the handler's return type is written, so that's where a mismatch is reported
+		let (written, span) = match &fun.sig.output {
+			ReturnType::Type(_, ty) => (ty.to_token_stream(), ty.span()),
+			ReturnType::Default => (quote!(()), fun.sig.ident.span()),
+		};
+		let reply = quote_spanned! {span=>
+			|reply: #written| -> <#message as ::shakespeare::Request>::Reply { reply }
+		};
+		return fallible_quote! {
+			(#(#payload_pattern),*) #guard => { (#reply)(#call) }
+		};
+	}
+
 	let into_call: Expr = fallible_quote! {
-			<dyn #role_name as ::shakespeare::Role>::Return::#variant_name( self.#fn_name(#(#method_call_pattern),*)#asyncness )
+			<#role_name as ::shakespeare::Role>::Return::#variant_name( #call )
 	}?;
 
+	// The handler gives its parameters back to stash them, so the message is rebuilt with the caller's return path
+	if stashable {
+		return fallible_quote! {
+			#payload_type::#variant_name ((#(#payload_pattern),*)) #guard => {
+				let (return_val, stashed) = {
+					let context = &mut ::shakespeare::StashContext::new(context);
+					(#into_call, context.take_stashed())
+				};
+				if let Some(params) = stashed {
+					context.keep_stashed::<#role_name>(::shakespeare::ReturnEnvelope {
+						payload: #payload_type::#variant_name(params),
+						return_path,
+						priority,
+					});
+					return;
+				}
+				return_val
+			}
+		};
+	}
+
 	fallible_quote! {
-		#payload_type::#variant_name ((#(#payload_pattern),*)) => { #into_call }
+		#payload_type::#variant_name ((#(#payload_pattern),*)) #guard => { #into_call }
 	}
 }
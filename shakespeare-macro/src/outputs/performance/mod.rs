@@ -2,15 +2,17 @@ mod actor_role_perf;
 mod dispatch_core_fn;
 
 use actor_role_perf::ActorPerf;
+pub(crate) use actor_role_perf::create_sending_method;
 use dispatch_core_fn::DispatchFunction;
 use quote::ToTokens;
-use syn::Result;
+use syn::{Generics, Ident, Result};
 
 use crate::data::{ActorName, DataName};
 use crate::declarations::PerformanceDecl;
 #[derive(Debug)]
 pub(crate) struct PerfDispatch {
-	actor_impl:  ActorPerf,
+	/// Absent for a blanket performance, which has no particular actor, and for the shell's own role
+	actor_impl:  Option<ActorPerf>,
 	dispatch_fn: DispatchFunction,
 }
 
@@ -22,26 +24,65 @@ impl PerfDispatch {
 	) -> Result<Option<PerfDispatch>> {
 		let data_name = data_name.clone();
 		let role_name = perf.get_role_name().clone();
-		let handlers = &perf.handlers;
 		let dispatch_method_name = role_name.method_name();
 		let payload_type = role_name.payload_path();
 
-		if perf.handlers.is_empty() {
+		if perf.blanket {
+			Ok(PerfDispatch {
+				actor_impl:  Some(ActorPerf::new(actor_path, &role_name, None)?),
+				dispatch_fn: DispatchFunction::forwarding(
+					&data_name,
+					&role_name,
+					&dispatch_method_name,
+				)?,
+			}
+			.into())
+		} else if perf.is_empty() {
 			Ok(None)
 		} else {
+			// The shell is already its own role
+			let actor_impl = if role_name.is_shell() {
+				None
+			} else {
+				Some(ActorPerf::new(
+					actor_path,
+					&role_name,
+					perf.message.as_ref(),
+				)?)
+			};
 			Ok(PerfDispatch {
-				actor_impl:  ActorPerf::new(actor_path, &payload_type, &role_name, handlers)?,
+				actor_impl,
 				dispatch_fn: DispatchFunction::new(
 					&data_name,
 					&role_name,
 					&payload_type,
 					&dispatch_method_name,
-					handlers,
+					perf,
 				)?,
 			}
 			.into())
 		}
 	}
+
+	/// A performance for every state type `state_param` that meets the bounds in `generics`, which actors opt into with `performs`
+	pub(crate) fn blanket(
+		perf: &PerformanceDecl,
+		generics: &Generics,
+		state_param: &Ident,
+	) -> Result<PerfDispatch> {
+		let role_name = perf.get_role_name();
+		Ok(PerfDispatch {
+			actor_impl:  None,
+			dispatch_fn: DispatchFunction::blanket(
+				generics,
+				state_param,
+				role_name,
+				&role_name.payload_path(),
+				&role_name.method_name(),
+				perf,
+			)?,
+		})
+	}
 }
 
 impl ToTokens for PerfDispatch {
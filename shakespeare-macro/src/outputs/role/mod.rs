@@ -1,64 +1,94 @@
+mod overlap;
 mod payload_enum;
 
+use itertools::Itertools;
 use payload_enum::{PayloadEnum, ReturnPayload};
-use quote::ToTokens;
+use proc_macro2::TokenStream;
+use quote::{ToTokens, format_ident, quote};
 use syn::fold::Fold;
-use syn::{ItemImpl, ItemTrait, Result};
+use syn::{
+	Expr, FnArg, GenericParam, Generics, Ident, ItemImpl, ItemTrait, Pat, Path, Result, Signature,
+	TraitItemFn, TraitItemType, WherePredicate, parse_quote,
+};
 
-use crate::data::RoleName;
+use crate::data::{RoleName, SignatureExt};
 use crate::declarations::RoleDecl;
-use crate::interfacerewriter::InterfaceRewriter;
-use crate::macros::fallible_quote;
+use crate::interfacerewriter::{AssocTypeReplacer, InterfaceRewriter};
+use crate::macros::{fallible_quote, filter_unwrap};
 
 #[derive(Debug)]
 pub(crate) struct RoleOutput {
 	payload_enum:        PayloadEnum,
 	return_payload_enum: ReturnPayload,
-	trait_definition:    ItemTrait,
+	/// Absent for an actor's `#[message]` methods, whose role is the shell
+	trait_definition:    Option<ItemTrait>,
 	role_impl:           ItemImpl,
 }
 
 impl RoleOutput {
 	pub(crate) fn new(role: RoleDecl) -> Result<RoleOutput> {
 		let RoleDecl {
-			name: role_name,
+			name,
 			signatures,
 			vis,
 			attributes,
+			conflating,
+			generics,
+			mut assoc_types,
+			supertraits,
+			shell,
 		} = role;
-		let role_name = RoleName::new(role_name);
-		let payload_type = role_name.payload_path();
-		let return_payload_type = role_name.return_payload_path();
-
-		let payload_enum = PayloadEnum::new(&payload_type, &signatures, &role_name)?;
-		let return_payload_enum =
-			ReturnPayload::new(&return_payload_type, &signatures, &role_name)?;
-
-		let mut rewriter = InterfaceRewriter::new(&role_name);
-		let signatures = signatures.into_iter().map(|s| rewriter.fold_signature(s));
-
-		let trait_definition = fallible_quote! {
-			#[::shakespeare::async_trait_export::async_trait]
-			#[allow(dead_code)]
-			#(#attributes)*
-			#vis trait #role_name: 'static + Send + Sync {
-				#(#signatures;)*
-				#[doc(hidden)]
-				async fn enqueue(&self, val: ::shakespeare::ReturnEnvelope<dyn #role_name>) -> Result<(), ::shakespeare::Role2SendError<dyn #role_name>>;
-			}
-		}?;
-
-		let role_impl = fallible_quote! {
-			impl<'a> ::shakespeare::Role for dyn #role_name + 'a
-			{
-				type Payload = #payload_type;
-				type Return = #return_payload_type;
-				type Channel = ::shakespeare::TokioUnbounded<::shakespeare::ReturnEnvelope<Self>>;
-				async fn enqueue(&self, val: ::shakespeare::ReturnEnvelope<Self>) -> Result<(), ::shakespeare::Role2SendError<Self>> {
-					self.enqueue(val).await
+		// The payload enums take the associated types as parameters after the role's own, ordered by name
+		assoc_types.sort_by(|a, b| a.ident.cmp(&b.ident));
+		let assoc_names = assoc_types.iter().map(|ty| &ty.ident).collect_vec();
+		let path = role_path(&name, &generics, &assoc_names, false);
+		let role_name = if shell {
+			RoleName::shell(path)
+		} else {
+			RoleName::new(path)
+		};
+		let trait_role_name = RoleName::new(role_path(&name, &generics, &assoc_names, true));
+
+		let enum_generics = enum_generics(&generics, &assoc_types);
+		let enum_signatures = enum_signatures(&signatures, &assoc_names);
+
+		let payload_enum = PayloadEnum::new(&enum_signatures, &role_name, &enum_generics)?;
+		let return_payload_enum = ReturnPayload::new(&enum_signatures, &role_name, &enum_generics)?;
+		let role_impl = role_impl(&name, &role_name, &enum_generics, &signatures, &conflating)?;
+
+		let mut rewriter = InterfaceRewriter::new(&trait_role_name);
+		let sending_methods = signatures
+			.into_iter()
+			.map(|s| sending_method(rewriter.fold_signature(s), &trait_role_name));
+
+		let trait_where = sendable(generics.clone()).where_clause;
+		let assoc_types = assoc_types.into_iter().map(|mut ty| {
+			ty.colon_token.get_or_insert_default();
+			ty.bounds.push(parse_quote!(Send));
+			ty.bounds.push(parse_quote!('static));
+			ty
+		});
+
+		let trait_definition = if shell {
+			None
+		} else {
+			Some(fallible_quote! {
+				#[allow(dead_code)]
+				#(#attributes)*
+				#vis trait #name #generics: #(#supertraits +)* 'static + Send + Sync + ::shakespeare::Monitorable #trait_where {
+					#(#assoc_types)*
+					#(#sending_methods)*
+					#[doc(hidden)]
+					/// The actor's shell as this role, for the envelopes the methods above produce
+					fn get_role(&self) -> ::std::sync::Arc<#trait_role_name>;
+					#[doc(hidden)]
+					// The queues never need to wait for room, so sending is synchronous, which keeps the trait dyn-compatible without boxing a future
+					fn enqueue(&self, val: ::shakespeare::ReturnEnvelope<#trait_role_name>) -> Result<(), ::shakespeare::Role2SendError<#trait_role_name>>;
+					#[doc(hidden)]
+					fn try_enqueue(&self, val: ::shakespeare::ReturnEnvelope<#trait_role_name>) -> Result<(), ::shakespeare::TrySendError<::shakespeare::ReturnEnvelope<#trait_role_name>>>;
 				}
-			}
-		}?;
+			}?)
+		};
 
 		Ok(RoleOutput {
 			payload_enum,
@@ -77,3 +107,167 @@ impl ToTokens for RoleOutput {
 		self.role_impl.to_tokens(tokens);
 	}
 }
+
+/// Implements `Role` for the role's type, which sends through the role's trait, or straight into the shell's queue for an actor's `#[message]` methods
+fn role_impl(
+	name: &Path,
+	role_name: &RoleName,
+	enum_generics: &Generics,
+	signatures: &[Signature],
+	conflating: &[Ident],
+) -> Result<ItemImpl> {
+	let payload_type = role_name.payload_path();
+	let return_payload_type = role_name.return_payload_path();
+	let type_params = enum_generics
+		.type_params()
+		.map(|ty| &ty.ident)
+		.collect_vec();
+
+	let conflating_variants = signatures
+		.iter()
+		.filter(|s| conflating.contains(&s.ident))
+		.map(SignatureExt::enum_variant_name)
+		.collect_vec();
+	let conflates: Expr = if conflating_variants.is_empty() {
+		fallible_quote! { false }?
+	} else {
+		fallible_quote! { matches!(payload, #(#payload_type::#conflating_variants(..))|*) }?
+	};
+
+	let variants = signatures.iter().map(SignatureExt::enum_variant_name);
+	let method_names = signatures.iter().map(|s| s.ident.to_string());
+	let phantom_arm = (!type_params.is_empty())
+		.then(|| quote! { #payload_type::Phantom(never, _) => match never {}, });
+	let method_name: Expr = fallible_quote! {
+		match *payload {
+			#(#payload_type::#variants(..) => #method_names,)*
+			#phantom_arm
+		}
+	}?;
+
+	// The shell puts messages straight into its queue, where a trait leaves it to each performer
+	let mut role_generics = enum_generics.clone();
+	let (role_type, enqueue, try_enqueue) = if role_name.is_shell() {
+		let sender_name = role_name.sender_method_name();
+		let try_sender_name = role_name.try_sender_method_name();
+		(
+			quote!(#role_name),
+			quote!(self.#sender_name(val)),
+			quote!(self.#try_sender_name(val)),
+		)
+	} else {
+		role_generics.params.insert(0, parse_quote!('a));
+		(
+			quote!(#role_name + 'a),
+			quote!(#name::enqueue(self, val)),
+			quote!(#name::try_enqueue(self, val)),
+		)
+	};
+	let (role_impl_generics, _, role_where) = role_generics.split_for_impl();
+	fallible_quote! {
+		impl #role_impl_generics ::shakespeare::Role for #role_type #role_where
+		{
+			type Payload = #payload_type;
+			type Return = #return_payload_type;
+			type Channel = ::shakespeare::PriorityUnbounded<::shakespeare::ReturnEnvelope<Self>>;
+			fn conflates(#[allow(unused_variables)] payload: &Self::Payload) -> bool {
+				#conflates
+			}
+			fn method_name(payload: &Self::Payload) -> &'static str {
+				#method_name
+			}
+			async fn enqueue(&self, val: ::shakespeare::ReturnEnvelope<Self>) -> Result<(), ::shakespeare::Role2SendError<Self>> {
+				#enqueue
+			}
+			fn try_enqueue(&self, val: ::shakespeare::ReturnEnvelope<Self>) -> Result<(), ::shakespeare::TrySendError<::shakespeare::ReturnEnvelope<Self>>> {
+				#try_enqueue
+			}
+		}
+	}
+}
+
+/// A role method packs its parameters into the payload enum, so every performer sends them the same way, including actors that never see the role's methods
+fn sending_method(mut sig: Signature, trait_role_name: &RoleName) -> TraitItemFn {
+	// Patterns other than names can't be turned back into the value they bind
+	let mut names = vec![];
+	for (n, param) in filter_unwrap!(&mut sig.inputs, FnArg::Typed).enumerate() {
+		if let Pat::Ident(pat) = &*param.pat {
+			names.push(pat.ident.clone());
+		} else {
+			let name = format_ident!("arg{n}");
+			*param.pat = parse_quote!(#name);
+			names.push(name);
+		}
+	}
+	let variant_name = sig.enum_variant_name();
+	// A subrole and the roles it extends all have a `get_role`
+	let trait_path = trait_role_name.trait_path();
+	parse_quote! {
+		#sig {
+			#[allow(unused_parens)]
+			let msg = (#(#names),*);
+			let payload = <#trait_role_name as ::shakespeare::Role>::Payload::#variant_name(msg);
+			::shakespeare::Envelope::new(payload, <Self as #trait_path>::get_role(self))
+		}
+	}
+}
+
+/// The role given its own parameters, with its associated types either as parameters of the same name, or as the implementor's inside its own trait
+fn role_path(name: &Path, generics: &Generics, assoc_names: &[&Ident], in_trait: bool) -> Path {
+	if generics.params.is_empty() && assoc_names.is_empty() {
+		return name.clone();
+	}
+	let params = param_names(generics);
+	let owner = in_trait.then(|| quote!(Self::));
+	parse_quote! { #name<#(#params,)* #(#assoc_names = #owner #assoc_names),*> }
+}
+
+/// Inside the enums and their impls, `Self::Item` is the enums' own parameter
+fn enum_signatures(signatures: &[Signature], assoc_names: &[&Ident]) -> Vec<Signature> {
+	let assoc_params = assoc_names
+		.iter()
+		.map(|name| ((*name).clone(), parse_quote!(#name)))
+		.collect_vec();
+	let mut replacer = AssocTypeReplacer::new(&assoc_params);
+	signatures
+		.iter()
+		.map(|s| replacer.fold_signature(s.clone()))
+		.collect()
+}
+
+/// The names of the parameters, to pass them on as arguments
+fn param_names(generics: &Generics) -> Vec<TokenStream> {
+	generics
+		.params
+		.iter()
+		.map(|param| match param {
+			GenericParam::Type(ty) => ty.ident.to_token_stream(),
+			GenericParam::Const(c) => c.ident.to_token_stream(),
+			GenericParam::Lifetime(l) => l.lifetime.to_token_stream(),
+		})
+		.collect()
+}
+
+/// The parameters of the payload enums and the impls for the role, which are the role's own followed by its associated types, all of which are `Send + 'static`
+fn enum_generics(generics: &Generics, assoc_types: &[TraitItemType]) -> Generics {
+	let mut enum_generics = generics.clone();
+	for ty in assoc_types {
+		let TraitItemType { ident, bounds, .. } = ty;
+		enum_generics.params.push(parse_quote! { #ident: #bounds });
+	}
+	sendable(enum_generics)
+}
+
+/// A role's values are all sent to another task, so its parameters and associated types are `Send + 'static` however they are declared
+fn sendable(mut generics: Generics) -> Generics {
+	let type_params = generics
+		.type_params()
+		.map(|ty| ty.ident.clone())
+		.collect_vec();
+	generics.make_where_clause().predicates.extend(
+		type_params
+			.iter()
+			.map(|ty| -> WherePredicate { parse_quote!(#ty: Send + 'static) }),
+	);
+	generics
+}
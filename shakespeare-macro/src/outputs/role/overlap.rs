@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use itertools::Itertools;
+use quote::ToTokens;
+use syn::{GenericArgument, Ident, PathArguments, Type, TypePath};
+
+/// A type in which the role's parameters are variables that could stand for any type
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Term {
+	Var(String),
+	/// A type constructor, named by the segments of its path, applied to some arguments
+	App(Vec<String>, Vec<Term>),
+}
+
+/// Whether the two types could be the same type for some choice of the role's parameters, in which case the impls of `Accepts` or `Emits` for them would overlap
+///
+/// Paths are compared as they are written, as it can't see through type aliases or `use`s, so `Option<T>` and `std::option::Option<u8>` are taken to be different types, as are `Ping` and `a::Ping`
+pub(crate) fn could_overlap(a: &Type, b: &Type, params: &[&Ident]) -> bool {
+	let mut fresh = 0;
+	let a = term(a, params, &mut fresh);
+	let b = term(b, params, &mut fresh);
+	unify(&a, &b, &mut HashMap::new())
+}
+
+fn term(ty: &Type, params: &[&Ident], fresh: &mut usize) -> Term {
+	match ty {
+		Type::Paren(p) => term(&p.elem, params, fresh),
+		Type::Group(g) => term(&g.elem, params, fresh),
+		Type::Path(TypePath { qself: None, path }) => {
+			let segments = path.segments.iter().collect_vec();
+			let (leaf, parents) = segments.split_last().unwrap();
+			if parents.is_empty() && leaf.arguments.is_none() && params.contains(&&leaf.ident) {
+				return Term::Var(leaf.ident.to_string());
+			}
+			let mut head = parents
+				.iter()
+				.map(|segment| segment.to_token_stream().to_string())
+				.collect_vec();
+			let mut name = leaf.ident.to_string();
+			let mut args = vec![];
+			match &leaf.arguments {
+				PathArguments::AngleBracketed(angle) => {
+					for arg in &angle.args {
+						if let GenericArgument::Type(t) = arg {
+							name.push_str("<_>");
+							args.push(term(t, params, fresh));
+						} else {
+							let _ = write!(name, "<{}>", arg.to_token_stream());
+						}
+					}
+				}
+				PathArguments::Parenthesized(_) => {
+					return opaque(&path.to_token_stream().to_string(), params, fresh);
+				}
+				PathArguments::None => {}
+			}
+			head.push(name);
+			Term::App(head, args)
+		}
+		Type::Tuple(t) => Term::App(
+			vec![format!("({})", t.elems.len())],
+			t.elems.iter().map(|e| term(e, params, fresh)).collect(),
+		),
+		Type::Reference(r) => Term::App(
+			vec![format!("&{}", r.mutability.is_some())],
+			vec![term(&r.elem, params, fresh)],
+		),
+		Type::Slice(s) => Term::App(vec!["[]".into()], vec![term(&s.elem, params, fresh)]),
+		Type::Array(a) => Term::App(
+			vec![format!("[{}]", a.len.to_token_stream())],
+			vec![term(&a.elem, params, fresh)],
+		),
+		other => opaque(&other.to_token_stream().to_string(), params, fresh),
+	}
+}
+
+/// A type that isn't looked into, which could be anything if it mentions one of the parameters
+fn opaque(tokens: &str, params: &[&Ident], fresh: &mut usize) -> Term {
+	let mentions_param = tokens
+		.split(|c: char| !c.is_alphanumeric() && c != '_')
+		.any(|word| params.iter().any(|p| *p == word));
+	if mentions_param {
+		*fresh += 1;
+		// Not a valid identifier, so can't clash with a parameter
+		Term::Var(format!("?{fresh}"))
+	} else {
+		Term::App(vec![tokens.to_string()], vec![])
+	}
+}
+
+fn resolve(t: &Term, subst: &HashMap<String, Term>) -> Term {
+	match t {
+		Term::Var(v) => subst
+			.get(v)
+			.map_or_else(|| t.clone(), |s| resolve(s, subst)),
+		Term::App(..) => t.clone(),
+	}
+}
+
+fn occurs(var: &str, t: &Term, subst: &HashMap<String, Term>) -> bool {
+	match resolve(t, subst) {
+		Term::Var(v) => v == var,
+		Term::App(_, args) => args.iter().any(|a| occurs(var, a, subst)),
+	}
+}
+
+fn unify(a: &Term, b: &Term, subst: &mut HashMap<String, Term>) -> bool {
+	match (resolve(a, subst), resolve(b, subst)) {
+		(Term::Var(x), Term::Var(y)) if x == y => true,
+		(Term::Var(x), t) | (t, Term::Var(x)) => {
+			if occurs(&x, &t, subst) {
+				return false;
+			}
+			subst.insert(x, t);
+			true
+		}
+		(Term::App(h1, a1), Term::App(h2, a2)) => {
+			h1 == h2 && a1.len() == a2.len() && a1.iter().zip(&a2).all(|(x, y)| unify(x, y, subst))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use quote::format_ident;
+	use syn::{Type, parse_quote};
+
+	use super::could_overlap;
+
+	fn overlap(a: &Type, b: &Type) -> bool {
+		let (t, u) = (format_ident!("T"), format_ident!("U"));
+		could_overlap(a, b, &[&t, &u])
+	}
+
+	#[test]
+	fn parameters() {
+		assert!(overlap(&parse_quote!(T), &parse_quote!(u32)));
+		assert!(overlap(&parse_quote!(Vec<T>), &parse_quote!(Vec<u8>)));
+		assert!(overlap(&parse_quote!((T, u8)), &parse_quote!((u16, U))));
+		assert!(!overlap(&parse_quote!((T, T)), &parse_quote!((u16, u8))));
+		assert!(!overlap(&parse_quote!(Vec<T>), &parse_quote!(T)));
+	}
+
+	#[test]
+	fn concrete() {
+		assert!(overlap(&parse_quote!(Vec<u8>), &parse_quote!(Vec<u8>)));
+		assert!(!overlap(&parse_quote!(Vec<u8>), &parse_quote!(Vec<u16>)));
+		assert!(!overlap(&parse_quote!(&u8), &parse_quote!(&mut u8)));
+		assert!(!overlap(&parse_quote!([u8; 2]), &parse_quote!([u8; 3])));
+	}
+
+	#[test]
+	fn paths() {
+		assert!(!overlap(&parse_quote!(a::Ping), &parse_quote!(b::Ping)));
+		assert!(!overlap(
+			&parse_quote!(a::Ping<T>),
+			&parse_quote!(b::Ping<u8>)
+		));
+		assert!(!overlap(&parse_quote!(Ping), &parse_quote!(a::Ping)));
+		assert!(!overlap(
+			&parse_quote!(Option<T>),
+			&parse_quote!(std::option::Option<u8>)
+		));
+		assert!(overlap(
+			&parse_quote!(std::option::Option<T>),
+			&parse_quote!(::std::option::Option<u8>)
+		));
+		assert!(!overlap(
+			&parse_quote!(core::option::Option<T>),
+			&parse_quote!(std::option::Option<u8>)
+		));
+	}
+
+	#[test]
+	fn const_arguments() {
+		assert!(!overlap(
+			&parse_quote!(Buf<T, 2>),
+			&parse_quote!(Buf<u8, 3>)
+		));
+		assert!(overlap(&parse_quote!(Buf<T, 2>), &parse_quote!(Buf<u8, 2>)));
+	}
+
+	#[test]
+	fn opaque() {
+		assert!(overlap(&parse_quote!(fn(T)), &parse_quote!(fn(u8))));
+		assert!(!overlap(&parse_quote!(fn(u16)), &parse_quote!(fn(u8))));
+	}
+}
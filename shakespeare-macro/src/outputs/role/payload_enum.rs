@@ -1,7 +1,11 @@
 use itertools::Itertools;
-use quote::ToTokens;
-use syn::{Ident, ItemEnum, ItemImpl, Path, Result, Signature, Type, Variant};
+use proc_macro2::TokenStream;
+use quote::{ToTokens, quote};
+use syn::{
+	Generics, Ident, ItemEnum, ItemImpl, Path, Result, Signature, Type, Variant, parse_quote,
+};
 
+use super::overlap::could_overlap;
 use crate::data::{RoleName, SignatureExt};
 use crate::macros::{fallible_quote, map_or_bail};
 
@@ -13,24 +17,25 @@ pub(crate) struct PayloadEnum {
 
 impl PayloadEnum {
 	pub(crate) fn new(
-		payload_type: &Path,
 		methods: &[Signature],
 		role_name: &RoleName,
+		generics: &Generics,
 	) -> Result<PayloadEnum> {
 		let variants = map_or_bail!(methods, Self::create_variant);
+		let payload_type = role_name.payload_path();
 
-		let impls = Self::create_from_impls(role_name, methods)?;
+		let impls = Self::create_from_impls(role_name, methods, generics)?;
 
-		let definition = fallible_quote! {
-			#[allow(unused_parens)]
-			#[doc(hidden)]
-			pub enum #payload_type { #(#variants),* }
-		}?;
+		let definition = enum_definition(&payload_type, &variants, generics)?;
 
 		Ok(PayloadEnum { definition, impls })
 	}
 
-	fn create_from_impls(role_name: &RoleName, sigs: &[Signature]) -> Result<Vec<ItemImpl>> {
+	fn create_from_impls(
+		role_name: &RoleName,
+		sigs: &[Signature],
+		generics: &Generics,
+	) -> Result<Vec<ItemImpl>> {
 		let variant_names = sigs.iter().map(SignatureExt::enum_variant_name);
 
 		let group_map = sigs
@@ -41,26 +46,41 @@ impl PayloadEnum {
 
 		let type_vector_set = group_map.reduce(|(ident, count), _, v| (ident, count + v.1));
 
-		let impls: Vec<_> = type_vector_set
+		// A type that could be the same as another method's, for some parameters, would be accepted by both
+		// Without parameters, the types left are already different
+		let params = generics.type_params().map(|ty| &ty.ident).collect_vec();
+		let accepted = type_vector_set
 			.into_iter()
 			.filter(|(types, (_, n))| !types.is_empty() && *n == 1)
-			.map(|(types, (ident, _))| Self::type_vector_to_accepts(&types, &ident, role_name))
+			.map(|(types, (ident, _))| (accepted_type(&types), ident))
+			.collect_vec();
+		let impls: Vec<_> = accepted
+			.iter()
+			.filter(|(ty, _)| {
+				params.is_empty()
+					|| !accepted
+						.iter()
+						.any(|(other, _)| other != ty && could_overlap(ty, other, &params))
+			})
+			.map(|(ty, ident)| Self::type_to_accepts(ty, ident, role_name, generics))
 			.try_collect()?;
 
 		Ok(impls)
 	}
 
-	fn type_vector_to_accepts(
-		types: &Vec<&Type>,
+	fn type_to_accepts(
+		ty: &Type,
 		name: &Ident,
 		role_name: &RoleName,
+		generics: &Generics,
 	) -> Result<ItemImpl> {
+		let (impl_generics, _, where_clause) = generics.split_for_impl();
 		fallible_quote! {
 			#[allow(unused_parens)]
-			impl ::shakespeare::Accepts<(#(#types),*)> for dyn #role_name {
+			impl #impl_generics ::shakespeare::Accepts<#ty> for #role_name #where_clause {
 				#[allow(unused_parens)]
 				#[doc(hidden)]
-				fn into_payload(value: (#(#types),*)) -> Self::Payload {
+				fn into_payload(value: #ty) -> Self::Payload {
 					Self::Payload::#name ( (value) )
 				}
 			}
@@ -93,19 +113,17 @@ pub(crate) struct ReturnPayload {
 
 impl ReturnPayload {
 	pub(crate) fn new(
-		return_payload_type: &Path,
 		methods: &[Signature],
 		role_name: &RoleName,
+		generics: &Generics,
 	) -> Result<ReturnPayload> {
 		let variants = map_or_bail!(methods, SignatureExt::create_return_variant);
+		let return_payload_type = role_name.return_payload_path();
 
-		let impls = Self::create_output_from_impls(return_payload_type, methods, role_name)?;
+		let impls =
+			Self::create_output_from_impls(&return_payload_type, methods, role_name, generics)?;
 
-		let definition = fallible_quote! {
-			#[allow(unused_parens)]
-			#[doc(hidden)]
-			pub enum #return_payload_type { #(#variants),* }
-		}?;
+		let definition = enum_definition(&return_payload_type, &variants, generics)?;
 
 		Ok(ReturnPayload { definition, impls })
 	}
@@ -114,6 +132,7 @@ impl ReturnPayload {
 		payload_type: &Path,
 		sigs: &[Signature],
 		role_name: &RoleName,
+		generics: &Generics,
 	) -> Result<Vec<ItemImpl>> {
 		let variant_names = sigs.iter().map(SignatureExt::enum_variant_name);
 
@@ -128,9 +147,20 @@ impl ReturnPayload {
 			group
 		});
 
+		// As with `Accepts`, a type that could be the same as another method's can't say which method it came from
+		let params = generics.type_params().map(|ty| &ty.ident).collect_vec();
+		let types = groups.keys().cloned().collect_vec();
 		groups
 			.into_iter()
-			.map(|(typ, idents)| Self::create_try_from(payload_type, &typ, &idents, role_name))
+			.filter(|(typ, _)| {
+				params.is_empty()
+					|| !types
+						.iter()
+						.any(|other| other != typ && could_overlap(typ, other, &params))
+			})
+			.map(|(typ, idents)| {
+				Self::create_try_from(payload_type, &typ, &idents, role_name, generics)
+			})
 			.try_collect()
 	}
 
@@ -139,9 +169,11 @@ impl ReturnPayload {
 		typ: &Type,
 		idents: &[Ident],
 		role_name: &RoleName,
+		generics: &Generics,
 	) -> Result<ItemImpl> {
+		let (impl_generics, _, where_clause) = generics.split_for_impl();
 		fallible_quote! {
-			impl ::shakespeare::Emits<#typ> for dyn #role_name {
+			impl #impl_generics ::shakespeare::Emits<#typ> for #role_name #where_clause {
 				#[allow(unreachable_patterns)]
 				#[doc(hidden)]
 				fn from_return_payload(value: Self::Return) -> #typ {
@@ -153,7 +185,7 @@ impl ReturnPayload {
 						unimplemented!("Failed to convert discriminant {:?} into type {} in role {}",
 							core::mem::discriminant(&value),
 							core::any::type_name::<#payload_type>(),
-							core::any::type_name::<dyn #role_name>())
+							core::any::type_name::<#role_name>())
 					}
 				}
 			}
@@ -169,3 +201,31 @@ impl ToTokens for ReturnPayload {
 		}
 	}
 }
+
+/// The type a method's parameters are sent as, which is a tuple unless there is only one
+fn accepted_type(types: &[&Type]) -> Type {
+	match types {
+		[ty] => (*ty).clone(),
+		_ => parse_quote! { (#(#types),*) },
+	}
+}
+
+/// Defines one of the role's enums, with a variant that can't be made so that every parameter is used
+fn enum_definition(path: &Path, variants: &[Variant], generics: &Generics) -> Result<ItemEnum> {
+	let ident = &path.segments.last().unwrap().ident;
+	let where_clause = &generics.where_clause;
+	let type_params = generics.type_params().map(|ty| &ty.ident).collect_vec();
+	let phantom: Option<TokenStream> = (!type_params.is_empty()).then(|| {
+		quote! { Phantom(::std::convert::Infallible, ::std::marker::PhantomData<fn() -> (#(#type_params,)*)>), }
+	});
+	// A variant's value is only read through `Emits`, which types that could overlap don't get
+	fallible_quote! {
+		#[allow(unused_parens)]
+		#[allow(dead_code)]
+		#[doc(hidden)]
+		pub enum #ident #generics #where_clause {
+			#(#variants,)*
+			#phantom
+		}
+	}
+}
@@ -0,0 +1,90 @@
+//! Tests the sink for dead letters to any actor without one of its own, in a test binary of its own so that no other tests' dead letters reach it
+use std::sync::{Arc, Mutex};
+
+use shakespeare::{
+	ActorHandles, ActorOutcome, DeadLetter, DeadLetterReason, actor, clear_dead_letter_sink,
+	scoped_dead_letter_sink, set_dead_letter_sink, spawn_fn,
+};
+
+#[actor]
+pub mod Recorder {
+	#[derive(Default)]
+	pub struct RecorderState;
+
+	#[performance(canonical)]
+	impl Record for RecorderState {
+		fn record(&mut self, _value: u32) {}
+	}
+
+	#[performance(canonical)]
+	impl Control for RecorderState {
+		fn quit(&mut self, ctx: &'_ mut shakespeare::Context<Self>) {
+			ctx.stop();
+		}
+	}
+}
+
+fn collect_into(
+	letters: &Arc<Mutex<Vec<DeadLetter>>>,
+) -> impl Fn(DeadLetter) + Send + Sync + 'static {
+	let letters = Arc::clone(letters);
+	move |letter| letters.lock().unwrap().push(letter)
+}
+
+async fn stopped() -> Arc<Recorder> {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Recorder::start(RecorderState);
+	message_handle.quit().await.unwrap();
+	join_handle.await;
+	message_handle
+}
+
+// The tests share the global sink, so run one after another as a single test
+#[tokio::test]
+async fn global_sink() {
+	let recorder = stopped().await;
+	let (outer, inner) = (Arc::default(), Arc::default());
+
+	set_dead_letter_sink(collect_into(&outer));
+	drop(recorder.record(0));
+	{
+		let letters = outer.lock().unwrap();
+		assert_eq!(letters.len(), 1);
+		assert_eq!(letters[0].method(), "record");
+		assert_eq!(letters[0].reason(), DeadLetterReason::Stopped);
+	}
+
+	// A scoped sink takes over until its guard drops, then the one before is used again
+	let guard = scoped_dead_letter_sink(collect_into(&inner));
+	drop(recorder.record(1));
+	drop(guard);
+	drop(recorder.record(2));
+	assert_eq!(inner.lock().unwrap().len(), 1);
+	assert_eq!(outer.lock().unwrap().len(), 2);
+
+	// A function actor's handler panicking leaves the call queued behind it unhandled
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = spawn_fn((), |(), n: u32| async move {
+		assert_ne!(n, 0, "The handler failed");
+		((), ())
+	});
+	drop(message_handle.send(0));
+	drop(message_handle.send(1));
+	assert!(matches!(join_handle.await, ActorOutcome::Panic(_)));
+	{
+		let letters = outer.lock().unwrap();
+		assert_eq!(letters.len(), 3);
+		assert_eq!(letters[2].method(), "send");
+		assert_eq!(letters[2].reason(), DeadLetterReason::Unhandled);
+	}
+
+	clear_dead_letter_sink();
+	drop(recorder.record(3));
+	assert_eq!(outer.lock().unwrap().len(), 3);
+}
@@ -0,0 +1,86 @@
+//! Tests that a batched performance receives the consecutive queued calls together and replies to each caller separately
+use futures::poll;
+use shakespeare::{ActorHandles, EnvelopeErr};
+
+#[shakespeare::actor]
+mod Writer {
+	struct WriterState {
+		rows:    Vec<u32>,
+		batches: Vec<usize>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Database for WriterState {
+		#[batch(limit = 3)]
+		fn insert(&mut self, rows: Vec<u32>) -> Vec<usize> {
+			self.batches.push(rows.len());
+			rows.into_iter()
+				.map(|row| {
+					self.rows.push(row);
+					self.rows.len()
+				})
+				.collect()
+		}
+
+		// Wrongly gives back a single value for the whole batch
+		#[batch(limit = 2)]
+		fn tally(&mut self, values: Vec<u32>) -> Vec<u32> {
+			vec![values.iter().sum()]
+		}
+
+		fn count(&self) -> usize {
+			self.rows.len()
+		}
+
+		fn batches(&self) -> Vec<usize> {
+			self.batches.clone()
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Writer::start(WriterState {
+		rows:    vec![],
+		batches: vec![],
+	});
+
+	// Polling each call once queues it, and the actor doesn't get to run until this task waits, so the calls are all waiting together
+	let mut inserts = (0..4)
+		.map(|row| message_handle.insert(row).into_future())
+		.collect::<Vec<_>>();
+	for insert in &mut inserts {
+		assert!(poll!(insert).is_pending());
+	}
+	let mut count = message_handle.count().into_future();
+	assert!(poll!(&mut count).is_pending());
+	let mut last = message_handle.insert(4).into_future();
+	assert!(poll!(&mut last).is_pending());
+	inserts.push(last);
+
+	for (n, insert) in inserts.into_iter().enumerate() {
+		assert_eq!(insert.await.unwrap(), n + 1);
+	}
+	assert_eq!(count.await.unwrap(), 4);
+	// The first batch is cut off by the limit, and the second by the call to a different method
+	assert_eq!(message_handle.batches().await.unwrap(), vec![3, 1, 1]);
+}
+
+#[tokio::test]
+async fn unmatched() {
+	let ActorHandles { message_handle, .. } = Writer::start(WriterState {
+		rows:    vec![],
+		batches: vec![],
+	});
+
+	// Both calls are queued before the actor gets to run, so are handled together
+	let mut first = message_handle.tally(1).into_future();
+	let mut second = message_handle.tally(2).into_future();
+	assert!(poll!(&mut first).is_pending());
+	assert!(poll!(&mut second).is_pending());
+	assert!(matches!(first.await, Err(EnvelopeErr::Unmatched)));
+	assert!(matches!(second.await, Err(EnvelopeErr::Unmatched)));
+
+	// The actor carries on, and a batch of one gets the right number of values
+	assert_eq!(message_handle.tally(3).await.unwrap(), 3);
+}
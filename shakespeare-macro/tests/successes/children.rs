@@ -0,0 +1,193 @@
+//! Tests that child actors are stopped along with their parent, and that the parent can hear about a child stopping
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use shakespeare::{ActorHandles, ActorOutcome, Context, actor};
+use tokio::sync::Notify;
+use tokio::task::yield_now;
+use tokio::time::timeout;
+
+#[actor]
+pub mod Parent {
+	pub struct ParentState {
+		// Counts the children that have run their stop hook
+		pub stopped:  Arc<AtomicUsize>,
+		pub children: Vec<Arc<Child>>,
+		pub reported: Vec<ActorOutcome<Child>>,
+		// Notified by each child once it is inside its handler
+		pub started:  Arc<Notify>,
+	}
+
+	#[performance(canonical)]
+	impl Family for ParentState {
+		fn adopt(&mut self, ctx: &'_ mut Context<Self>) {
+			let child = ctx.spawn_child(ChildState {
+				stopped: self.stopped.clone(),
+				started: self.started.clone(),
+			});
+			self.children.push(child);
+		}
+
+		fn adopt_reporting(&mut self, ctx: &'_ mut Context<Self>) {
+			let child = ctx.spawn_child_reporting::<dyn Reports, _>(ChildState {
+				stopped: self.stopped.clone(),
+				started: self.started.clone(),
+			});
+			self.children.push(child);
+		}
+
+		async fn keep_children_busy(&mut self) {
+			// One at a time, so that no child's notification is lost to another's
+			for child in &self.children {
+				drop(child.hang());
+				self.started.notified().await;
+			}
+		}
+
+		fn dismiss(&mut self) {
+			for child in self.children.drain(..) {
+				drop(child.leave());
+			}
+		}
+
+		fn finish(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.stop();
+		}
+
+		fn explode(&mut self) {
+			panic!("The parent failed");
+		}
+
+		fn reported(&self) -> usize {
+			self.reported.len()
+		}
+	}
+
+	#[performance(canonical)]
+	impl Reports for ParentState {
+		fn child_stopped(&mut self, outcome: ActorOutcome<Child>) {
+			self.reported.push(outcome);
+		}
+	}
+
+	fn stop(self) -> usize {
+		self.stopped.load(Ordering::SeqCst)
+	}
+
+	fn catch(self, _panic: Box<dyn std::any::Any + Send>) -> usize {
+		self.stopped.load(Ordering::SeqCst)
+	}
+}
+
+#[actor]
+pub mod Child {
+	pub struct ChildState {
+		pub stopped: Arc<AtomicUsize>,
+		pub started: Arc<Notify>,
+	}
+
+	#[performance(canonical)]
+	impl Chores for ChildState {
+		async fn hang(&mut self) {
+			self.started.notify_one();
+			std::future::pending::<()>().await;
+		}
+
+		fn leave(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.stop();
+		}
+	}
+
+	fn stop(self) {
+		self.stopped.fetch_add(1, Ordering::SeqCst);
+	}
+}
+
+fn new_parent(stopped: &Arc<AtomicUsize>) -> ParentState {
+	ParentState {
+		stopped:  stopped.clone(),
+		children: vec![],
+		reported: vec![],
+		started:  Arc::new(Notify::new()),
+	}
+}
+
+#[tokio::test]
+async fn children_stop_with_parent() {
+	let stopped = Arc::new(AtomicUsize::new(0));
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Parent::start(new_parent(&stopped));
+
+	message_handle.adopt().await.unwrap();
+	message_handle.adopt().await.unwrap();
+	message_handle.finish().await.unwrap();
+
+	// The children have stopped by the time the parent's stop hook runs
+	let outcome = timeout(Duration::from_millis(500), join_handle)
+		.await
+		.expect("Idle children should stop without being killed");
+	assert_eq!(outcome, ActorOutcome::Exit(2));
+}
+
+#[tokio::test]
+async fn busy_children_are_killed() {
+	let stopped = Arc::new(AtomicUsize::new(0));
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Parent::start(new_parent(&stopped));
+
+	message_handle.adopt().await.unwrap();
+	message_handle.keep_children_busy().await.unwrap();
+	message_handle.finish().await.unwrap();
+
+	let outcome = timeout(Duration::from_secs(5), join_handle)
+		.await
+		.expect("A child stuck in a handler should be killed");
+	assert_eq!(outcome, ActorOutcome::Exit(1));
+}
+
+#[tokio::test]
+async fn children_stop_when_parent_panics() {
+	let stopped = Arc::new(AtomicUsize::new(0));
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Parent::start(new_parent(&stopped));
+
+	message_handle.adopt().await.unwrap();
+	let _ = message_handle.explode().await;
+
+	let outcome = timeout(Duration::from_millis(500), join_handle)
+		.await
+		.unwrap();
+	assert_eq!(outcome, ActorOutcome::Panic(1));
+}
+
+#[tokio::test]
+async fn parent_hears_about_children() {
+	let stopped = Arc::new(AtomicUsize::new(0));
+	let ActorHandles { message_handle, .. } = Parent::start(new_parent(&stopped));
+
+	message_handle.adopt_reporting().await.unwrap();
+	message_handle.adopt_reporting().await.unwrap();
+	assert_eq!(message_handle.reported().await.unwrap(), 0);
+
+	message_handle.dismiss().await.unwrap();
+	// The reports are queued behind this task's calls, so keep asking until both arrive
+	timeout(Duration::from_millis(500), async {
+		while message_handle.reported().await.unwrap() < 2 {
+			yield_now().await;
+		}
+	})
+	.await
+	.expect("Both children should report stopping");
+	// A child's stop hook runs before its parent hears about it
+	assert_eq!(stopped.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,68 @@
+//! Tests that `&self` handlers overlap with each other when the actor opts in, and that `&mut self` handlers still run alone
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::poll;
+use shakespeare::ActorHandles;
+use tokio::sync::{Barrier, Notify};
+use tokio::time::timeout;
+
+#[shakespeare::actor(concurrent_reads)]
+mod Cache {
+	struct CacheState {
+		value:   usize,
+		barrier: Arc<Barrier>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl CacheRole for CacheState {
+		async fn rendezvous(&self) -> usize {
+			// Only completes if both readers are running at the same time
+			self.barrier.wait().await;
+			self.value
+		}
+
+		async fn gated_read(&self, started: Arc<Notify>, gate: Arc<Notify>) -> usize {
+			started.notify_one();
+			gate.notified().await;
+			self.value
+		}
+
+		fn read(&self) -> usize {
+			self.value
+		}
+
+		fn write(&mut self, value: usize) {
+			self.value = value;
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let state = CacheState {
+		value:   1,
+		barrier: Arc::new(Barrier::new(2)),
+	};
+	let ActorHandles { message_handle, .. } = Cache::start(state);
+
+	let both = async { tokio::join!(message_handle.rendezvous(), message_handle.rendezvous()) };
+	let (first, second) = timeout(Duration::from_secs(5), both)
+		.await
+		.expect("Readers should not be serialised");
+	assert_eq!((first.unwrap(), second.unwrap()), (1, 1));
+
+	// The write is queued after the read, so has to wait for it, even though the read is held up until the write could have run
+	let (started, gate) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+	let mut read = message_handle
+		.gated_read(started.clone(), gate.clone())
+		.into_future();
+	let mut write = message_handle.write(2).into_future();
+	assert!(poll!(&mut read).is_pending());
+	assert!(poll!(&mut write).is_pending());
+	started.notified().await;
+	gate.notify_one();
+	assert_eq!(read.await.unwrap(), 1);
+	write.await.unwrap();
+	assert_eq!(message_handle.read().await.unwrap(), 2);
+}
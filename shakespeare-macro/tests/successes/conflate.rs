@@ -0,0 +1,91 @@
+//! Tests that waiting calls to a conflating method are superseded by the latest one
+use std::sync::{Arc, Mutex};
+
+use futures::poll;
+use shakespeare::{ActorHandles, Context, DeadLetterReason, EnvelopeErr};
+use tokio::sync::Notify;
+
+#[shakespeare::role]
+trait Tracker {
+	fn hold(&self, gate: Arc<Notify>);
+	#[conflate]
+	fn set_position(&self, position: u32) -> u32;
+	fn history(&self) -> Vec<u32>;
+	fn watch(&self, letters: Arc<Mutex<Vec<DeadLetterReason>>>);
+}
+
+#[shakespeare::actor]
+mod Marker {
+	struct MarkerState {
+		history: Vec<u32>,
+	}
+
+	#[shakespeare::performance]
+	impl Tracker for MarkerState {
+		async fn hold(&self, gate: std::sync::Arc<tokio::sync::Notify>) {
+			gate.notified().await;
+		}
+
+		fn set_position(&mut self, position: u32) -> u32 {
+			self.history.push(position);
+			position
+		}
+
+		fn history(&self) -> Vec<u32> {
+			self.history.clone()
+		}
+
+		fn watch(&self, ctx: &'_ mut Context<Self>, letters: Arc<Mutex<Vec<DeadLetterReason>>>) {
+			ctx.set_dead_letter_sink(move |letter| letters.lock().unwrap().push(letter.reason()));
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Marker::start(MarkerState { history: vec![] });
+
+	// Keep the actor busy so that the following calls are all waiting in the queue together
+	let gate = Arc::new(Notify::new());
+	drop(message_handle.hold(gate.clone()));
+
+	// Polling each call once puts it in the queue
+	let mut first = message_handle.set_position(1).into_future();
+	assert!(poll!(&mut first).is_pending());
+	let mut second = message_handle.set_position(2).into_future();
+	assert!(poll!(&mut second).is_pending());
+	let mut history = message_handle.history().into_future();
+	assert!(poll!(&mut history).is_pending());
+	let mut third = message_handle.set_position(3).into_future();
+	assert!(poll!(&mut third).is_pending());
+
+	gate.notify_one();
+
+	assert!(matches!(first.await, Err(EnvelopeErr::Superseded)));
+	assert!(matches!(second.await, Err(EnvelopeErr::Superseded)));
+	assert_eq!(third.await.unwrap(), 3);
+	// Other methods are still handled in the order they were sent
+	assert_eq!(history.await.unwrap(), Vec::<u32>::new());
+	assert_eq!(message_handle.history().await.unwrap(), vec![3]);
+}
+
+#[tokio::test]
+async fn window() {
+	let ActorHandles { message_handle, .. } = Marker::start(MarkerState { history: vec![] });
+	let letters = Arc::new(Mutex::new(vec![]));
+	message_handle.watch(letters.clone()).await.unwrap();
+
+	let gate = Arc::new(Notify::new());
+	drop(message_handle.hold(gate.clone()));
+	for position in 1..=70 {
+		drop(message_handle.set_position(position));
+	}
+	gate.notify_one();
+
+	// Only the first 64 waiting calls are looked at together, so the last of those is handled as well as the last of the rest
+	assert_eq!(message_handle.history().await.unwrap(), vec![64, 70]);
+	// And the superseded calls, which nothing was waiting on, are dead letters
+	let letters = letters.lock().unwrap();
+	assert_eq!(letters.len(), 68);
+	assert!(letters.iter().all(|r| *r == DeadLetterReason::Superseded));
+}
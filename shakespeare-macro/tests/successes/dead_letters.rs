@@ -0,0 +1,150 @@
+//! Tests that messages sent to a stopped actor, or left unhandled when it stops, with no-one waiting on them go to a dead-letter sink
+use std::sync::{Arc, Mutex};
+
+use futures::poll;
+use shakespeare::{
+	ActorHandles, ActorId, Context, DeadLetter, DeadLetterReason, EnvelopeErr, actor,
+};
+use tokio::sync::Notify;
+
+#[actor]
+pub mod Recorder {
+	#[derive(Default)]
+	pub struct RecorderState {
+		log: Vec<u32>,
+	}
+
+	#[performance(canonical)]
+	impl Record for RecorderState {
+		fn record(&mut self, value: u32) {
+			self.log.push(value);
+		}
+
+		fn log(&self) -> Vec<u32> {
+			self.log.clone()
+		}
+	}
+
+	#[performance(canonical)]
+	impl Control for RecorderState {
+		fn sink_into(
+			&self,
+			ctx: &'_ Context<Self>,
+			letters: Arc<Mutex<Vec<DeadLetter>>>,
+		) -> ActorId {
+			ctx.set_dead_letter_sink(move |letter| letters.lock().unwrap().push(letter));
+			ctx.id()
+		}
+
+		fn redirect_to(&self, ctx: &'_ Context<Self>, backup: Arc<dyn Record>) {
+			ctx.set_dead_letter_sink(move |letter| letter.redeliver(&backup).unwrap());
+		}
+
+		async fn quit_when(
+			&mut self,
+			ctx: &'_ mut Context<Self>,
+			started: Arc<Notify>,
+			gate: Arc<Notify>,
+		) {
+			started.notify_one();
+			gate.notified().await;
+			ctx.stop();
+		}
+
+		fn panicking_sink(&self, ctx: &'_ Context<Self>) {
+			ctx.set_dead_letter_sink(|_| panic!("The sink failed"));
+		}
+
+		fn quit(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.stop();
+		}
+	}
+}
+
+#[tokio::test]
+async fn actor_sink() {
+	let letters = Arc::new(Mutex::new(vec![]));
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Recorder::start(RecorderState::default());
+	let id = message_handle.sink_into(letters.clone()).await.unwrap();
+	message_handle.quit().await.unwrap();
+	join_handle.await;
+
+	drop(message_handle.record(1));
+
+	let letters = letters.lock().unwrap();
+	assert_eq!(letters.len(), 1);
+	assert_eq!(letters[0].target(), id);
+	assert_eq!(letters[0].method(), "record");
+	assert!(letters[0].role().ends_with("Record"));
+	assert_eq!(letters[0].reason(), DeadLetterReason::Stopped);
+}
+
+#[tokio::test]
+async fn redelivery() {
+	let backup = Recorder::start(RecorderState::default()).message_handle;
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Recorder::start(RecorderState::default());
+	message_handle.redirect_to(backup.clone()).await.unwrap();
+	message_handle.quit().await.unwrap();
+	join_handle.await;
+
+	drop(message_handle.record(1));
+	drop(message_handle.record(2));
+
+	assert_eq!(backup.log().await.unwrap(), vec![1, 2]);
+}
+
+#[tokio::test]
+async fn unhandled_at_stop() {
+	let letters = Arc::new(Mutex::new(vec![]));
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Recorder::start(RecorderState::default());
+	let id = message_handle.sink_into(letters.clone()).await.unwrap();
+
+	// Both calls are queued while the actor waits to stop, so neither is handled
+	let (started, gate) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+	drop(message_handle.quit_when(started.clone(), gate.clone()));
+	started.notified().await;
+	drop(message_handle.record(1));
+	let mut waiting = message_handle.record(2).into_future();
+	assert!(poll!(&mut waiting).is_pending());
+	gate.notify_one();
+	join_handle.await;
+
+	// A caller waiting on the reply is told that the actor hung up, rather than the sink being given the call
+	assert!(matches!(waiting.await, Err(EnvelopeErr::Hangup)));
+	let letters = letters.lock().unwrap();
+	assert_eq!(letters.len(), 1);
+	assert_eq!(letters[0].target(), id);
+	assert_eq!(letters[0].method(), "record");
+	assert_eq!(letters[0].reason(), DeadLetterReason::Unhandled);
+}
+
+#[tokio::test]
+async fn sink_panics() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Recorder::start(RecorderState::default());
+	message_handle.panicking_sink().await.unwrap();
+	message_handle.quit().await.unwrap();
+	join_handle.await;
+
+	// The panic is caught, rather than unwinding out of the envelope's drop
+	drop(message_handle.record(1));
+	assert!(matches!(
+		message_handle.record(2).await,
+		Err(EnvelopeErr::SendingError(_))
+	));
+}
@@ -0,0 +1,39 @@
+//! Tests that dropped envelopes are sent straight away, in the order they were dropped, even from outside a runtime
+use shakespeare::ActorHandles;
+
+#[shakespeare::actor]
+mod Recorder {
+	struct RecorderState {
+		log: Vec<u32>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Record for RecorderState {
+		fn record(&mut self, value: u32) {
+			self.log.push(value);
+		}
+
+		fn log(&self) -> Vec<u32> {
+			self.log.clone()
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Recorder::start(RecorderState { log: vec![] });
+
+	// The actor doesn't get to run until this task waits, so every call is queued by being dropped, not by anything awaiting it
+	for value in 0..50 {
+		drop(message_handle.record(value));
+	}
+	let actor = message_handle.clone();
+	std::thread::spawn(move || drop(actor.record(50)))
+		.join()
+		.expect("Dropping an envelope outside a runtime should not panic");
+
+	assert_eq!(
+		message_handle.log().await.unwrap(),
+		(0..=50).collect::<Vec<_>>()
+	);
+}
@@ -0,0 +1,108 @@
+//! Tests that actors can be made from a single function, with `spawn_fn` or `#[actor]` on a function
+use std::sync::Arc;
+
+use futures::stream;
+use shakespeare::{ActorHandles, ActorOutcome, FnActor, Message, MessageStream, actor, spawn_fn};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+#[actor]
+async fn accumulate(total: &mut u64, n: u64) -> u64 {
+	*total += n;
+	*total
+}
+
+pub struct Collection {
+	items:   Vec<String>,
+	// Told about each item as it is collected
+	arrived: UnboundedSender<String>,
+}
+
+#[actor]
+pub fn collect(collection: &mut Collection, item: String) {
+	let _ = collection.arrived.send(item.clone());
+	collection.items.push(item);
+}
+
+#[tokio::test]
+async fn closure() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = spawn_fn(0u32, |count, n: u32| async move { (count + n, count + n) });
+
+	assert_eq!(message_handle.send(2).await.unwrap(), 2);
+	assert_eq!(message_handle.send(3).await.unwrap(), 5);
+
+	drop(message_handle);
+	assert!(matches!(join_handle.await, ActorOutcome::Exit(5)));
+}
+
+#[tokio::test]
+async fn function() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = accumulate(10);
+
+	assert_eq!(message_handle.send(1).await.unwrap(), 11);
+	message_handle.send(2).await.unwrap();
+
+	drop(message_handle);
+	assert!(matches!(join_handle.await, ActorOutcome::Exit(13)));
+}
+
+#[tokio::test]
+async fn accepts() {
+	let (arrived, mut collected) = unbounded_channel();
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = collect(Collection {
+		items: vec![],
+		arrived,
+	});
+
+	// Each way of sending runs in a task of its own, so one is waited for before the next is started
+	async { String::from("a") }.send_when_ready(message_handle.clone());
+	assert_eq!(collected.recv().await.unwrap(), "a");
+	stream::iter(["b", "c"].map(String::from)).feed_to(message_handle.clone());
+	assert_eq!(collected.recv().await.unwrap(), "b");
+	assert_eq!(collected.recv().await.unwrap(), "c");
+
+	// Replies can be forwarded to a function actor, and from one
+	let namer: Arc<FnActor<(), u64, String>> =
+		spawn_fn((), |(), n: u64| async move { (n.to_string(), ()) }).message_handle;
+	let total = accumulate(0).message_handle;
+	total.send(4).forward_to(namer.clone()).await.unwrap();
+	namer
+		.send(5)
+		.forward_to(message_handle.clone())
+		.await
+		.unwrap();
+	assert_eq!(collected.recv().await.unwrap(), "5");
+
+	drop(message_handle);
+	match join_handle.await {
+		ActorOutcome::Exit(collection) => assert_eq!(collection.items, vec!["a", "b", "c", "5"]),
+		ActorOutcome::Panic(_) => panic!("The actor should not have panicked"),
+	}
+}
+
+#[tokio::test]
+async fn panics() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = spawn_fn((), |(), n: u32| async move {
+		assert_ne!(n, 0, "Zero is not allowed");
+		(n, ())
+	});
+
+	assert_eq!(message_handle.send(1).await.unwrap(), 1);
+	assert!(message_handle.send(0).await.is_err());
+	assert!(matches!(join_handle.await, ActorOutcome::Panic(_)));
+}
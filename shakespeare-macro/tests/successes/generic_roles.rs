@@ -0,0 +1,257 @@
+//! Tests that roles can have type parameters and associated types, and be performed at different instantiations
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use futures::stream;
+use shakespeare::{Message, MessageStream, actor, role};
+use tokio::sync::Notify;
+
+#[role]
+pub trait Store<K: Eq + Hash, V> {
+	fn get(&self, key: K) -> Option<V>;
+	fn put(&mut self, key: K, value: V);
+	fn size(&self) -> usize;
+}
+
+#[role]
+pub trait Sink {
+	type Item: Clone;
+	fn push(&mut self, item: Self::Item);
+	fn last(&self) -> Option<Self::Item>;
+}
+
+#[actor]
+pub mod Names {
+	#[derive(Default)]
+	pub struct NamesState {
+		names:  HashMap<u64, String>,
+		// Notified as each name is put in
+		stored: Arc<Notify>,
+	}
+
+	#[performance]
+	impl Store<u64, String> for NamesState {
+		fn get(&self, key: u64) -> Option<String> {
+			self.names.get(&key).cloned()
+		}
+
+		fn put(&mut self, key: u64, value: String) {
+			self.names.insert(key, value);
+			self.stored.notify_one();
+		}
+
+		fn size(&self) -> usize {
+			self.names.len()
+		}
+	}
+}
+
+#[actor]
+pub mod Map {
+	pub struct MapState<K: Eq + Hash + Send + 'static, V: Clone + Send + 'static> {
+		pub map: HashMap<K, V>,
+	}
+
+	#[performance]
+	impl<K: Eq + Hash + Send + 'static, V: Clone + Send + 'static> Store<K, V> for MapState<K, V> {
+		fn get(&self, key: K) -> Option<V> {
+			self.map.get(&key).cloned()
+		}
+
+		fn put(&mut self, key: K, value: V) {
+			self.map.insert(key, value);
+		}
+
+		fn size(&self) -> usize {
+			self.map.len()
+		}
+	}
+}
+
+#[actor]
+pub mod Log {
+	pub struct LogState<T: Clone + Send + 'static> {
+		pub items: Vec<T>,
+	}
+
+	#[performance]
+	impl<T: Clone + Send + 'static> Sink for LogState<T> {
+		type Item = T;
+
+		fn push(&mut self, item: Self::Item) {
+			self.items.push(item);
+		}
+
+		fn last(&self) -> Option<T> {
+			self.items.last().cloned()
+		}
+	}
+}
+
+#[derive(PartialEq, Eq, Hash)]
+pub struct A;
+#[derive(PartialEq, Eq, Hash)]
+pub struct AB;
+#[derive(Clone)]
+pub struct C;
+#[derive(Clone)]
+pub struct BC;
+
+#[actor]
+pub mod Buffers {
+	#[derive(Default)]
+	pub struct BuffersState {
+		bytes: HashMap<u8, Vec<u8>>,
+		words: HashMap<u8, Vec<u16>>,
+		named: usize,
+	}
+
+	#[performance]
+	impl Store<u8, Vec<u8>> for BuffersState {
+		fn get(&self, key: u8) -> Option<Vec<u8>> {
+			self.bytes.get(&key).cloned()
+		}
+
+		fn put(&mut self, key: u8, value: Vec<u8>) {
+			self.bytes.insert(key, value);
+		}
+
+		fn size(&self) -> usize {
+			self.bytes.len()
+		}
+	}
+
+	#[performance]
+	impl Store<u8, Vec<u16>> for BuffersState {
+		fn get(&self, key: u8) -> Option<Vec<u16>> {
+			self.words.get(&key).cloned()
+		}
+
+		fn put(&mut self, key: u8, value: Vec<u16>) {
+			self.words.insert(key, value);
+		}
+
+		fn size(&self) -> usize {
+			self.words.len()
+		}
+	}
+
+	// The arguments' names run together to the same text, so must still be told apart
+	#[performance]
+	impl Store<AB, C> for BuffersState {
+		fn get(&self, _key: AB) -> Option<C> {
+			None
+		}
+
+		fn put(&mut self, _key: AB, _value: C) {
+			self.named += 1;
+		}
+
+		fn size(&self) -> usize {
+			self.named
+		}
+	}
+
+	#[performance]
+	impl Store<A, BC> for BuffersState {
+		fn get(&self, _key: A) -> Option<BC> {
+			None
+		}
+
+		fn put(&mut self, _key: A, _value: BC) {
+			self.named += 10;
+		}
+
+		fn size(&self) -> usize {
+			self.named
+		}
+	}
+}
+
+#[tokio::test]
+async fn parameters() {
+	let names = Names::start(NamesState::default()).message_handle;
+	names.put(1, String::from("one")).await.unwrap();
+	assert_eq!(names.get(1).await.unwrap().as_deref(), Some("one"));
+	assert_eq!(names.get(2).await.unwrap(), None);
+
+	// Different actors perform the role at the same instantiation
+	let map = Map::start(MapState {
+		map: HashMap::new(),
+	})
+	.message_handle;
+	let stores: Vec<Arc<dyn Store<u64, String>>> = vec![names, map];
+	for store in &stores {
+		store.put(7, String::from("seven")).await.unwrap();
+	}
+	let mut sizes = vec![];
+	for store in &stores {
+		sizes.push(store.size().await.unwrap());
+	}
+	assert_eq!(sizes, vec![2, 1]);
+
+	// And the same actor performs it at different instantiations
+	let flags: Arc<dyn Store<char, bool>> = Map::start(MapState {
+		map: HashMap::from([('a', true)]),
+	})
+	.message_handle;
+	assert_eq!(flags.get('a').await.unwrap(), Some(true));
+}
+
+#[tokio::test]
+async fn similar_instantiations() {
+	let handle = Buffers::start(BuffersState::default()).message_handle;
+	let bytes: Arc<dyn Store<u8, Vec<u8>>> = handle.clone();
+	let words: Arc<dyn Store<u8, Vec<u16>>> = handle.clone();
+	bytes.put(1, vec![1]).await.unwrap();
+	words.put(1, vec![2, 3]).await.unwrap();
+	words.put(2, vec![4]).await.unwrap();
+	assert_eq!(bytes.get(1).await.unwrap(), Some(vec![1]));
+	assert_eq!(words.get(1).await.unwrap(), Some(vec![2, 3]));
+	assert_eq!(
+		(bytes.size().await.unwrap(), words.size().await.unwrap()),
+		(1, 2)
+	);
+
+	let ab_c: Arc<dyn Store<AB, C>> = handle.clone();
+	let a_bc: Arc<dyn Store<A, BC>> = handle;
+	ab_c.put(AB, C).await.unwrap();
+	a_bc.put(A, BC).await.unwrap();
+	assert_eq!(ab_c.size().await.unwrap(), 11);
+}
+
+#[tokio::test]
+async fn assoc_types() {
+	let numbers = Log::start(LogState { items: vec![] }).message_handle;
+	numbers.push(1u32).await.unwrap();
+	numbers.push(2).await.unwrap();
+	assert_eq!(numbers.last().await.unwrap(), Some(2));
+
+	let words: Arc<dyn Sink<Item = String>> = Log::start(LogState { items: vec![] }).message_handle;
+	words.push(String::from("a")).await.unwrap();
+	assert_eq!(words.last().await.unwrap().as_deref(), Some("a"));
+}
+
+#[tokio::test]
+async fn accepts() {
+	let state = NamesState::default();
+	let stored = state.stored.clone();
+	let store: Arc<dyn Store<u64, String>> = Names::start(state).message_handle;
+
+	async { (1, String::from("one")) }.send_when_ready(store.clone());
+	stream::iter([(2, String::from("two")), (3, String::from("three"))]).feed_to(store.clone());
+	// A name put in after the size is read leaves a permit, so isn't missed
+	while store.size().await.unwrap() < 3 {
+		stored.notified().await;
+	}
+	assert_eq!(store.size().await.unwrap(), 3);
+
+	// A reply from one generic role can be forwarded to another
+	let log: Arc<dyn Sink<Item = Option<String>>> =
+		Log::start(LogState { items: vec![] }).message_handle;
+	store.get(2).forward_to(log.clone()).await.unwrap();
+	// The reply is queued for the log as the store handles the call, which is before it handles this one
+	store.size().await.unwrap();
+	assert_eq!(log.last().await.unwrap(), Some(Some(String::from("two"))));
+}
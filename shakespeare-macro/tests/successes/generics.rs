@@ -0,0 +1,102 @@
+//! Tests that the state type's generic parameters, along with their bounds, are given to the actor
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use shakespeare::{ActorOutcome, Handles, Request, actor};
+
+pub struct Push<T>(pub T);
+
+impl<T: Send + 'static> Request for Push<T> {
+	type Reply = ();
+}
+
+#[actor(inspect)]
+pub mod Recent {
+	/// Keeps the last `N` items pushed to it
+	pub struct RecentState<T: Clone + Send + 'static, const N: usize>
+	where
+		T: Debug,
+	{
+		pub items: VecDeque<T>,
+	}
+
+	impl<T: Clone + Send + 'static, const N: usize> RecentState<T, N>
+	where
+		T: Debug,
+	{
+		#[handler]
+		fn push(&mut self, msg: Push<T>) {
+			if self.items.len() == N {
+				self.items.pop_front();
+			}
+			self.items.push_back(msg.0);
+		}
+	}
+
+	#[performance(canonical)]
+	impl<T: Clone + Send + 'static, const N: usize> Counter for RecentState<T, N>
+	where
+		T: Debug,
+	{
+		fn count(&self) -> usize {
+			self.items.len()
+		}
+
+		fn describe(&self) -> String {
+			format!("{:?}", self.items)
+		}
+	}
+
+	fn stop(self) -> Vec<T> {
+		self.items.into()
+	}
+}
+
+fn empty<T: Clone + Send + Debug + 'static, const N: usize>() -> RecentState<T, N> {
+	RecentState {
+		items: VecDeque::new(),
+	}
+}
+
+#[tokio::test]
+async fn type_and_const() {
+	let handles = Recent::start(empty::<u32, 2>());
+	let recent: Arc<Recent<u32, 2>> = handles.message_handle;
+
+	for n in 1..=3 {
+		recent.handle(Push(n)).await.unwrap();
+	}
+	assert_eq!(recent.count().await.unwrap(), 2);
+	let items = recent
+		.with_state(|state| state.items.clone())
+		.await
+		.unwrap();
+	assert_eq!(items, [2, 3]);
+
+	drop(recent);
+	match handles.join_handle.await {
+		ActorOutcome::Exit(items) => assert_eq!(items, vec![2, 3]),
+		ActorOutcome::Panic(_) => panic!("The actor should not have panicked"),
+	}
+}
+
+#[tokio::test]
+async fn instantiations() {
+	let numbers = Recent::start(empty::<u64, 1>()).message_handle;
+	let words = Recent::start(empty::<String, 3>()).message_handle;
+
+	numbers.handle(Push(4)).await.unwrap();
+	numbers.handle(Push(5)).await.unwrap();
+	let pusher: Arc<dyn Handles<Push<String>>> = words.clone();
+	pusher.handle(Push(String::from("a"))).await.unwrap();
+	pusher.handle(Push(String::from("b"))).await.unwrap();
+
+	// Each instantiation performs the same role, so they can be used together
+	let counters: Vec<Arc<dyn Counter>> = vec![numbers, words];
+	let mut descriptions = vec![];
+	for counter in counters {
+		descriptions.push(counter.describe().await.unwrap());
+	}
+	assert_eq!(descriptions, vec!["[5]", r#"["a", "b"]"#]);
+}
@@ -0,0 +1,192 @@
+//! Tests that an actor can hand off to a successor, which answers every call made through the existing handles
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use futures::poll;
+use shakespeare::{ActorHandles, ActorOutcome, Context};
+use tokio::sync::Notify;
+
+#[shakespeare::role]
+trait Counting {
+	fn hold(&self, gate: Arc<Notify>);
+	fn add(&mut self, amount: u32) -> u64;
+	fn upgrade(&mut self);
+	fn upgrade_when(&mut self, started: Arc<Notify>, gate: Arc<Notify>);
+	fn fail(&self);
+	fn version(&self) -> u32;
+}
+
+#[shakespeare::actor]
+pub mod CounterV1 {
+	pub struct CounterV1State {
+		pub count: u32,
+		// What was left of the count when the actor stopped
+		pub left:  Arc<AtomicU32>,
+	}
+
+	#[shakespeare::performance]
+	impl Counting for CounterV1State {
+		async fn hold(&self, gate: Arc<Notify>) {
+			gate.notified().await;
+		}
+
+		fn add(&mut self, amount: u32) -> u64 {
+			self.count += amount;
+			u64::from(self.count)
+		}
+
+		fn upgrade(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.hand_off(|state: &mut CounterV1State| CounterV2State {
+				total: u64::from(std::mem::take(&mut state.count)),
+			});
+		}
+
+		async fn upgrade_when(
+			&mut self,
+			ctx: &'_ mut Context<Self>,
+			started: Arc<Notify>,
+			gate: Arc<Notify>,
+		) {
+			started.notify_one();
+			gate.notified().await;
+			ctx.hand_off(|state: &mut CounterV1State| CounterV2State {
+				total: u64::from(std::mem::take(&mut state.count)),
+			});
+		}
+
+		fn fail(&self) {}
+
+		fn version(&self) -> u32 {
+			1
+		}
+	}
+
+	fn stop(self) -> u32 {
+		self.left.store(self.count, Ordering::SeqCst);
+		self.count
+	}
+}
+
+#[shakespeare::actor]
+pub mod CounterV2 {
+	pub struct CounterV2State {
+		pub total: u64,
+	}
+
+	#[shakespeare::performance]
+	impl Counting for CounterV2State {
+		async fn hold(&self, gate: Arc<Notify>) {
+			gate.notified().await;
+		}
+
+		fn add(&mut self, amount: u32) -> u64 {
+			self.total += u64::from(amount);
+			self.total
+		}
+
+		fn upgrade(&mut self) {}
+
+		fn upgrade_when(&mut self, _started: Arc<Notify>, _gate: Arc<Notify>) {}
+
+		fn fail(&self) {
+			panic!("The successor failed");
+		}
+
+		fn version(&self) -> u32 {
+			2
+		}
+	}
+}
+
+#[shakespeare::actor]
+pub mod Owner {
+	pub struct OwnerState {
+		pub left: Arc<AtomicU32>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Owning for OwnerState {
+		fn adopt(&mut self, ctx: &'_ mut Context<Self>, count: u32) -> Arc<CounterV1> {
+			ctx.spawn_child(CounterV1State {
+				count,
+				left: self.left.clone(),
+			})
+		}
+
+		fn finish(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.stop();
+		}
+	}
+}
+
+fn start(count: u32) -> (Arc<dyn Counting>, shakespeare::ExitHandle<CounterV1>) {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = CounterV1::start(CounterV1State {
+		count,
+		left: Arc::default(),
+	});
+	(message_handle, join_handle)
+}
+
+#[tokio::test]
+async fn main() {
+	let (counter, mut join_handle) = start(0);
+	assert_eq!(counter.add(2).await.unwrap(), 2);
+
+	// Keep the actor busy so that the following calls are waiting in its queue when it hands off
+	let gate = Arc::new(Notify::new());
+	drop(counter.hold(gate.clone()));
+	drop(counter.upgrade());
+	let mut waiting = counter.add(3).into_future();
+	// Polling the call once puts it in the queue
+	assert!(poll!(&mut waiting).is_pending());
+	gate.notify_one();
+
+	// The successor answers both the call that was waiting and those made afterwards, through the same handle
+	assert_eq!(waiting.await.unwrap(), 5);
+	assert_eq!(counter.version().await.unwrap(), 2);
+	assert_eq!(counter.add(1).await.unwrap(), 6);
+
+	// The first actor only finishes once its handles have all gone
+	assert!(poll!(&mut join_handle).is_pending());
+	drop(counter);
+	assert!(matches!(join_handle.await, ActorOutcome::Exit(0)));
+}
+
+#[tokio::test]
+async fn successor_panics() {
+	let (counter, join_handle) = start(4);
+	drop(counter.upgrade());
+	assert!(counter.fail().await.is_err());
+
+	// The successor's panic becomes the first actor's own
+	drop(counter);
+	assert!(matches!(join_handle.await, ActorOutcome::Panic(_)));
+}
+
+#[tokio::test]
+async fn stopped_by_parent() {
+	let left = Arc::new(AtomicU32::new(u32::MAX));
+	let ActorHandles {
+		message_handle: owner,
+		join_handle,
+		..
+	} = Owner::start(OwnerState { left: left.clone() });
+	let counter = owner.adopt(7).await.unwrap();
+
+	let (started, gate) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+	drop(counter.upgrade_when(started.clone(), gate.clone()));
+	started.notified().await;
+
+	// The parent tells the child to stop before the task running this test is woken up by the reply
+	owner.finish().await.unwrap();
+	gate.notify_one();
+	drop((owner, counter));
+	assert!(matches!(join_handle.await, ActorOutcome::Exit(())));
+
+	// Being told to stop meant the child never handed off, so still had all of its count
+	assert_eq!(left.load(Ordering::SeqCst), 7);
+}
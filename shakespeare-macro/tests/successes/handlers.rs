@@ -0,0 +1,195 @@
+//! Tests that `#[handler]` methods handle message types that carry their own reply type, and can be sent to through `dyn Handles<M>`
+use std::sync::Arc;
+
+use futures::stream;
+use shakespeare::{Context, Handles, Message, MessageStream, Request, actor};
+use tokio::sync::Notify;
+
+pub struct Double(pub u32);
+
+impl Request for Double {
+	type Reply = u32;
+}
+
+pub struct Record(pub u32);
+
+impl Request for Record {
+	type Reply = ();
+}
+
+pub struct Total;
+
+impl Request for Total {
+	type Reply = u32;
+}
+
+pub struct Shutdown;
+
+impl Request for Shutdown {
+	type Reply = ();
+}
+
+#[actor]
+pub mod Summer {
+	#[derive(Default)]
+	pub struct SummerState {
+		total:    u32,
+		// Notified as each record is handled
+		recorded: Arc<Notify>,
+	}
+
+	impl SummerState {
+		#[handler]
+		fn double(&self, msg: Double) -> u32 {
+			msg.0 * 2
+		}
+
+		#[handler]
+		async fn record(&mut self, msg: Record) {
+			self.total += msg.0;
+			self.recorded.notify_one();
+		}
+
+		#[handler]
+		fn total(&self, _: Total) -> Total::Reply {
+			self.total
+		}
+
+		#[handler]
+		fn shutdown(&mut self, ctx: &'_ mut Context<Self>, _: Shutdown) {
+			ctx.stop();
+		}
+	}
+
+	#[performance(canonical)]
+	impl Reset for SummerState {
+		fn reset(&mut self) -> u32 {
+			std::mem::take(&mut self.total)
+		}
+	}
+}
+
+pub mod a {
+	pub struct Ping;
+
+	impl shakespeare::Request for Ping {
+		type Reply = char;
+	}
+}
+
+pub mod b {
+	pub struct Ping;
+
+	impl shakespeare::Request for Ping {
+		type Reply = u8;
+	}
+}
+
+#[actor]
+pub mod Tripler {
+	pub struct TriplerState;
+
+	impl TriplerState {
+		#[handler]
+		fn triple(&self, msg: Double) -> u32 {
+			msg.0 * 3
+		}
+
+		// Message types with the same name are kept apart
+		#[handler]
+		fn ping_a(&self, _: a::Ping) -> char {
+			'a'
+		}
+
+		#[handler]
+		fn ping_b(&self, _: b::Ping) -> u8 {
+			b'b'
+		}
+	}
+}
+
+/// Waits for records sent some other way than through `summer` to bring the total up to `total`
+async fn wait_for_total(summer: &Summer, recorded: &Notify, total: u32) {
+	// A record handled after the total is read leaves a permit, so isn't missed
+	while summer.handle(Total).await.unwrap() < total {
+		recorded.notified().await;
+	}
+}
+
+#[tokio::test]
+async fn handle() {
+	let summer = Summer::start(SummerState::default()).message_handle;
+
+	assert_eq!(summer.handle(Double(4)).await.unwrap(), 8);
+	summer.handle(Record(3)).await.unwrap();
+	summer.handle(Record(4)).await.unwrap();
+	assert_eq!(summer.handle(Total).await.unwrap(), 7);
+	// Handlers work alongside the actor's roles
+	assert_eq!(summer.reset().await.unwrap(), 7);
+	assert_eq!(summer.handle(Total).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn through_dyn() {
+	let summer = Summer::start(SummerState::default()).message_handle;
+	let tripler = Tripler::start(TriplerState).message_handle;
+
+	let doublers: Vec<Arc<dyn Handles<Double>>> = vec![summer, tripler];
+	let mut results = vec![];
+	for doubler in doublers {
+		results.push(doubler.handle(Double(5)).await.unwrap());
+	}
+	assert_eq!(results, vec![10, 15]);
+}
+
+#[tokio::test]
+async fn same_name() {
+	let tripler = Tripler::start(TriplerState).message_handle;
+	assert_eq!(tripler.handle(a::Ping).await.unwrap(), 'a');
+	assert_eq!(tripler.handle(b::Ping).await.unwrap(), b'b');
+}
+
+#[tokio::test]
+async fn accepts() {
+	let state = SummerState::default();
+	let recorded = state.recorded.clone();
+	let summer = Summer::start(state).message_handle;
+	let recorder: Arc<dyn Handles<Record>> = summer.clone();
+
+	async { Record(1) }.send_when_ready(recorder.clone());
+	stream::iter([Record(2), Record(3)]).feed_to(recorder);
+	wait_for_total(&summer, &recorded, 6).await;
+	assert_eq!(summer.handle(Total).await.unwrap(), 6);
+
+	// The reply to one handler can be forwarded to another
+	struct Spare(u32);
+	impl Request for Spare {
+		type Reply = Record;
+	}
+	#[actor]
+	mod Spares {
+		struct SparesState;
+		impl SparesState {
+			#[handler]
+			fn spare(&self, msg: Spare) -> Record {
+				Record(msg.0)
+			}
+		}
+	}
+	let spares = Spares::start(SparesState).message_handle;
+	spares
+		.handle(Spare(4))
+		.forward_to(summer.clone() as Arc<dyn Handles<Record>>)
+		.await
+		.unwrap();
+	wait_for_total(&summer, &recorded, 10).await;
+	assert_eq!(summer.handle(Total).await.unwrap(), 10);
+}
+
+#[tokio::test]
+async fn context() {
+	let handles = Summer::start(SummerState::default());
+	handles.message_handle.handle(Shutdown).await.unwrap();
+	handles.join_handle.await;
+	assert!(handles.message_handle.handle(Total).await.is_err());
+}
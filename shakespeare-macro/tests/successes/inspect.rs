@@ -0,0 +1,97 @@
+//! Tests that an actor declared with `inspect` can have closures run on its state from outside
+use std::sync::Arc;
+
+use shakespeare::{ActorHandles, Context, StateQueryErr, actor};
+
+#[actor(inspect)]
+pub mod Account {
+	pub struct AccountState {
+		pub balance: i64,
+	}
+
+	#[performance(canonical)]
+	impl Banking for AccountState {
+		fn deposit(&mut self, amount: i64) {
+			self.balance += amount;
+		}
+
+		fn close(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.stop();
+		}
+	}
+}
+
+#[actor(inspect, single_mailbox)]
+pub mod Ledger {
+	pub struct LedgerState {
+		pub entries: Vec<String>,
+	}
+
+	#[performance(canonical)]
+	impl Entries for LedgerState {
+		fn record(&mut self, entry: String) {
+			self.entries.push(entry);
+		}
+	}
+
+	#[performance(canonical)]
+	impl Audit for LedgerState {
+		fn audit(&mut self) {
+			self.entries.push("audited".to_owned());
+		}
+	}
+}
+
+#[tokio::test]
+async fn query_and_mutate() {
+	let account: Arc<Account> = Account::start(AccountState { balance: 0 }).message_handle;
+	account.deposit(10).await.unwrap();
+
+	assert_eq!(account.with_state(|state| state.balance).await, Ok(10));
+	let doubled = account
+		.with_state(|state| {
+			state.balance *= 2;
+			state.balance
+		})
+		.await;
+	assert_eq!(doubled, Ok(20));
+
+	// Dropping the query still runs the closure
+	drop(account.with_state(|state| state.balance = 5));
+	assert_eq!(account.with_state(|state| state.balance).await, Ok(5));
+}
+
+#[tokio::test]
+async fn stopped() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Account::start(AccountState { balance: 0 });
+	message_handle.close().await.unwrap();
+	join_handle.await;
+
+	assert_eq!(
+		message_handle.with_state(|state| state.balance).await,
+		Err(StateQueryErr::Hangup)
+	);
+}
+
+#[tokio::test]
+async fn ordered_in_single_mailbox() {
+	let ledger = Ledger::start(LedgerState { entries: vec![] }).message_handle;
+
+	drop(ledger.record("first".to_owned()));
+	let seen = ledger.with_state(|state| state.entries.clone());
+	drop(ledger.audit());
+	drop(ledger.with_state(|state| state.entries.push("inspected".to_owned())));
+
+	assert_eq!(seen.await.unwrap(), ["first"]);
+	assert_eq!(
+		ledger
+			.with_state(|state| state.entries.clone())
+			.await
+			.unwrap(),
+		["first", "audited", "inspected"]
+	);
+}
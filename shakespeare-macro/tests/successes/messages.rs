@@ -0,0 +1,109 @@
+//! Tests that `#[message]` methods in an inherent impl can be called on the shell without declaring a role, and that a canonical performance can be outside the actor module
+use shakespeare::ActorHandles;
+
+pub mod actor {
+	use shakespeare::{ActorHandles, Context};
+
+	#[shakespeare::actor]
+	pub mod Tally {
+		pub struct TallyState {
+			pub count: u32,
+		}
+
+		impl TallyState {
+			pub fn new() -> TallyState {
+				TallyState { count: 0 }
+			}
+
+			#[message]
+			fn add(&mut self, amount: u32) -> u32 {
+				self.count += amount;
+				self.count
+			}
+
+			#[message]
+			async fn reset(&mut self) -> u32 {
+				std::mem::take(&mut self.count)
+			}
+
+			#[message]
+			fn close(&mut self, ctx: &'_ mut Context<Self>) {
+				ctx.stop();
+			}
+		}
+
+		impl TallyState {
+			#[message]
+			fn count(&self) -> u32 {
+				self.count
+			}
+		}
+
+		#[shakespeare::performance]
+		impl super::perf::Report for TallyState {}
+	}
+
+	// The messages are sent to the shell itself, so no role is declared for them
+	#[allow(dead_code)]
+	pub trait TallyMessages {}
+
+	#[shakespeare::actor]
+	pub mod Slot {
+		pub struct SlotState<T: Send + 'static> {
+			pub value: T,
+		}
+
+		impl<T: Send + 'static> SlotState<T> {
+			#[message]
+			fn replace(&mut self, value: T) -> T {
+				std::mem::replace(&mut self.value, value)
+			}
+		}
+	}
+
+	pub(crate) fn new() -> ActorHandles<Tally> {
+		Tally::start(TallyState::new())
+	}
+
+	pub(crate) fn slot<T: Send + 'static>(value: T) -> ActorHandles<Slot<T>> {
+		Slot::start(SlotState { value })
+	}
+}
+
+pub mod perf {
+	#[shakespeare::performance(canonical)]
+	impl Report for super::actor::TallyState {
+		fn report(&self) -> String {
+			format!("{} so far", self.count)
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	use std::sync::Arc;
+
+	use perf::Report;
+
+	let ActorHandles { message_handle, .. } = actor::new();
+	assert_eq!(message_handle.add(2).await.unwrap(), 2);
+	assert_eq!(message_handle.add(3).await.unwrap(), 5);
+	assert_eq!(message_handle.count().await.unwrap(), 5);
+
+	let report: Arc<dyn Report> = message_handle.clone();
+	assert_eq!(report.report().await.unwrap(), "5 so far");
+
+	assert_eq!(message_handle.reset().await.unwrap(), 5);
+	assert_eq!(message_handle.count().await.unwrap(), 0);
+	message_handle.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn generic() {
+	use shakespeare::Envelope;
+
+	let slot = actor::slot('a').message_handle;
+	let envelope: Envelope<actor::Slot<char>, char> = slot.replace('b');
+	assert_eq!(envelope.await.unwrap(), 'a');
+	assert_eq!(slot.replace('c').await.unwrap(), 'b');
+}
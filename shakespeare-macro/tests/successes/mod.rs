@@ -1,6 +1,29 @@
 mod accepts;
 mod basic;
+mod batch;
+mod children;
+mod concurrent_reads;
+mod conflate;
+mod dead_letters;
+mod dropped;
+mod functions;
+mod generic_roles;
+mod generics;
+mod hand_off;
+mod handlers;
+mod inspect;
+mod messages;
 mod modules;
+mod monitor;
+mod performs;
+mod phases;
+mod priority;
 mod returns;
+mod shell_methods;
+mod single_mailbox;
+mod stash;
 mod stream;
+mod supertraits;
 mod supervisor;
+mod timers;
+mod unhandled;
@@ -0,0 +1,146 @@
+//! Tests that monitoring actors are told when the actors they monitor stop
+use std::sync::Arc;
+
+use shakespeare::{ActorHandles, ActorId, Context, Termination, actor};
+use tokio::sync::Notify;
+
+#[actor]
+pub mod Watcher {
+	#[derive(Default)]
+	pub struct WatcherState {
+		notices: Vec<Termination>,
+		// Notified as each notice is handled
+		noticed: Arc<Notify>,
+	}
+
+	#[performance(canonical)]
+	impl Watch for WatcherState {
+		fn watch(&mut self, ctx: &'_ mut Context<Self>, peer: Arc<dyn Peer>) -> ActorId {
+			ctx.monitor::<dyn Notices, _>(&peer)
+		}
+
+		fn unwatch(&mut self, ctx: &'_ mut Context<Self>, peer: Arc<dyn Peer>) {
+			ctx.demonitor(&peer);
+		}
+
+		fn notices(&self) -> Vec<Termination> {
+			self.notices.clone()
+		}
+	}
+
+	#[performance(canonical)]
+	impl Notices for WatcherState {
+		fn peer_stopped(&mut self, notice: Termination) {
+			self.notices.push(notice);
+			self.noticed.notify_one();
+		}
+	}
+}
+
+#[actor]
+pub mod Worker {
+	pub struct WorkerState;
+
+	#[performance(canonical)]
+	impl Peer for WorkerState {
+		fn quit(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.stop();
+		}
+
+		fn explode(&mut self) {
+			panic!("The worker failed");
+		}
+	}
+}
+
+fn start_watcher() -> (Arc<Watcher>, Arc<Notify>) {
+	let state = WatcherState::default();
+	let noticed = state.noticed.clone();
+	(Watcher::start(state).message_handle, noticed)
+}
+
+#[tokio::test]
+async fn every_monitor_is_told() {
+	let ActorHandles {
+		message_handle: worker,
+		join_handle,
+		..
+	} = Worker::start(WorkerState);
+	let (first, first_noticed) = start_watcher();
+	let (second, second_noticed) = start_watcher();
+
+	let id = first.watch(worker.clone()).await.unwrap();
+	assert_eq!(second.watch(worker.clone()).await.unwrap(), id);
+	// Monitoring twice still only gives one notice
+	first.watch(worker.clone()).await.unwrap();
+	assert!(first.notices().await.unwrap().is_empty());
+
+	worker.quit().await.unwrap();
+	join_handle.await;
+	first_noticed.notified().await;
+	second_noticed.notified().await;
+
+	for watcher in [first, second] {
+		let notices = watcher.notices().await.unwrap();
+		assert_eq!(notices.len(), 1);
+		assert_eq!(notices[0].actor, id);
+		assert!(!notices[0].panicked);
+	}
+}
+
+#[tokio::test]
+async fn panics_are_reported() {
+	let ActorHandles {
+		message_handle: worker,
+		join_handle,
+		..
+	} = Worker::start(WorkerState);
+	let (watcher, noticed) = start_watcher();
+
+	watcher.watch(worker.clone()).await.unwrap();
+	let _ = worker.explode().await;
+	join_handle.await;
+	noticed.notified().await;
+
+	let notices = watcher.notices().await.unwrap();
+	assert_eq!(notices.len(), 1);
+	assert!(notices[0].panicked);
+}
+
+#[tokio::test]
+async fn demonitor() {
+	let ActorHandles {
+		message_handle: worker,
+		join_handle,
+		..
+	} = Worker::start(WorkerState);
+	let (watcher, _) = start_watcher();
+
+	watcher.watch(worker.clone()).await.unwrap();
+	watcher.unwatch(worker.clone()).await.unwrap();
+	worker.quit().await.unwrap();
+	// Notices are sent as the worker stops, so there will be no more of them once it has
+	join_handle.await;
+
+	assert!(watcher.notices().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn already_stopped() {
+	let ActorHandles {
+		message_handle: worker,
+		join_handle,
+		..
+	} = Worker::start(WorkerState);
+	let (watcher, noticed) = start_watcher();
+
+	worker.quit().await.unwrap();
+	join_handle.await;
+
+	let id = watcher.watch(worker.clone()).await.unwrap();
+	noticed.notified().await;
+
+	let notices = watcher.notices().await.unwrap();
+	assert_eq!(notices.len(), 1);
+	assert_eq!(notices[0].actor, id);
+}
@@ -0,0 +1,198 @@
+//! Tests that a performance written for any state type meeting some bounds can be shared by actors that name its role in `performs`
+use std::sync::Arc;
+
+use shakespeare::{ActorOutcome, Context, actor, performance, role};
+
+pub trait HasHealth {
+	fn healthy(&self) -> bool;
+	fn checks(&mut self) -> &mut u32;
+}
+
+pub trait HasItems<T> {
+	fn items(&mut self) -> &mut Vec<T>;
+}
+
+pub trait Named {
+	fn name() -> &'static str;
+}
+
+#[role]
+pub trait Naming {
+	fn name(&self) -> String;
+}
+
+#[role]
+pub trait Health {
+	fn check(&self) -> bool;
+	fn retire(&self);
+}
+
+#[role]
+pub trait Listing<T> {
+	fn add(&self, item: T) -> usize;
+	fn all(&self) -> Vec<T>;
+}
+
+#[performance]
+impl<S: HasHealth> Health for S {
+	fn check(&mut self) -> bool {
+		*self.checks() += 1;
+		self.healthy()
+	}
+
+	fn retire(&self, ctx: &mut Context<S>) {
+		ctx.stop();
+	}
+}
+
+#[performance]
+impl<S> Naming for S
+where
+	Box<S>: Named,
+{
+	fn name(&self) -> String {
+		<Box<S>>::name().to_owned()
+	}
+}
+
+#[performance]
+impl<S, T> Listing<T> for S
+where
+	S: HasItems<T>,
+	T: Clone + Send + 'static,
+{
+	#[batch(limit = 10)]
+	fn add(&mut self, items: Vec<T>) -> Vec<usize> {
+		items
+			.into_iter()
+			.map(|item| {
+				self.items().push(item);
+				self.items().len()
+			})
+			.collect()
+	}
+
+	async fn all(&mut self) -> Vec<T> {
+		self.items().clone()
+	}
+}
+
+#[actor(concurrent_reads, performs(Health, Naming))]
+pub mod Worker {
+	#[derive(Default)]
+	pub struct WorkerState {
+		pub checks: u32,
+		pub jobs:   u32,
+	}
+
+	impl HasHealth for WorkerState {
+		fn healthy(&self) -> bool {
+			self.jobs < 2
+		}
+
+		fn checks(&mut self) -> &mut u32 {
+			&mut self.checks
+		}
+	}
+
+	impl Named for Box<WorkerState> {
+		fn name() -> &'static str {
+			"worker"
+		}
+	}
+
+	#[performance(canonical)]
+	impl Jobs for WorkerState {
+		fn work(&mut self) {
+			self.jobs += 1;
+		}
+	}
+
+	fn stop(self) -> u32 {
+		self.checks
+	}
+}
+
+#[actor(performs(Health, Listing<T>))]
+pub mod Queue {
+	pub struct QueueState<T: Clone + Send + 'static> {
+		pub items:  Vec<T>,
+		pub checks: u32,
+	}
+
+	impl<T: Clone + Send + 'static> HasHealth for QueueState<T> {
+		fn healthy(&self) -> bool {
+			true
+		}
+
+		fn checks(&mut self) -> &mut u32 {
+			&mut self.checks
+		}
+	}
+
+	impl<T: Clone + Send + 'static> HasItems<T> for QueueState<T> {
+		fn items(&mut self) -> &mut Vec<T> {
+			&mut self.items
+		}
+	}
+}
+
+#[tokio::test]
+async fn shared() {
+	let handles = Worker::start(WorkerState::default());
+	let worker = handles.message_handle;
+	let queue = Queue::start(QueueState {
+		items:  vec![String::from("a")],
+		checks: 0,
+	})
+	.message_handle;
+
+	assert!(worker.check().await.unwrap());
+	worker.work().await.unwrap();
+	worker.work().await.unwrap();
+
+	let healths: Vec<Arc<dyn Health>> = vec![worker.clone(), queue.clone()];
+	let mut results = vec![];
+	for health in &healths {
+		results.push(health.check().await.unwrap());
+	}
+	assert_eq!(results, vec![false, true]);
+
+	worker.retire().await.unwrap();
+	drop((worker, healths));
+	match handles.join_handle.await {
+		ActorOutcome::Exit(checks) => assert_eq!(checks, 2),
+		ActorOutcome::Panic(_) => panic!("The actor should not have panicked"),
+	}
+}
+
+#[tokio::test]
+async fn generic() {
+	let words = Queue::start(QueueState {
+		items:  vec![String::from("a")],
+		checks: 0,
+	})
+	.message_handle;
+	let numbers: Arc<dyn Listing<u32>> = Queue::start(QueueState {
+		items:  vec![],
+		checks: 0,
+	})
+	.message_handle;
+
+	let added = [words.add(String::from("b")), words.add(String::from("c"))];
+	let mut lengths = vec![];
+	for add in added {
+		lengths.push(add.await.unwrap());
+	}
+	assert_eq!(lengths, vec![2, 3]);
+	assert_eq!(words.all().await.unwrap(), vec!["a", "b", "c"]);
+
+	numbers.add(7).await.unwrap();
+	assert_eq!(numbers.all().await.unwrap(), vec![7]);
+}
+
+#[tokio::test]
+async fn bounds_naming_state() {
+	let worker = Worker::start(WorkerState::default()).message_handle;
+	assert_eq!(worker.name().await.unwrap(), "worker");
+}
@@ -0,0 +1,73 @@
+//! Tests that performances with `when` handle messages only while the state matches, and that other messages go to the fallback
+use shakespeare::{ActorHandles, EnvelopeErr};
+
+#[shakespeare::role]
+trait Entrance {
+	fn open(&self);
+	fn close(&self);
+	fn enter(&self) -> Option<usize>;
+	fn knock(&self) -> &'static str;
+}
+
+#[shakespeare::actor]
+mod Door {
+	enum DoorState {
+		Closed,
+		Open { visitors: usize },
+	}
+
+	#[shakespeare::performance]
+	impl Entrance for DoorState {
+		fn open(&mut self) {
+			*self = DoorState::Open { visitors: 0 };
+		}
+
+		fn close(&mut self) {}
+
+		fn enter(&self) -> Option<usize> {
+			None
+		}
+	}
+
+	#[shakespeare::performance(when = DoorState::Open { .. })]
+	impl Entrance for DoorState {
+		fn close(&mut self) {
+			*self = DoorState::Closed;
+		}
+
+		fn enter(&mut self) -> Option<usize> {
+			let DoorState::Open { visitors } = self else {
+				unreachable!()
+			};
+			*visitors += 1;
+			Some(*visitors)
+		}
+	}
+
+	#[shakespeare::performance(when = DoorState::Closed)]
+	impl Entrance for DoorState {
+		fn knock(&self) -> &'static str {
+			"Who's there?"
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Door::start(DoorState::Closed);
+
+	assert_eq!(message_handle.enter().await.unwrap(), None);
+	assert_eq!(message_handle.knock().await.unwrap(), "Who's there?");
+
+	message_handle.open().await.unwrap();
+	assert_eq!(message_handle.enter().await.unwrap(), Some(1));
+	assert_eq!(message_handle.enter().await.unwrap(), Some(2));
+	// No performance handles knocking while the door is open
+	assert!(matches!(
+		message_handle.knock().await,
+		Err(EnvelopeErr::OutOfPhase)
+	));
+
+	message_handle.close().await.unwrap();
+	assert_eq!(message_handle.enter().await.unwrap(), None);
+}
@@ -0,0 +1,116 @@
+//! Tests that a role with a higher priority is served first, and that prioritised calls overtake others in the same queue
+use std::sync::Arc;
+
+use futures::poll;
+use shakespeare::ActorHandles;
+use tokio::sync::Notify;
+
+#[shakespeare::actor]
+mod Worker {
+	struct WorkerState {
+		log: Vec<&'static str>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Data for WorkerState {
+		// Looking for a later call that supersedes this one takes the calls behind it out of the queue too
+		#[conflate]
+		async fn refresh(&mut self, started: Arc<Notify>, gate: Arc<Notify>) {
+			started.notify_one();
+			gate.notified().await;
+			self.log.push("refresh");
+		}
+
+		fn process(&mut self, item: &'static str) {
+			self.log.push(item);
+		}
+
+		fn log(&self) -> Vec<&'static str> {
+			self.log.clone()
+		}
+	}
+
+	#[shakespeare::performance(canonical, priority = 10)]
+	impl Control for WorkerState {
+		fn health_check(&mut self) {
+			self.log.push("health check");
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Worker::start(WorkerState { log: vec![] });
+
+	// Polling each call once queues it, and the actor doesn't get to run until this task waits, so the calls are all waiting in the queues together
+	let mut calls = vec![
+		message_handle.process("first").into_future(),
+		message_handle.process("second").into_future(),
+		message_handle
+			.process("urgent")
+			.with_priority(5)
+			.into_future(),
+		message_handle
+			.process("background")
+			.with_priority(-1)
+			.into_future(),
+		message_handle.process("third").into_future(),
+		message_handle
+			.process("also urgent")
+			.with_priority(5)
+			.into_future(),
+	];
+	for call in &mut calls {
+		assert!(poll!(call).is_pending());
+	}
+	let mut check = message_handle.health_check().into_future();
+	assert!(poll!(&mut check).is_pending());
+
+	check.await.unwrap();
+	for call in calls {
+		call.await.unwrap();
+	}
+
+	assert_eq!(
+		message_handle.log().await.unwrap(),
+		vec![
+			"health check",
+			"urgent",
+			"also urgent",
+			"first",
+			"second",
+			"third",
+			"background"
+		]
+	);
+}
+
+#[tokio::test]
+async fn overtakes_leftovers() {
+	let ActorHandles { message_handle, .. } = Worker::start(WorkerState { log: vec![] });
+
+	let (started, gate) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+	let mut refresh = message_handle
+		.refresh(started.clone(), gate.clone())
+		.into_future();
+	let mut first = message_handle.process("first").into_future();
+	let mut second = message_handle.process("second").into_future();
+	assert!(poll!(&mut refresh).is_pending());
+	assert!(poll!(&mut first).is_pending());
+	assert!(poll!(&mut second).is_pending());
+	started.notified().await;
+
+	// The later calls have been taken out of the queue, but the higher priority role still goes before them
+	let mut check = message_handle.health_check().into_future();
+	assert!(poll!(&mut check).is_pending());
+	gate.notify_one();
+	refresh.await.unwrap();
+	check.await.unwrap();
+	first.await.unwrap();
+	second.await.unwrap();
+
+	assert_eq!(
+		message_handle.log().await.unwrap(),
+		vec!["refresh", "health check", "first", "second"]
+	);
+}
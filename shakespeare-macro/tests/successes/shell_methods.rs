@@ -0,0 +1,56 @@
+//! Tests that calls made directly on a shell go to the same queue, and give the same results, as calls made through its roles
+use std::sync::Arc;
+
+use shakespeare::{ActorHandles, Envelope};
+
+#[shakespeare::actor]
+mod Counter {
+	struct CounterState {
+		count: u32,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Count for CounterState {
+		fn add(&mut self, amount: u32) -> u32 {
+			self.count += amount;
+			self.count
+		}
+
+		fn describe(&self) -> String {
+			format!("counted {}", self.count)
+		}
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Describe for CounterState {
+		fn describe(&self) -> String {
+			String::from("a counter")
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Counter::start(CounterState { count: 0 });
+
+	let direct: Envelope<dyn Count, u32, Counter> = message_handle.add_direct(1);
+	assert_eq!(direct.await.unwrap(), 1);
+	message_handle.add_direct(2).ignore_return().await.unwrap();
+	drop(message_handle.add_direct(3));
+
+	// The role's own method is not hidden by the shell's
+	let through_role: Envelope<dyn Count, u32> = message_handle.add(4);
+	assert_eq!(through_role.await.unwrap(), 10);
+	let dynamic: Arc<dyn Count> = message_handle.clone();
+	assert_eq!(dynamic.add(5).await.unwrap(), 15);
+
+	// A name used by two roles has to go through one of them
+	assert_eq!(
+		Count::describe(&*message_handle).await.unwrap(),
+		"counted 15"
+	);
+	assert_eq!(
+		Describe::describe(&*message_handle).await.unwrap(),
+		"a counter"
+	);
+}
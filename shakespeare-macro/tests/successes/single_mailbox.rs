@@ -0,0 +1,51 @@
+//! Tests that calls made through different roles are handled in the order they were sent when the actor has one mailbox
+use shakespeare::ActorHandles;
+
+#[shakespeare::actor(single_mailbox)]
+mod Pipeline {
+	struct PipelineState {
+		log: Vec<String>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Init for PipelineState {
+		fn configure(&mut self, name: &'static str) {
+			self.log.push(format!("configure {name}"));
+		}
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Data for PipelineState {
+		#[batch(limit = 10)]
+		fn record(&mut self, items: Vec<u32>) {
+			self.log.push(format!("record {items:?}"));
+		}
+
+		fn log(&self) -> Vec<String> {
+			self.log.clone()
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Pipeline::start(PipelineState { log: vec![] });
+
+	// Dropping each call queues it straight away, and the actor doesn't get to run until this task waits, so the calls are all waiting in the mailbox together
+	drop(message_handle.configure("first"));
+	drop(message_handle.record(1));
+	drop(message_handle.record(2));
+	drop(message_handle.configure("second"));
+	drop(message_handle.record(3));
+
+	// A batch stops at a call made through another role
+	assert_eq!(
+		message_handle.log().await.unwrap(),
+		vec![
+			"configure first",
+			"record [1, 2]",
+			"configure second",
+			"record [3]"
+		]
+	);
+}
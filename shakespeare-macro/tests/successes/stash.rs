@@ -0,0 +1,77 @@
+//! Tests that a handler can stash the message it is handling until it is unstashed, while other messages carry on being handled
+use futures::poll;
+use shakespeare::{ActorHandles, Context, StashContext};
+
+#[shakespeare::actor]
+mod Connection {
+	struct ConnectionState {
+		connected: bool,
+		sent:      Vec<usize>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Data for ConnectionState {
+		#[stashable]
+		fn send(&mut self, ctx: &'_ mut StashContext<'_, Self, usize>, val: usize) -> usize {
+			if !self.connected {
+				ctx.stash(val);
+				return 0;
+			}
+			self.sent.push(val);
+			self.sent.len()
+		}
+
+		fn sent(&self) -> Vec<usize> {
+			self.sent.clone()
+		}
+
+		// A stashable method doesn't have to be able to stash
+		#[stashable]
+		fn pair(&mut self, _ctx: &'_ mut Context<Self>, a: usize, b: usize) -> usize {
+			a + b
+		}
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Control for ConnectionState {
+		fn connect(&mut self, ctx: &'_ mut Context<Self>) {
+			self.connected = true;
+			ctx.unstash_all();
+		}
+	}
+}
+
+#[tokio::test]
+async fn main() {
+	let ActorHandles { message_handle, .. } = Connection::start(ConnectionState {
+		connected: false,
+		sent:      vec![],
+	});
+
+	let mut first = message_handle.send(1).into_future();
+	let mut second = message_handle.send(2).into_future();
+	assert!(poll!(&mut first).is_pending());
+	assert!(poll!(&mut second).is_pending());
+
+	// Once a later call on the same role is answered, both have been stashed, and neither caller has been answered
+	assert_eq!(message_handle.sent().await.unwrap(), Vec::<usize>::new());
+	assert!(poll!(&mut first).is_pending());
+
+	message_handle.connect().await.unwrap();
+	let after = message_handle.send(3).await.unwrap();
+
+	assert_eq!(first.await.unwrap(), 1);
+	assert_eq!(second.await.unwrap(), 2);
+	assert_eq!(after, 3);
+	assert_eq!(message_handle.sent().await.unwrap(), vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn without_stashing() {
+	let ActorHandles { message_handle, .. } = Connection::start(ConnectionState {
+		connected: false,
+		sent:      vec![],
+	});
+
+	assert_eq!(message_handle.pair(1, 2).await.unwrap(), 3);
+}
@@ -0,0 +1,110 @@
+//! Tests that a role can extend other roles, so that it can be used wherever they can
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use shakespeare::{actor, role};
+
+#[role]
+pub trait Reader {
+	fn read(&self, key: u32) -> Option<String>;
+	fn size(&self) -> usize;
+}
+
+#[role]
+pub trait Admin: Reader {
+	fn write(&self, key: u32, value: String);
+	fn clear(&self) -> usize;
+}
+
+#[role]
+pub trait Source<T> {
+	fn latest(&self) -> Option<T>;
+}
+
+#[role]
+pub trait Feed<T>: Source<T> {
+	fn publish(&self, item: T);
+}
+
+#[actor]
+pub mod Db {
+	#[derive(Default)]
+	pub struct DbState {
+		pub entries: BTreeMap<u32, String>,
+	}
+
+	#[performance]
+	impl Reader for DbState {
+		fn read(&self, key: u32) -> Option<String> {
+			self.entries.get(&key).cloned()
+		}
+
+		fn size(&self) -> usize {
+			self.entries.len()
+		}
+	}
+
+	#[performance]
+	impl Admin for DbState {
+		fn write(&mut self, key: u32, value: String) {
+			self.entries.insert(key, value);
+		}
+
+		fn clear(&mut self) -> usize {
+			let size = self.entries.len();
+			self.entries.clear();
+			size
+		}
+	}
+}
+
+#[actor]
+pub mod Topic {
+	pub struct TopicState<T: Clone + Send + 'static> {
+		pub items: Vec<T>,
+	}
+
+	#[performance]
+	impl<T: Clone + Send + 'static> Source<T> for TopicState<T> {
+		fn latest(&self) -> Option<T> {
+			self.items.last().cloned()
+		}
+	}
+
+	#[performance]
+	impl<T: Clone + Send + 'static> Feed<T> for TopicState<T> {
+		fn publish(&mut self, item: T) {
+			self.items.push(item);
+		}
+	}
+}
+
+async fn size_of(reader: Arc<dyn Reader>) -> usize {
+	reader.size().await.unwrap()
+}
+
+#[tokio::test]
+async fn extends() {
+	let admin: Arc<dyn Admin> = Db::start(DbState::default()).message_handle;
+	admin.write(1, String::from("one")).await.unwrap();
+	admin.write(2, String::from("two")).await.unwrap();
+
+	// The parent's methods can be called through the subrole, and are handled by the parent's performance
+	assert_eq!(admin.read(1).await.unwrap().as_deref(), Some("one"));
+
+	// And the subrole can be used wherever the parent can
+	assert_eq!(size_of(admin.clone()).await, 2);
+	let reader: Arc<dyn Reader> = admin.clone();
+	assert_eq!(admin.clear().await.unwrap(), 2);
+	assert_eq!(reader.size().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn generic() {
+	let feed: Arc<dyn Feed<u32>> = Topic::start(TopicState { items: vec![] }).message_handle;
+	feed.publish(3).await.unwrap();
+	feed.publish(4).await.unwrap();
+
+	let source: Arc<dyn Source<u32>> = feed;
+	assert_eq!(source.latest().await.unwrap(), Some(4));
+}
@@ -0,0 +1,140 @@
+//! Tests that timers send their messages later, can be cancelled, and do not keep the actor alive
+use std::time::Duration;
+
+use shakespeare::{ActorHandles, ActorOutcome, Context, TimerHandle};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::time::{Instant, advance, timeout};
+
+#[shakespeare::actor]
+mod Clock {
+	struct ClockState {
+		ticks:     Vec<u32>,
+		heartbeat: Option<TimerHandle>,
+		// Told about each tick as it is handled
+		arrived:   UnboundedSender<u32>,
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Ticker for ClockState {
+		fn tick(&mut self, n: u32) {
+			self.ticks.push(n);
+			let _ = self.arrived.send(n);
+		}
+	}
+
+	#[shakespeare::performance(canonical)]
+	impl Control for ClockState {
+		fn tick_later(&mut self, ctx: &'_ mut Context<Self>, n: u32, delay: Duration) {
+			ctx.send_after::<dyn Ticker, _>(delay, n);
+		}
+
+		fn start_heartbeat(&mut self, ctx: &'_ mut Context<Self>, period: Duration) {
+			self.heartbeat = Some(ctx.send_interval::<dyn Ticker, _>(period, 0u32));
+		}
+
+		fn stop_heartbeat(&mut self) {
+			if let Some(heartbeat) = self.heartbeat.take() {
+				heartbeat.cancel();
+			}
+		}
+
+		fn ticks(&self) -> Vec<u32> {
+			self.ticks.clone()
+		}
+	}
+}
+
+fn new_clock() -> (ClockState, UnboundedReceiver<u32>) {
+	let (arrived, ticked) = unbounded_channel();
+	let state = ClockState {
+		ticks: vec![],
+		heartbeat: None,
+		arrived,
+	};
+	(state, ticked)
+}
+
+// The ticks go through a different queue from the calls that ask for them, so each is waited for before asking
+
+#[tokio::test(start_paused = true)]
+async fn send_after() {
+	let (state, mut ticked) = new_clock();
+	let ActorHandles { message_handle, .. } = Clock::start(state);
+
+	message_handle
+		.tick_later(1, Duration::from_millis(50))
+		.await
+		.unwrap();
+	advance(Duration::from_millis(49)).await;
+	assert_eq!(message_handle.ticks().await.unwrap(), Vec::<u32>::new());
+
+	advance(Duration::from_millis(1)).await;
+	assert_eq!(ticked.recv().await, Some(1));
+	assert_eq!(message_handle.ticks().await.unwrap(), vec![1]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn send_interval() {
+	let (state, mut ticked) = new_clock();
+	let ActorHandles { message_handle, .. } = Clock::start(state);
+
+	message_handle
+		.start_heartbeat(Duration::from_millis(20))
+		.await
+		.unwrap();
+	for _ in 0..3 {
+		advance(Duration::from_millis(20)).await;
+		assert_eq!(ticked.recv().await, Some(0));
+	}
+	message_handle.stop_heartbeat().await.unwrap();
+	assert_eq!(message_handle.ticks().await.unwrap(), vec![0, 0, 0]);
+
+	advance(Duration::from_millis(60)).await;
+	assert_eq!(message_handle.ticks().await.unwrap(), vec![0, 0, 0]);
+	assert!(ticked.try_recv().is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn send_at() {
+	let (state, mut ticked) = new_clock();
+	let ActorHandles { message_handle, .. } = Clock::start(state);
+
+	let timer = message_handle
+		.tick(1)
+		.send_at(Instant::now() + Duration::from_millis(30));
+	let cancelled = message_handle
+		.tick(2)
+		.send_at(Instant::now() + Duration::from_millis(30));
+	cancelled.cancel();
+	advance(Duration::from_millis(29)).await;
+	assert!(!timer.is_finished());
+
+	advance(Duration::from_millis(1)).await;
+	assert_eq!(ticked.recv().await, Some(1));
+	assert!(timer.is_finished());
+	assert_eq!(message_handle.ticks().await.unwrap(), vec![1]);
+	assert!(ticked.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn timers_do_not_keep_actor_alive() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = Clock::start(new_clock().0);
+
+	message_handle
+		.start_heartbeat(Duration::from_millis(10))
+		.await
+		.unwrap();
+	let _pending = message_handle
+		.tick(1)
+		.send_at(Instant::now() + Duration::from_secs(60));
+	drop(message_handle);
+
+	let outcome = timeout(Duration::from_secs(5), join_handle)
+		.await
+		.expect("The actor should stop while its timers are pending");
+	assert!(matches!(outcome, ActorOutcome::Exit(())));
+}
@@ -0,0 +1,133 @@
+//! Tests that the stop and catch hooks can be given the calls that were still waiting when the actor stopped
+use std::any::Any;
+use std::sync::Arc;
+
+use futures::poll;
+use shakespeare::{ActorHandles, ActorOutcome, Context, EnvelopeErr, Unhandled, actor};
+use tokio::sync::Notify;
+
+#[actor]
+pub mod Counter {
+	pub struct CounterState {
+		pub total:     u32,
+		// Where waiting calls go when the actor stops, rather than being turned down
+		pub successor: Option<Arc<Counter>>,
+	}
+
+	#[performance(canonical)]
+	impl Tally for CounterState {
+		async fn hold(&self, started: Arc<Notify>, gate: Arc<Notify>) {
+			started.notify_one();
+			gate.notified().await;
+		}
+
+		fn add(&mut self, amount: u32) -> u32 {
+			self.total += amount;
+			self.total
+		}
+
+		fn quit(&mut self, ctx: &'_ mut Context<Self>) {
+			ctx.stop();
+		}
+
+		fn explode(&mut self) {
+			panic!("The counter failed");
+		}
+	}
+
+	fn stop(self, mut pending: Unhandled<Self>) -> Vec<&'static str> {
+		let calls = pending.take::<dyn Tally>();
+		assert!(pending.is_empty());
+		let methods = calls.iter().map(|call| call.method()).collect();
+		for call in calls {
+			match &self.successor {
+				Some(successor) => call.forward_to(successor).unwrap(),
+				None => call.reject(),
+			}
+		}
+		methods
+	}
+
+	fn catch(self, _panic: Box<dyn Any + Send>, pending: Unhandled<Self>) -> Vec<&'static str> {
+		// Dropping the calls hangs up on their callers
+		vec![""; pending.len()]
+	}
+}
+
+fn start(successor: Option<Arc<Counter>>) -> ActorHandles<Counter> {
+	Counter::start(CounterState {
+		total: 0,
+		successor,
+	})
+}
+
+/// Keeps the actor busy so that the calls made while the returned gate is closed wait in its queue
+async fn hold(counter: &Arc<Counter>) -> Arc<Notify> {
+	let (started, gate) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+	drop(counter.hold(started.clone(), gate.clone()));
+	started.notified().await;
+	gate
+}
+
+#[tokio::test]
+async fn rejected_on_stop() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = start(None);
+
+	let gate = hold(&message_handle).await;
+	drop(message_handle.quit());
+	let mut first = message_handle.add(1).into_future();
+	let mut second = message_handle.add(2).into_future();
+	assert!(poll!(&mut first).is_pending());
+	assert!(poll!(&mut second).is_pending());
+	gate.notify_one();
+
+	assert!(matches!(first.await, Err(EnvelopeErr::Rejected)));
+	assert!(matches!(second.await, Err(EnvelopeErr::Rejected)));
+	assert!(matches!(join_handle.await, ActorOutcome::Exit(methods) if methods == ["add", "add"]));
+}
+
+#[tokio::test]
+async fn forwarded_on_stop() {
+	let successor = start(None).message_handle;
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = start(Some(successor.clone()));
+
+	let gate = hold(&message_handle).await;
+	drop(message_handle.quit());
+	let mut first = message_handle.add(1).into_future();
+	let mut second = message_handle.add(2).into_future();
+	assert!(poll!(&mut first).is_pending());
+	assert!(poll!(&mut second).is_pending());
+	gate.notify_one();
+
+	// The successor answers the original callers, in the order they called
+	assert_eq!(first.await.unwrap(), 1);
+	assert_eq!(second.await.unwrap(), 3);
+	assert!(matches!(join_handle.await, ActorOutcome::Exit(_)));
+	assert_eq!(successor.add(0).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn given_to_catch() {
+	let ActorHandles {
+		message_handle,
+		join_handle,
+		..
+	} = start(None);
+
+	let gate = hold(&message_handle).await;
+	drop(message_handle.explode());
+	let mut call = message_handle.add(1).into_future();
+	assert!(poll!(&mut call).is_pending());
+	gate.notify_one();
+
+	assert!(matches!(call.await, Err(EnvelopeErr::Hangup)));
+	assert!(matches!(join_handle.await, ActorOutcome::Panic(methods) if methods.len() == 1));
+}
@@ -3,8 +3,11 @@ use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use futures::Future;
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 
+use super::{Bury, Monitorable};
+
 /// Indicates whether an actor closed down successfully and any output value produced
 pub enum Outcome<A: Shell> {
 	/// The actor exited successfully either as all of its strong references dropped or by explicit shutdown.
@@ -50,11 +53,22 @@ where
 /// As with `JoinHandle`, dropping this value without awaiting it detaches the actor task and makes any output value from the actor inaccessible, but does **not** shut down the actor's processing.
 ///
 /// The exact types contained in the [`Outcome`] returned by the handle depends on whether the actor defines `stop` and/or a `catch` hook functions. (See the [actor macro](`crate::actor`) documentation.) If `stop` is defined, the `Exit` branch contains the same type as that function returns; if that function is not defined, it contains `()`. The same applies for `catch` and the `Panic` branch, except that if `catch` is not defined, the type is instead `Box<dyn std::any::Any + Send>`.
-pub struct ExitHandle<A: Shell>(JoinHandle<Result<A::ExitType, A::PanicType>>);
+pub struct ExitHandle<A: Shell>(
+	JoinHandle<Result<A::ExitType, A::PanicType>>,
+	Arc<StopSignal>,
+);
 
 impl<A: Shell> ExitHandle<A> {
-	fn new(val: JoinHandle<Result<A::ExitType, A::PanicType>>) -> ExitHandle<A> {
-		ExitHandle(val)
+	fn new(
+		val: JoinHandle<Result<A::ExitType, A::PanicType>>,
+		stop_signal: Arc<StopSignal>,
+	) -> ExitHandle<A> {
+		ExitHandle(val, stop_signal)
+	}
+
+	/// The means of telling the actor to stop from outside
+	pub(crate) fn stop_signal(&self) -> Arc<StopSignal> {
+		Arc::clone(&self.1)
 	}
 }
 
@@ -86,19 +100,27 @@ impl<A: Shell> Future for ExitHandle<A> {
 }
 
 /// A trait indicating the message-handling external interface of an actor. Implementations will be generated by macros - you should not implement this manually.
-pub trait Shell {
+pub trait Shell: Monitorable {
 	/// The internal state type within this shell
 	type StateType: State<ShellType = Self>;
 	/// The output type yielded by the actor exiting successfully
-	type ExitType;
+	type ExitType: Send + 'static;
 	/// The output type yielded by the actor's panic handler after a message handler panicked
-	type PanicType;
+	type PanicType: Send + 'static;
 }
 
 /// The state type of an actor. This trait is for macro use only.
 pub trait State {
 	/// The internal state type within this shell
 	type ShellType: Shell<StateType = Self>;
+	#[doc(hidden)]
+	/// A message sent via any of the actor's roles
+	type Payload: Send + Bury + 'static;
+	#[doc(hidden)]
+	/// Starts a new actor with this state, in the same way as the shell's `start` function
+	fn start_actor(self) -> ActorHandles<Self::ShellType>
+	where
+		Self: Sized;
 }
 
 /// Various values used to interact with an actor that has just been `start()`d.
@@ -123,10 +145,75 @@ impl<A: Shell> ActorHandles<A> {
 	pub fn new(
 		actor: Arc<A>,
 		handle: JoinHandle<Result<A::ExitType, A::PanicType>>,
+		stop_signal: Arc<StopSignal>,
 	) -> ActorHandles<A> {
 		ActorHandles {
 			message_handle: actor,
-			join_handle:    ExitHandle::new(handle),
+			join_handle:    ExitHandle::new(handle, stop_signal),
 		}
 	}
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum StopLevel {
+	Running,
+	// Stop once the current handler is finished, as for `Context::stop`
+	Stopping,
+	// Stop straight away, abandoning the current handler
+	Killed,
+}
+
+#[doc(hidden)]
+/// Lets an actor be told to stop by something outside of it, currently its parent
+#[derive(Debug)]
+pub struct StopSignal(watch::Sender<StopLevel>);
+
+impl Default for StopSignal {
+	fn default() -> Self {
+		StopSignal(watch::Sender::new(StopLevel::Running))
+	}
+}
+
+impl StopSignal {
+	fn raise(&self, level: StopLevel) {
+		self.0.send_if_modified(|current| {
+			let raised = *current < level;
+			*current = (*current).max(level);
+			raised
+		});
+	}
+
+	async fn reached(&self, level: StopLevel) {
+		let _ = self
+			.0
+			.subscribe()
+			.wait_for(|current| *current >= level)
+			.await;
+	}
+
+	/// Asks the actor to stop once its current handler is finished
+	pub(crate) fn stop(&self) {
+		self.raise(StopLevel::Stopping);
+	}
+
+	/// Tells the actor to abandon its current handler and stop straight away
+	pub(crate) fn kill(&self) {
+		self.raise(StopLevel::Killed);
+	}
+
+	/// Whether the actor has been asked to stop
+	#[must_use]
+	pub fn is_stopping(&self) -> bool {
+		*self.0.borrow() >= StopLevel::Stopping
+	}
+
+	/// Resolves when the actor has been asked to stop
+	pub async fn stopping(&self) {
+		self.reached(StopLevel::Stopping).await;
+	}
+
+	/// Resolves when the actor has been told to stop straight away
+	pub async fn killed(&self) {
+		self.reached(StopLevel::Killed).await;
+	}
+}
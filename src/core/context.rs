@@ -1,6 +1,32 @@
-use std::sync::Arc;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
 
-use super::State;
+use futures::future::join_all;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, timeout};
+
+use super::deadletter::{DeadLetter, bury_for_gone};
+use super::handoff::{ForwardTo, Forwarder, Successor};
+use super::returnval::{deliver_or_bury, send_or_spawn};
+use super::timer::{self, TimerHandle};
+use super::{
+	Accepts, ActorHandles, ActorId, Monitorable, Outcome, Performs, ReturnEnvelope, ReturnPath,
+	Role, State, StopSignal, Termination,
+};
+
+/// How long children are given to stop on their own once their parent has stopped, before their current handler is abandoned
+const CHILD_STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+/// An actor started by another with [`Context::spawn_child`]
+struct Child {
+	stop_signal: Arc<StopSignal>,
+	// Finishes after the child has stopped and its outcome has been dealt with
+	exited:      JoinHandle<()>,
+}
 
 #[derive(Debug)]
 /// Various options for controlling the behaviour of the currently running actor.
@@ -8,11 +34,33 @@ use super::State;
 /// This is what you need if you want to:
 /// * get a copy of the currently running actor's handle
 /// * stop the currently running actor without waiting for all handles to drop
+/// * replay the messages that `#[stashable]` methods set aside with [`StashContext::stash`](crate::StashContext::stash)
+/// * send the actor a message later, or repeatedly, such as for heartbeats or retries
+/// * start other actors that should not outlive the current one
+/// * find out when another actor stops
+/// * hand the actor's state and messages over to a new actor, without invalidating its handles
 ///
 /// To access this, the performance signature should take a `&mut Context<Self>` as its second parameter after the receiver.
 pub struct Context<A: State + ?Sized> {
 	shell_handle: Arc<A::ShellType>,
 	running:      bool,
+	stop_signal:  Arc<StopSignal>,
+	children:     Vec<Child>,
+	// Only ever changed through `&mut self`, the locks just let shared handlers hold a `&Context` across threads
+	stash:        Mutex<VecDeque<A::Payload>>,
+	unstashed:    Mutex<VecDeque<A::Payload>>,
+	successor:    Mutex<Option<SuccessorSlot<A>>>,
+	// The panic from the successor the actor handed off to, if it panics
+	handed_off:   Arc<Mutex<Option<Box<dyn Any + Send>>>>,
+}
+
+/// Holds the successor an actor is handing off to, so that the context can still be printed
+struct SuccessorSlot<A: State + ?Sized>(Successor<A>);
+
+impl<A: State + ?Sized> std::fmt::Debug for SuccessorSlot<A> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("Successor")
+	}
 }
 
 impl<A: State + ?Sized> Context<A> {
@@ -21,6 +69,12 @@ impl<A: State + ?Sized> Context<A> {
 		Context {
 			shell_handle,
 			running: true,
+			stop_signal: Arc::default(),
+			children: Vec::new(),
+			stash: Mutex::default(),
+			unstashed: Mutex::default(),
+			successor: Mutex::new(None),
+			handed_off: Arc::default(),
 		}
 	}
 
@@ -28,7 +82,48 @@ impl<A: State + ?Sized> Context<A> {
 	/// Whether the message queue should still be held open
 	#[must_use]
 	pub fn is_running(&self) -> bool {
-		Arc::strong_count(&self.shell_handle) > 1 && self.running
+		Arc::strong_count(&self.shell_handle) > 1 && self.running && !self.stop_signal.is_stopping()
+	}
+
+	#[doc(hidden)]
+	/// Whether the actor has been told to stop, by [`Context::stop`] or by its parent, so should not handle any more messages
+	#[must_use]
+	pub fn is_stopped(&self) -> bool {
+		!self.running || self.stop_signal.is_stopping()
+	}
+
+	#[doc(hidden)]
+	/// Whether anything other than the actor itself still holds a handle to it
+	#[must_use]
+	pub fn has_handles(&self) -> bool {
+		Arc::strong_count(&self.shell_handle) > 1
+	}
+
+	#[doc(hidden)]
+	/// Starts the successor set with [`Context::hand_off`], if there is one, as a child of this actor, and gives back the means of forwarding messages to it
+	pub fn start_successor(&mut self, state: &mut A) -> Option<Forwarder<A>> {
+		let start = self
+			.successor
+			.get_mut()
+			.unwrap_or_else(PoisonError::into_inner)
+			.take()?;
+		Some((start.0)(state, self))
+	}
+
+	#[doc(hidden)]
+	/// The panic from the successor started by [`Context::start_successor`], if it panicked
+	pub fn take_successor_panic(&mut self) -> Option<Box<dyn Any + Send>> {
+		self.handed_off
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.take()
+	}
+
+	#[doc(hidden)]
+	/// Lets the actor be told to stop from outside
+	#[must_use]
+	pub fn stop_signal(&self) -> Arc<StopSignal> {
+		Arc::clone(&self.stop_signal)
 	}
 
 	#[must_use]
@@ -37,8 +132,270 @@ impl<A: State + ?Sized> Context<A> {
 		self.shell_handle.clone()
 	}
 
+	#[must_use]
+	/// Gets the ID of the currently running actor
+	pub fn id(&self) -> ActorId {
+		self.shell_handle.lifecycle().id()
+	}
+
+	/// Sets what is given every [`DeadLetter`] sent to this actor, instead of the sink set by [`set_dead_letter_sink`](crate::set_dead_letter_sink), replacing any sink set before.
+	///
+	/// The sink is kept until all of the actor's handles have dropped, so it sees messages that are sent after the actor has stopped. It is called on whichever task failed to send the message, so should not block.
+	pub fn set_dead_letter_sink(&self, sink: impl Fn(DeadLetter) + Send + Sync + 'static) {
+		self.shell_handle
+			.lifecycle()
+			.set_dead_letter_sink(Arc::new(sink));
+	}
+
 	/// Stops the actor and runs the exit function after the current performance handler is completed
 	pub fn stop(&mut self) {
 		self.running = false;
 	}
+
+	/// Hands this actor over to a successor, which takes over every message sent to it from then on.
+	///
+	/// Once the current handler finishes, `successor` is called with the actor's state to build the successor's state, and the successor is started. Everything this actor had received but not yet handled, including its stash, is then passed on to the successor in order, followed by every message sent to this actor afterwards, so every existing handle to this actor stays valid and its callers are answered by the successor. The successor can be a different actor with a different state type, as long as it performs all of the same Roles, which makes this useful for upgrading an actor's logic or migrating its state to a new version.
+	///
+	/// This actor's children are stopped as for [`Context::stop`]. Its `stop` function is run with whatever `successor` leaves of the state, so `successor` can take what it needs with [`std::mem::take`] and similar. This actor's [`ExitHandle`](crate::ExitHandle) only completes, and actors monitoring it are only told that it has stopped, once all of its handles have dropped and it has nothing left to pass on.
+	///
+	/// The successor is a child of this actor, as for [`Context::spawn_child`], so it is stopped once this actor has nothing left to pass on, and this actor only finishes once the successor has. If the successor panics, this actor's `catch` function is run instead of `stop`, with the successor's [`ActorOutcome::Panic`](crate::ActorOutcome::Panic) value as the panic. If this actor is told to stop by its parent before the current handler finishes, the hand-off doesn't happen, and the actor stops as normal. If it is told to stop while passing messages on, it stops passing them on, and the messages still waiting are treated as for any other actor that has stopped.
+	///
+	/// Calling this again before the current handler finishes replaces the earlier successor.
+	pub fn hand_off<S, F>(&mut self, successor: F)
+	where
+		S: State<ShellType: 'static>,
+		A::Payload: ForwardTo<S::ShellType>,
+		F: FnOnce(&mut A) -> S + Send + 'static,
+	{
+		let start: Successor<A> = Box::new(move |state, ctx| {
+			let slot = Arc::clone(&ctx.handed_off);
+			let successor = ctx.link_child(successor(state), move |outcome| async move {
+				if let Outcome::Panic(panic) = outcome {
+					let panic: Box<dyn Any + Send> = Box::new(panic);
+					*slot.lock().unwrap_or_else(PoisonError::into_inner) = Some(panic);
+				}
+			});
+			Box::new(move |mail: A::Payload| mail.forward_to(&successor))
+		});
+		*self
+			.successor
+			.get_mut()
+			.unwrap_or_else(PoisonError::into_inner) = Some(SuccessorSlot(start));
+		self.stop();
+	}
+
+	#[doc(hidden)]
+	/// Puts a message in the stash, after any already there
+	pub fn keep_stashed<R>(&mut self, msg: ReturnEnvelope<R>)
+	where
+		R: Role + ?Sized + 'static,
+		A::Payload: From<ReturnEnvelope<R>>,
+	{
+		self.stash
+			.get_mut()
+			.unwrap_or_else(PoisonError::into_inner)
+			.push_back(msg.into());
+	}
+
+	/// Puts all of the stashed messages back at the front of the mailbox.
+	///
+	/// The stashed messages are handled before any other message that has not already started being handled, and in the same order they arrived in. If the actor stops before it handles them, their callers see the same error as for any other message that was still queued. A handler that calls both this and [`StashContext::stash`](crate::StashContext::stash) stashes its own message again afterwards, as that happens once it has finished.
+	pub fn unstash_all(&mut self) {
+		let stash = self.stash.get_mut().unwrap_or_else(PoisonError::into_inner);
+		let mut stash = std::mem::take(stash);
+		let unstashed = self
+			.unstashed
+			.get_mut()
+			.unwrap_or_else(PoisonError::into_inner);
+		stash.append(unstashed);
+		*unstashed = stash;
+	}
+
+	/// Sends `payload` to this actor via the role `R` after `delay` has passed.
+	///
+	/// `R` must [`Accept`](Accepts) the payload in the same way as for [`Message::send_when_ready`](crate::Message::send_when_ready), and may need to be named if more than one of the actor's roles do, e.g. `ctx.send_after::<dyn Ticker, _>(delay, payload)`. Any return value from handling the message is thrown away.
+	///
+	/// The timer only holds a weak reference to the actor, so it does not stop the actor shutting down when the last handle drops. The returned [`TimerHandle`] can be used to cancel the message before it is sent.
+	pub fn send_after<R, T>(&self, delay: Duration, payload: T) -> TimerHandle
+	where
+		R: Accepts<T> + ?Sized + 'static,
+		A::ShellType: Performs<R> + 'static,
+	{
+		let envelope = ReturnEnvelope {
+			payload:     R::into_payload(payload),
+			return_path: ReturnPath::Discard,
+			priority:    0,
+		};
+		timer::send_at(&self.shell_handle, Instant::now() + delay, envelope)
+	}
+
+	/// Sends a copy of `payload` to this actor via the role `R` once every `period`, starting one `period` from now.
+	///
+	/// This works in the same way as [`Context::send_after`], except that the timer keeps going until it is cancelled through the returned [`TimerHandle`] or the actor stops. If the actor falls behind, ticks that were missed are skipped rather than sent all at once.
+	///
+	/// # Panics
+	///
+	/// Panics if `period` is zero.
+	pub fn send_interval<R, T>(&self, period: Duration, payload: T) -> TimerHandle
+	where
+		R: Accepts<T> + ?Sized + 'static,
+		A::ShellType: Performs<R> + 'static,
+		T: Clone + Send + 'static,
+	{
+		assert!(!period.is_zero(), "send_interval period must be non-zero");
+		timer::send_every::<R, _, _>(&self.shell_handle, period, payload)
+	}
+
+	/// Starts a new actor with the given state as a child of this one, and returns its handle.
+	///
+	/// A child is stopped when this actor stops for any reason, including a handler panicking. The child is first asked to stop in the same way as if it had called [`Context::stop`] itself, and if it has not stopped a second later, its current handler is abandoned and it stops straight away. Either way, the child's `stop` function is run, and this actor's own `stop` or `catch` function is only run once all of its children have stopped, so that a tree of actors is shut down from the leaves upwards.
+	///
+	/// A child can still stop before its parent does. See [`Context::spawn_child_reporting`] to find out when it has.
+	pub fn spawn_child<S>(&mut self, state: S) -> Arc<S::ShellType>
+	where
+		S: State<ShellType: 'static>,
+	{
+		self.link_child(state, |_| async {})
+	}
+
+	/// Starts a new child actor in the same way as [`Context::spawn_child`], and sends the child's [`ActorOutcome`](crate::ActorOutcome) to this actor via the role `R` when it stops.
+	///
+	/// This is like passing the child's [`ExitHandle`](crate::ExitHandle) to [`Message::send_when_ready`](crate::Message::send_when_ready), except that it does not keep this actor alive until the child stops, which would stop the two ever shutting down. The outcome is thrown away if this actor has already stopped.
+	pub fn spawn_child_reporting<R, S>(&mut self, state: S) -> Arc<S::ShellType>
+	where
+		R: Accepts<Outcome<S::ShellType>> + ?Sized + 'static,
+		A::ShellType: Performs<R> + 'static,
+		S: State<ShellType: 'static>,
+	{
+		let parent_id = self.id();
+		let parent = Arc::downgrade(&self.shell_handle);
+		self.link_child(state, move |outcome| async move {
+			let envelope = ReturnEnvelope {
+				payload:     R::into_payload(outcome),
+				return_path: ReturnPath::Discard,
+				priority:    0,
+			};
+			match parent.upgrade() {
+				Some(parent) => {
+					deliver_or_bury(parent, envelope).await;
+				}
+				None => bury_for_gone(parent_id, envelope),
+			}
+		})
+	}
+
+	fn link_child<S, F, Fut>(&mut self, state: S, on_exit: F) -> Arc<S::ShellType>
+	where
+		S: State<ShellType: 'static>,
+		F: FnOnce(Outcome<S::ShellType>) -> Fut + Send + 'static,
+		Fut: Future<Output = ()> + Send,
+	{
+		self.children.retain(|child| !child.exited.is_finished());
+
+		let ActorHandles {
+			message_handle,
+			join_handle,
+		} = state.start_actor();
+		let stop_signal = join_handle.stop_signal();
+		let exited = tokio::spawn(async move { on_exit(join_handle.await).await });
+		self.children.push(Child {
+			stop_signal,
+			exited,
+		});
+		message_handle
+	}
+
+	#[doc(hidden)]
+	/// Stops all of the actor's children, and waits for them to finish
+	pub async fn stop_children(&mut self) {
+		let mut children = std::mem::take(&mut self.children);
+		for child in &children {
+			child.stop_signal.stop();
+		}
+		let all_exited = join_all(children.iter_mut().map(|child| &mut child.exited));
+		if timeout(CHILD_STOP_TIMEOUT, all_exited).await.is_err() {
+			for child in &children {
+				child.stop_signal.kill();
+			}
+			join_all(children.iter_mut().map(|child| &mut child.exited)).await;
+		}
+	}
+
+	/// Starts monitoring the actor behind `handle`, and returns its ID.
+	///
+	/// When the monitored actor stops, for any reason, a [`Termination`] notice is sent to this actor via the role `R`, which must [`Accept`](Accepts) it. The notice holds the ID returned here, so that an actor monitoring several others can tell which one has stopped. If the monitored actor has already stopped, the notice is sent straight away.
+	///
+	/// Any number of actors can monitor the same actor. Monitoring an actor does not keep either actor alive, and each actor is sent at most one notice per actor it monitors, however many times it calls this.
+	pub fn monitor<R, T>(&self, handle: &Arc<T>) -> ActorId
+	where
+		R: Accepts<Termination> + ?Sized + 'static,
+		A::ShellType: Performs<R> + 'static,
+		T: Monitorable + ?Sized,
+	{
+		let watcher_id = self.id();
+		let watcher = Arc::downgrade(&self.shell_handle);
+		let lifecycle = handle.lifecycle();
+		lifecycle.monitor(
+			watcher_id,
+			Box::new(move |termination| {
+				let envelope = ReturnEnvelope {
+					payload:     R::into_payload(termination),
+					return_path: ReturnPath::Discard,
+					priority:    0,
+				};
+				match watcher.upgrade() {
+					Some(watcher) => send_or_spawn(watcher, envelope),
+					None => bury_for_gone(watcher_id, envelope),
+				}
+			}),
+		);
+		lifecycle.id()
+	}
+
+	/// Stops monitoring the actor behind `handle`, so that this actor is not sent a notice when it stops. This does nothing if this actor was not monitoring it.
+	pub fn demonitor<T: Monitorable + ?Sized>(&self, handle: &Arc<T>) {
+		handle.lifecycle().demonitor(self.id());
+	}
+
+	#[doc(hidden)]
+	/// Tells every actor monitoring this one that it has stopped
+	pub fn terminate(&self, panicked: bool) {
+		self.shell_handle.lifecycle().terminate(panicked);
+	}
+
+	#[doc(hidden)]
+	/// Whether there are messages that have been taken out of the stash but not handled yet
+	#[must_use]
+	pub fn has_unstashed(&self) -> bool {
+		!self
+			.unstashed
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.is_empty()
+	}
+
+	#[doc(hidden)]
+	/// Looks at the next message that has been taken out of the stash, if any, without taking it
+	pub fn peek_unstashed<T>(&mut self, f: impl FnOnce(&A::Payload) -> T) -> Option<T> {
+		self.unstashed
+			.get_mut()
+			.unwrap_or_else(PoisonError::into_inner)
+			.front()
+			.map(f)
+	}
+
+	#[doc(hidden)]
+	/// The next message that has been taken out of the stash, if any
+	pub fn take_unstashed(&mut self) -> Option<A::Payload> {
+		self.unstashed
+			.get_mut()
+			.unwrap_or_else(PoisonError::into_inner)
+			.pop_front()
+	}
+
+	#[doc(hidden)]
+	/// Empties the stash, giving back the messages in it in the order they arrived
+	pub fn take_stash(&mut self) -> VecDeque<A::Payload> {
+		std::mem::take(self.stash.get_mut().unwrap_or_else(PoisonError::into_inner))
+	}
 }
@@ -0,0 +1,205 @@
+use std::any::{Any, type_name};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use super::{ActorId, Lifecycle, ReturnEnvelope, ReturnPath, Role, TrySendError};
+
+/// Something that is given every [`DeadLetter`] for an actor, or for all actors. See [`set_dead_letter_sink`](crate::set_dead_letter_sink) and [`Context::set_dead_letter_sink`](crate::Context::set_dead_letter_sink).
+pub(crate) type Sink = Arc<dyn Fn(DeadLetter) + Send + Sync>;
+
+static GLOBAL_SINK: RwLock<Option<Sink>> = RwLock::new(None);
+
+/// Why a [`DeadLetter`] could not be delivered
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterReason {
+	/// The actor had already stopped when the message was sent
+	Stopped,
+	/// The message was delivered, but the actor stopped before handling it, and its `stop` or `catch` function did not take it
+	Unhandled,
+	/// The message was delivered, but a later call to the same `#[conflate]` method replaced it before it was handled
+	Superseded,
+}
+
+/// A message that could not be delivered, and that nothing was waiting on to find out.
+///
+/// Messages sent by dropping an [`Envelope`](crate::Envelope), by [`Message::send_when_ready`](crate::Message::send_when_ready), [`MessageStream::feed_to`](crate::MessageStream::feed_to) or a timer have no caller to report a failure to, so if the actor has stopped by the time they are sent, they are given to a dead-letter sink instead, if one has been set. The same goes for these messages, and ones whose return value is being sent on with [`Envelope::forward_to`](crate::Envelope::forward_to), when a later call to the same `#[conflate]` method supersedes them. The actor's own sink, from [`Context::set_dead_letter_sink`](crate::Context::set_dead_letter_sink), is used if it has one, otherwise the one from [`set_dead_letter_sink`](crate::set_dead_letter_sink).
+///
+/// A sink can log the message using the details here, or send it on to a different actor with [`DeadLetter::redeliver`]. If the sink panics, the panic is caught and the letter is lost.
+#[derive(Debug)]
+pub struct DeadLetter {
+	target:  ActorId,
+	role:    &'static str,
+	method:  &'static str,
+	reason:  DeadLetterReason,
+	// Always a ReturnEnvelope<R> for the role named by `role`
+	message: Box<dyn Any + Send>,
+}
+
+impl DeadLetter {
+	fn new<R: Role + ?Sized + 'static>(
+		target: ActorId,
+		envelope: ReturnEnvelope<R>,
+		reason: DeadLetterReason,
+	) -> DeadLetter {
+		DeadLetter {
+			target,
+			role: type_name::<R>(),
+			method: R::method_name(&envelope.payload),
+			reason,
+			message: Box::new(envelope),
+		}
+	}
+
+	/// The actor the message was sent to
+	#[must_use]
+	pub fn target(&self) -> ActorId {
+		self.target
+	}
+
+	/// The name of the Role the message was sent through, which is the same as [`std::any::type_name`] gives for `dyn Role`
+	#[must_use]
+	pub fn role(&self) -> &'static str {
+		self.role
+	}
+
+	/// The name of the Role method that was called
+	#[must_use]
+	pub fn method(&self) -> &'static str {
+		self.method
+	}
+
+	/// Why the message could not be delivered
+	#[must_use]
+	pub fn reason(&self) -> DeadLetterReason {
+		self.reason
+	}
+
+	/// Sends the message to a different actor that performs the same Role.
+	///
+	/// # Errors
+	///
+	/// Gives back the letter unchanged if `R` is not the Role the message was sent through, or a new letter for `dest` if it has also stopped. That letter is not given to any dead-letter sink, so that a sink redelivering its letters cannot loop forever.
+	pub fn redeliver<R: Role + ?Sized + 'static>(self, dest: &Arc<R>) -> Result<(), DeadLetter> {
+		let envelope = match self.message.downcast::<ReturnEnvelope<R>>() {
+			Ok(envelope) => *envelope,
+			Err(message) => return Err(DeadLetter { message, ..self }),
+		};
+		match dest.try_enqueue(envelope) {
+			Ok(()) => Ok(()),
+			Err(TrySendError::Full(envelope)) => {
+				let dest = Arc::clone(dest);
+				tokio::spawn(async move {
+					let _ = dest.enqueue(envelope).await;
+				});
+				Ok(())
+			}
+			Err(TrySendError::Closed(envelope)) => Err(DeadLetter::new(
+				dest.lifecycle().id(),
+				envelope,
+				DeadLetterReason::Stopped,
+			)),
+		}
+	}
+}
+
+/// Sets what is given every [`DeadLetter`] for an actor that does not have its own sink, replacing any sink set before.
+///
+/// The sink is called on whichever task failed to send the message, so should not block.
+pub fn set_dead_letter_sink(sink: impl Fn(DeadLetter) + Send + Sync + 'static) {
+	replace_global_sink(Some(Arc::new(sink)));
+}
+
+/// Removes the sink set by [`set_dead_letter_sink`], so that dead letters for actors without their own sink are dropped.
+pub fn clear_dead_letter_sink() {
+	replace_global_sink(None);
+}
+
+/// Sets the sink as [`set_dead_letter_sink`] does, until the returned guard drops, when the sink that was set before is put back.
+///
+/// Guards should be dropped in the reverse of the order they were made in, or an outer guard may put back a sink that an inner one had replaced.
+pub fn scoped_dead_letter_sink(
+	sink: impl Fn(DeadLetter) + Send + Sync + 'static,
+) -> DeadLetterSinkGuard {
+	DeadLetterSinkGuard {
+		previous: replace_global_sink(Some(Arc::new(sink))),
+	}
+}
+
+/// Puts back the global dead-letter sink that was set before [`scoped_dead_letter_sink`] when it drops
+#[must_use = "The sink is removed as soon as the guard drops"]
+pub struct DeadLetterSinkGuard {
+	previous: Option<Sink>,
+}
+
+impl Drop for DeadLetterSinkGuard {
+	fn drop(&mut self) {
+		replace_global_sink(self.previous.take());
+	}
+}
+
+impl std::fmt::Debug for DeadLetterSinkGuard {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DeadLetterSinkGuard")
+			.finish_non_exhaustive()
+	}
+}
+
+#[doc(hidden)]
+/// A message that an actor received but stopped before handling, which is given to a dead-letter sink unless a caller is waiting on it
+pub trait Bury {
+	fn bury(self, target: &Lifecycle);
+}
+
+impl<R: Role + ?Sized + 'static> Bury for ReturnEnvelope<R> {
+	fn bury(self, target: &Lifecycle) {
+		// A waiting caller is told instead, by seeing the actor hang up
+		if !matches!(self.return_path, ReturnPath::Immediate(_)) {
+			bury(target, self, DeadLetterReason::Unhandled);
+		}
+	}
+}
+
+/// Gives an undeliverable message to the target actor's sink, or the global sink if it has none, or drops it if there is neither
+pub(crate) fn bury<R: Role + ?Sized + 'static>(
+	target: &Lifecycle,
+	envelope: ReturnEnvelope<R>,
+	reason: DeadLetterReason,
+) {
+	let sink = target.dead_letter_sink().or_else(global_sink);
+	if let Some(sink) = sink {
+		give(&sink, DeadLetter::new(target.id(), envelope, reason));
+	}
+}
+
+/// Gives an undeliverable message to the global sink, for an actor that has stopped and whose handles have all dropped, so that only its ID is known
+pub(crate) fn bury_for_gone<R: Role + ?Sized + 'static>(
+	target: ActorId,
+	envelope: ReturnEnvelope<R>,
+) {
+	if let Some(sink) = global_sink() {
+		give(
+			&sink,
+			DeadLetter::new(target, envelope, DeadLetterReason::Stopped),
+		);
+	}
+}
+
+/// Calls the sink, which is often done while dropping an envelope, so must not panic
+fn give(sink: &Sink, letter: DeadLetter) {
+	let _ = catch_unwind(AssertUnwindSafe(|| sink(letter)));
+}
+
+fn global_sink() -> Option<Sink> {
+	GLOBAL_SINK
+		.read()
+		.unwrap_or_else(PoisonError::into_inner)
+		.clone()
+}
+
+fn replace_global_sink(sink: Option<Sink>) -> Option<Sink> {
+	std::mem::replace(
+		&mut *GLOBAL_SINK.write().unwrap_or_else(PoisonError::into_inner),
+		sink,
+	)
+}
@@ -0,0 +1,93 @@
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use tokio::select;
+use tokio::time::{Instant, Sleep, sleep};
+
+use super::{Context, Receiver, State, StopSignal};
+use crate::catch_future;
+
+#[doc(hidden)]
+/// How long an actor waits for a message before checking whether its handles have all dropped
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[doc(hidden)]
+/// What an actor's event loop does after waiting for a message
+#[derive(Debug)]
+pub enum Wait<M> {
+	/// Handles the message
+	Mail(M),
+	/// Looks again, as it was woken up to check whether the actor should stop
+	Woken,
+	/// Stops, as the queues are empty and the actor is no longer running
+	Finished,
+}
+
+#[doc(hidden)]
+/// How long an actor has been waiting for a message
+#[derive(Debug)]
+pub struct IdleTimer(Pin<Box<Sleep>>);
+
+impl Default for IdleTimer {
+	fn default() -> Self {
+		IdleTimer(Box::pin(sleep(IDLE_TIMEOUT)))
+	}
+}
+
+impl IdleTimer {
+	/// Starts the wait over, as the actor has just been given something to do
+	pub fn reset(&mut self) {
+		self.0.as_mut().reset(Instant::now() + IDLE_TIMEOUT);
+	}
+}
+
+#[doc(hidden)]
+/// Waits for `queues` to give the actor's next message, or `None` once none of them can.
+///
+/// While the actor is running, this also wakes up when it is asked to stop, or has waited for [`IDLE_TIMEOUT`] in case its handles have all dropped, so that the event loop can make `queues` again, checking whether the actor is still running as it does.
+pub async fn wait_for_mail<A, M>(
+	context: &Context<A>,
+	stop_signal: &StopSignal,
+	idle: &mut IdleTimer,
+	queues: impl Future<Output = Option<M>>,
+) -> Wait<M>
+where
+	A: State + ?Sized,
+{
+	select! {
+		biased;
+		mail = queues => match mail {
+			Some(mail) => {
+				idle.reset();
+				Wait::Mail(mail)
+			}
+			None => Wait::Finished,
+		},
+		() = stop_signal.stopping(), if context.is_running() => Wait::Woken,
+		() = idle.0.as_mut(), if context.is_running() => {
+			idle.reset();
+			Wait::Woken
+		}
+	}
+}
+
+#[doc(hidden)]
+/// Runs the actor's event loop, catching any panic, until it finishes or the actor is killed, which abandons the handler that was running
+pub async fn run_until_killed(
+	stop_signal: &StopSignal,
+	event_loop: impl Future<Output = ()>,
+) -> Result<(), Box<dyn Any + Send>> {
+	select! {
+		result = catch_future(event_loop) => result,
+		() = stop_signal.killed() => Ok(()),
+	}
+}
+
+#[doc(hidden)]
+/// Closes the queue, so that anything sent from now on fails to send rather than waiting forever, and takes out everything still in it
+pub fn close_and_drain<T: Send>(receiver: &mut impl Receiver<T>) -> impl Iterator<Item = T> {
+	receiver.close();
+	std::iter::from_fn(|| receiver.try_recv())
+}
@@ -0,0 +1,265 @@
+use std::any::Any;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{
+	ActorHandles, Bury, Channel, Context, Emits, Envelope, IdleTimer, Lifecycle, Monitorable,
+	Receiver, ReturnEnvelope, Role, Sender, Shell, State, TrySendError, Wait, close_and_drain,
+	run_until_killed, wait_for_mail,
+};
+use crate::{PriorityUnbounded, Role2SendError};
+
+/// What the actor gives back instead of its state if it is killed while handling a message
+const ABANDONED: &str = "The actor was killed while handling a message, so its state was lost";
+
+/// The function run for each message, which is given the state and gives it back along with the reply
+type Handler<S, T, R> = Box<dyn FnMut(S, T) -> Pin<Box<dyn Future<Output = (R, S)> + Send>> + Send>;
+
+/// The shell of an actor made from a single function, with [`spawn_fn`] or an `#[actor]` function, that is sent messages of type `T` and replies with `R`. `S` is the actor's state.
+///
+/// The shell is its own [`Role`], so it implements [`Accepts<T>`](crate::Accepts) and [`Emits<R>`] and can be used with [`Message::send_when_ready`](crate::Message::send_when_ready), [`MessageStream::feed_to`](crate::MessageStream::feed_to) and [`Envelope::forward_to`] without any declarations. The actor's [`ExitHandle`](crate::ExitHandle) gives back the final state once all of the handles have dropped.
+///
+/// As with any other actor, a function actor that is told to stop finishes the message it is handling first, and anything it had not handled, with nothing waiting on it, goes to the [`DeadLetter`](crate::DeadLetter) sink. If it is told to stop straight away instead, the state is lost along with the running handler, so the `ExitHandle` gives [`ActorOutcome::Panic`](crate::ActorOutcome::Panic).
+pub struct FnActor<S, T, R = ()>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	sender:    UnboundedSender<ReturnEnvelope<Self>>,
+	lifecycle: Lifecycle,
+	_state:    PhantomData<fn() -> S>,
+}
+
+impl<S, T, R> FnActor<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	/// Sends `msg` to the actor, which is run through the function when the [`Envelope`] is awaited or dropped
+	#[must_use]
+	pub fn send(self: &Arc<Self>, msg: T) -> Envelope<Self, R> {
+		Envelope::new(msg, Arc::clone(self))
+	}
+}
+
+impl<S, T, R> std::fmt::Debug for FnActor<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("FnActor")
+			.field("lifecycle", &self.lifecycle)
+			.finish_non_exhaustive()
+	}
+}
+
+impl<S, T, R> Monitorable for FnActor<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	fn lifecycle(&self) -> &Lifecycle {
+		&self.lifecycle
+	}
+}
+
+impl<S, T, R> Role for FnActor<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	type Channel = PriorityUnbounded<ReturnEnvelope<Self>>;
+	type Payload = T;
+	type Return = R;
+
+	fn conflates(_payload: &T) -> bool {
+		false
+	}
+
+	fn method_name(_payload: &T) -> &'static str {
+		"send"
+	}
+
+	async fn enqueue(&self, val: ReturnEnvelope<Self>) -> Result<(), Role2SendError<Self>> {
+		self.sender.send(val)
+	}
+
+	fn try_enqueue(
+		&self,
+		val: ReturnEnvelope<Self>,
+	) -> Result<(), TrySendError<ReturnEnvelope<Self>>> {
+		Sender::try_send(&self.sender, val)
+	}
+}
+
+impl<S, T, R> Emits<R> for FnActor<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	fn from_return_payload(t: R) -> R {
+		t
+	}
+}
+
+impl<S, T, R> Shell for FnActor<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	type ExitType = S;
+	type PanicType = Box<dyn Any + Send>;
+	type StateType = FnState<S, T, R>;
+}
+
+#[doc(hidden)]
+/// The state of an [`FnActor`], along with the function that handles its messages
+pub struct FnState<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	state:   S,
+	handler: Handler<S, T, R>,
+}
+
+impl<S, T, R> std::fmt::Debug for FnState<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("FnState")
+	}
+}
+
+impl<S, T, R> State for FnState<S, T, R>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+{
+	type Payload = ReturnEnvelope<FnActor<S, T, R>>;
+	type ShellType = FnActor<S, T, R>;
+
+	fn start_actor(self) -> ActorHandles<FnActor<S, T, R>> {
+		let (sender, mut receiver) = <FnActor<S, T, R> as Role>::Channel::new(());
+		let actor = Arc::new(FnActor {
+			sender,
+			lifecycle: Lifecycle::default(),
+			_state: PhantomData,
+		});
+		let context = Context::<Self>::new(Arc::clone(&actor));
+		let stop_signal = context.stop_signal();
+		let outside_stop_signal = context.stop_signal();
+
+		let FnState { state, mut handler } = self;
+		let event_loop = async move {
+			// Only taken out while a message is being handled, so is only missing if the handler panics or is abandoned
+			let mut state = Some(state);
+			let run = async {
+				let mut idle = IdleTimer::default();
+				loop {
+					let queue = async {
+						if !receiver.is_empty() || context.is_running() {
+							receiver.recv().await
+						} else {
+							None
+						}
+					};
+					let msg = match wait_for_mail(&context, &stop_signal, &mut idle, queue).await {
+						Wait::Mail(msg) => msg,
+						Wait::Woken => continue,
+						Wait::Finished => break,
+					};
+					let ReturnEnvelope {
+						payload,
+						return_path,
+						..
+					} = msg;
+					let current = state.take().expect("Only missing after a panic");
+					let (reply, next) = handler(current, payload).await;
+					state = Some(next);
+					return_path.send(reply);
+					// Messages still waiting are only handled if the actor is stopping because its handles dropped
+					if context.is_stopped() {
+						break;
+					}
+				}
+			};
+			// Being killed abandons the running handler, and the state along with it
+			let result = run_until_killed(&stop_signal, run).await;
+
+			// Anything still waiting goes to the dead-letter sink
+			for msg in close_and_drain(&mut receiver) {
+				msg.bury(context.get_shell().lifecycle());
+			}
+
+			let outcome = match (result, state) {
+				(Ok(()), Some(state)) => Ok(state),
+				(Ok(()), None) => Err(Box::new(ABANDONED) as Box<dyn Any + Send>),
+				(Err(panic), _) => Err(panic),
+			};
+			context.terminate(outcome.is_err());
+			outcome
+		};
+
+		let join_handle = tokio::spawn(event_loop);
+		ActorHandles::new(actor, join_handle, outside_stop_signal)
+	}
+}
+
+/// Starts an actor made from a single function, rather than a module with a state type and performances.
+///
+/// Each message of type `T` sent to the actor is run through `f` along with the current state, and `f` gives back the reply and the next state, in the same way as [`futures::stream::unfold`](https://docs.rs/futures/latest/futures/stream/fn.unfold.html). Messages are handled one at a time, in the order they are sent.
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// use shakespeare::{ActorHandles, ActorOutcome, spawn_fn};
+///
+/// let ActorHandles {
+/// 	message_handle,
+/// 	join_handle,
+/// 	..
+/// } = spawn_fn(0, |total, n: u32| async move { (total + n, total + n) });
+/// assert_eq!(message_handle.send(2).await.unwrap(), 2);
+/// assert_eq!(message_handle.send(3).await.unwrap(), 5);
+///
+/// // The final state comes out once the handles drop
+/// drop(message_handle);
+/// assert!(matches!(join_handle.await, ActorOutcome::Exit(5)));
+/// # }
+/// ```
+///
+/// The `#[actor]` attribute can also be put on an `async fn` taking `&mut` the state and a message, which gives a function that takes the initial state and calls this one. See [`actor`](crate::actor).
+///
+/// This must be called from within a tokio runtime.
+pub fn spawn_fn<S, T, R, F, Fut>(state: S, mut f: F) -> ActorHandles<FnActor<S, T, R>>
+where
+	S: Send + 'static,
+	T: Send + 'static,
+	R: Send + 'static,
+	F: FnMut(S, T) -> Fut + Send + 'static,
+	Fut: Future<Output = (R, S)> + Send + 'static,
+{
+	FnState {
+		state,
+		handler: Box::new(move |state, msg| Box::pin(f(state, msg))),
+	}
+	.start_actor()
+}
@@ -0,0 +1,73 @@
+use super::{Emits, Envelope, Monitorable, ReturnEnvelope, Role, TrySendError};
+use crate::{PriorityUnbounded, Role2SendError};
+
+/// A message type that an actor can handle with a `#[handler]` method, which says what the handler replies with
+///
+/// Unlike the methods of a Role, the set of message types is open-ended - any crate can define a new one and any actor can add a handler for it, without a Role having to list it.
+///
+/// ```
+/// use shakespeare::Request;
+///
+/// struct Lookup(String);
+///
+/// impl Request for Lookup {
+/// 	type Reply = Option<u64>;
+/// }
+/// ```
+pub trait Request: Send + 'static {
+	/// The value the handler produces
+	type Reply: Send + 'static;
+}
+
+/// Denotes an actor that has a `#[handler]` for the message type `M`
+///
+/// `dyn Handles<M>` is a [`Role`] whose only message is `M` itself, so `Arc<dyn Handles<M>>` can be used wherever a Role is, and implements [`Accepts<M>`](crate::Accepts) and [`Emits<M::Reply>`](Emits) to work with [`Message::send_when_ready`](crate::Message::send_when_ready), [`MessageStream::feed_to`](crate::MessageStream::feed_to) and [`Envelope::forward_to`]. See the [`actor`](crate::actor) macro for declaring a handler.
+///
+/// (This trait's implementations are normally automatically generated)
+pub trait Handles<M: Request>: 'static + Send + Sync + Monitorable {
+	/// Sends `msg` to the actor
+	fn handle(&self, msg: M) -> Envelope<dyn Handles<M>, M::Reply>;
+	#[doc(hidden)]
+	fn enqueue(
+		&self,
+		val: ReturnEnvelope<dyn Handles<M>>,
+	) -> Result<(), Role2SendError<dyn Handles<M>>>;
+	#[doc(hidden)]
+	fn try_enqueue(
+		&self,
+		val: ReturnEnvelope<dyn Handles<M>>,
+	) -> Result<(), TrySendError<ReturnEnvelope<dyn Handles<M>>>>;
+}
+
+// As for the generated Roles, this has to cover every lifetime rather than just 'static, or the event loop's future is not `Send`
+#[allow(clippy::elidable_lifetime_names)]
+impl<'a, M: Request> Role for dyn Handles<M> + 'a {
+	type Channel = PriorityUnbounded<ReturnEnvelope<Self>>;
+	type Payload = M;
+	type Return = M::Reply;
+
+	fn conflates(_payload: &M) -> bool {
+		false
+	}
+
+	fn method_name(_payload: &M) -> &'static str {
+		"handle"
+	}
+
+	async fn enqueue(&self, val: ReturnEnvelope<Self>) -> Result<(), Role2SendError<Self>> {
+		Handles::enqueue(self, val)
+	}
+
+	fn try_enqueue(
+		&self,
+		val: ReturnEnvelope<Self>,
+	) -> Result<(), TrySendError<ReturnEnvelope<Self>>> {
+		Handles::try_enqueue(self, val)
+	}
+}
+
+impl<M: Request> Emits<M::Reply> for dyn Handles<M> {
+	fn from_return_payload(t: M::Reply) -> M::Reply {
+		t
+	}
+}
@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use super::returnval::send_or_spawn;
+use super::{Context, Performs, ReturnEnvelope, Role, State};
+
+/// Passes each message an actor receives on to its successor, once it has handed off with [`Context::hand_off`](crate::Context::hand_off)
+pub(crate) type Forwarder<A> = Box<dyn Fn(<A as State>::Payload) + Send>;
+
+/// Starts an actor's successor from the state it leaves behind, as a child of the actor, and gives back the means of forwarding messages to it
+pub(crate) type Successor<A> = Box<dyn FnOnce(&mut A, &mut Context<A>) -> Forwarder<A> + Send>;
+
+/// An actor's message type, which can be sent on to any actor performing all of the same Roles
+///
+/// (This trait's implementations are normally automatically generated)
+pub trait ForwardTo<S: ?Sized> {
+	#[doc(hidden)]
+	/// Sends the message on to `dest`, which replies to the original caller
+	fn forward_to(self, dest: &Arc<S>);
+}
+
+#[doc(hidden)]
+/// Sends a message on to `dest`, keeping its return path, or gives it to the dead-letter sink if `dest` has stopped
+pub fn forward<R, S>(dest: &Arc<S>, envelope: ReturnEnvelope<R>)
+where
+	R: Role + ?Sized + 'static,
+	S: Performs<R> + ?Sized + 'static,
+{
+	send_or_spawn(Arc::clone(dest), envelope);
+}
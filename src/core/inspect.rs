@@ -0,0 +1,59 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::sync::oneshot;
+
+#[doc(hidden)]
+/// A closure sent to an actor by its shell's `with_state` method, to be run on the actor's state
+pub struct Inspection<A: ?Sized>(Box<dyn FnOnce(&mut A) + Send>);
+
+impl<A: ?Sized> Inspection<A> {
+	/// Wraps `f` so that its result is sent back to the returned query
+	pub fn new<T, F>(f: F) -> (Inspection<A>, StateQuery<T>)
+	where
+		T: Send + 'static,
+		F: FnOnce(&mut A) -> T + Send + 'static,
+	{
+		let (sender, receiver) = oneshot::channel();
+		let inspection = Inspection(Box::new(move |state: &mut A| {
+			let _ = sender.send(f(state));
+		}));
+		(inspection, StateQuery(receiver))
+	}
+
+	pub fn run(self, state: &mut A) {
+		(self.0)(state);
+	}
+}
+
+impl<A: ?Sized> std::fmt::Debug for Inspection<A> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("Inspection")
+	}
+}
+
+/// The value returned by a closure sent to an actor with the `with_state` method of an actor declared with `#[actor(inspect)]`.
+///
+/// The closure is sent as soon as `with_state` is called, so dropping this value still runs the closure, but throws its result away.
+#[must_use = "The closure runs whether or not this is awaited, but its result is lost"]
+#[derive(Debug)]
+pub struct StateQuery<T>(oneshot::Receiver<T>);
+
+impl<T> Future for StateQuery<T> {
+	type Output = Result<T, StateQueryErr>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		Pin::new(&mut self.0)
+			.poll(cx)
+			.map_err(|_| StateQueryErr::Hangup)
+	}
+}
+
+/// Represents a problem running a closure sent with `with_state`
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateQueryErr {
+	/// The actor stopped, or panicked, before the closure returned a value
+	Hangup,
+}
@@ -1,11 +1,50 @@
 mod actor;
-pub use actor::{ActorHandles, ExitHandle, Outcome, Shell, State};
+pub use actor::{ActorHandles, ExitHandle, Outcome, Shell, State, StopSignal};
 
 mod role;
-pub use role::{Accepts, Channel, Emits, Receiver, Role, Sender};
+pub use role::{
+	Accepts, Channel, Emits, Performance, Performs, Receiver, Role, Sender, TrySendError,
+	discard_superseded, split_batch,
+};
 
 mod returnval;
-pub use returnval::{Envelope, EnvelopeErr, ReturnCaster, ReturnEnvelope, ReturnPath};
+pub(crate) use returnval::deliver_or_bury;
+pub use returnval::{Envelope, EnvelopeErr, Prioritised, ReturnCaster, ReturnEnvelope, ReturnPath};
 
 mod context;
 pub use context::Context;
+
+mod timer;
+pub use timer::TimerHandle;
+
+mod deadletter;
+pub use deadletter::{
+	Bury, DeadLetter, DeadLetterReason, DeadLetterSinkGuard, clear_dead_letter_sink,
+	scoped_dead_letter_sink, set_dead_letter_sink,
+};
+
+mod monitor;
+pub use monitor::{ActorId, Lifecycle, Monitorable, Termination};
+
+mod handoff;
+pub use handoff::{ForwardTo, forward};
+
+mod inspect;
+pub use inspect::{Inspection, StateQuery, StateQueryErr};
+
+mod handles;
+pub use handles::{Handles, Request};
+
+mod event_loop;
+pub use event_loop::{
+	IDLE_TIMEOUT, IdleTimer, Wait, close_and_drain, run_until_killed, wait_for_mail,
+};
+
+mod function;
+pub use function::{FnActor, FnState, spawn_fn};
+
+mod stash;
+pub use stash::StashContext;
+
+mod unhandled;
+pub use unhandled::{PayloadFor, PendingCall, Unhandled};
@@ -0,0 +1,141 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError, RwLock};
+
+use super::deadletter::Sink;
+
+/// Identifies a single actor for as long as the program runs.
+///
+/// This is mainly useful for telling apart the [`Termination`] notices from the different actors being monitored with [`Context::monitor`](crate::Context::monitor), which returns the ID of the actor it starts monitoring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ActorId(u64);
+
+impl ActorId {
+	fn next() -> ActorId {
+		static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+		ActorId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+	}
+}
+
+impl std::fmt::Display for ActorId {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "actor #{}", self.0)
+	}
+}
+
+/// The notice sent to an actor that was monitoring another, when the other actor stops. See [`Context::monitor`](crate::Context::monitor).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Termination {
+	/// The actor that stopped
+	pub actor:    ActorId,
+	/// Whether the actor stopped because a handler panicked, rather than gracefully. This matches which branch of [`ActorOutcome`](crate::ActorOutcome) the actor's [`ExitHandle`](crate::ExitHandle) produced.
+	pub panicked: bool,
+}
+
+/// Denotes a handle to an actor that can be monitored with [`Context::monitor`](crate::Context::monitor)
+///
+/// This is implemented by every Role (that is, `dyn Role`) and every actor shell.
+///
+/// (This trait's implementations are normally automatically generated)
+pub trait Monitorable: Sync + Send {
+	#[doc(hidden)]
+	fn lifecycle(&self) -> &Lifecycle;
+}
+
+/// Sends a [`Termination`] to an actor that is monitoring another
+type Notify = Box<dyn FnOnce(Termination) + Send>;
+
+enum Monitors {
+	Watching(Vec<(ActorId, Notify)>),
+	// The actor has already stopped, so anything monitoring it from now on is told straight away
+	Terminated(Termination),
+}
+
+#[doc(hidden)]
+/// The identity of an actor, and the actors that want to know when it stops
+pub struct Lifecycle {
+	id:           ActorId,
+	monitors:     Mutex<Monitors>,
+	dead_letters: RwLock<Option<Sink>>,
+}
+
+impl Default for Lifecycle {
+	fn default() -> Self {
+		Lifecycle {
+			id:           ActorId::next(),
+			monitors:     Mutex::new(Monitors::Watching(vec![])),
+			dead_letters: RwLock::new(None),
+		}
+	}
+}
+
+impl Debug for Lifecycle {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Lifecycle")
+			.field("id", &self.id)
+			.finish_non_exhaustive()
+	}
+}
+
+impl Lifecycle {
+	#[must_use]
+	pub fn id(&self) -> ActorId {
+		self.id
+	}
+
+	pub(crate) fn dead_letter_sink(&self) -> Option<Sink> {
+		self.dead_letters
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clone()
+	}
+
+	pub(crate) fn set_dead_letter_sink(&self, sink: Sink) {
+		*self
+			.dead_letters
+			.write()
+			.unwrap_or_else(PoisonError::into_inner) = Some(sink);
+	}
+
+	/// Arranges for `notify` to be called when the actor stops, replacing any earlier arrangement made by `watcher`, or calls it straight away if the actor has already stopped
+	pub(crate) fn monitor(&self, watcher: ActorId, notify: Notify) {
+		let mut monitors = self.monitors.lock().unwrap_or_else(PoisonError::into_inner);
+		match &mut *monitors {
+			Monitors::Watching(watchers) => {
+				watchers.retain(|(id, _)| *id != watcher);
+				watchers.push((watcher, notify));
+			}
+			Monitors::Terminated(termination) => {
+				let termination = *termination;
+				drop(monitors);
+				notify(termination);
+			}
+		}
+	}
+
+	pub(crate) fn demonitor(&self, watcher: ActorId) {
+		if let Monitors::Watching(watchers) =
+			&mut *self.monitors.lock().unwrap_or_else(PoisonError::into_inner)
+		{
+			watchers.retain(|(id, _)| *id != watcher);
+		}
+	}
+
+	/// Tells everything monitoring the actor that it has stopped
+	pub(crate) fn terminate(&self, panicked: bool) {
+		let termination = Termination {
+			actor: self.id,
+			panicked,
+		};
+		let previous = std::mem::replace(
+			&mut *self.monitors.lock().unwrap_or_else(PoisonError::into_inner),
+			Monitors::Terminated(termination),
+		);
+		if let Monitors::Watching(watchers) = previous {
+			for (_, notify) in watchers {
+				notify(termination);
+			}
+		}
+	}
+}
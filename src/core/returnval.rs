@@ -9,7 +9,11 @@ use std::task::{Context, Poll, ready};
 use futures::Future;
 use tokio::sync::oneshot::{Receiver, Sender};
 
-use crate::{Accepts, Emits, Message, Role, Role2SendError};
+use super::deadletter::{DeadLetterReason, bury};
+use super::timer::{self, TimerHandle};
+use crate::{
+	Accepts, Emits, Performs, Role, Role2SendError, Role2Sender, RoleSender, TrySendError,
+};
 
 type PinnedAction<T> = Pin<Box<dyn Send + Future<Output = T>>>;
 
@@ -20,9 +24,23 @@ pub enum ReturnPath<Payload: Send> {
 	#[default]
 	Discard,
 	// Send it onwards to another actor's mailbox by running a function
-	Mailbox(Box<dyn Send + FnOnce(Payload) -> PinnedAction<()>>),
-	// Send it directly back to the caller via the given sender
-	Immediate(Sender<Payload>),
+	Mailbox(Box<dyn Send + FnOnce(Payload)>),
+	// Send it directly back to the caller via the given sender, or why it was not handled
+	Immediate(Sender<Result<Payload, NotHandled>>),
+}
+
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Why a caller was answered without its call being handled
+pub enum NotHandled {
+	/// A later call to the same method replaced it
+	Superseded,
+	/// The actor stopped, and its stop hook turned the call down
+	Rejected,
+	/// The actor was in a phase with no handler for it
+	OutOfPhase,
+	/// The call was part of a batch whose handler did not give back one value for each call
+	Unmatched,
 }
 
 impl<Payload: Send> std::fmt::Debug for ReturnPath<Payload> {
@@ -36,63 +54,114 @@ impl<Payload: Send> std::fmt::Debug for ReturnPath<Payload> {
 }
 
 impl<Payload: Send + 'static> ReturnPath<Payload> {
-	pub(crate) fn create_immediate() -> (ReturnPath<Payload>, Receiver<Payload>) {
+	pub(crate) fn create_immediate() -> (ReturnPath<Payload>, Receiver<Result<Payload, NotHandled>>)
+	{
 		let (send, recv) = tokio::sync::oneshot::channel();
 		(ReturnPath::Immediate(send), recv)
 	}
 
-	pub async fn send(self, val: Payload) {
+	pub fn send(self, val: Payload) {
 		use ReturnPath::{Discard, Immediate, Mailbox};
 
 		match self {
 			Discard => (),
-			Mailbox(callback) => callback(val).await,
+			Mailbox(callback) => callback(val),
 			Immediate(channel) => {
-				let _ = channel.send(val);
+				let _ = channel.send(Ok(val));
 			}
 		}
 	}
+
+	/// Tells the caller that the message will not be handled because a later call to the same method replaced it
+	pub fn supersede(self) {
+		self.refuse(NotHandled::Superseded);
+	}
+
+	/// Tells the caller that the message will not be handled because the actor is in a phase without a handler for it
+	pub fn out_of_phase(self) {
+		self.refuse(NotHandled::OutOfPhase);
+	}
+
+	/// Tells the caller that the message was handled in a batch, but that there is no telling which of the batch's return values is its own
+	pub fn unmatched(self) {
+		self.refuse(NotHandled::Unmatched);
+	}
+
+	/// Tells the caller that the message will not be handled, for the given reason
+	pub(crate) fn refuse(self, reason: NotHandled) {
+		if let ReturnPath::Immediate(channel) = self {
+			let _ = channel.send(Err(reason));
+		}
+	}
 }
 
 /// A message that has been prepared to be (*but not yet*) sent to an actor, produced by calling a Role method on the actor shell.
 ///
 /// This type allows the caller to control how the return value, of type `Output`, produced by the actor processing the message will be handled. As a result, while this value exists the message has not been sent.
 ///
-/// The caller is expected to do one of four things with this value:
-/// 1. nothing - that is, allowing it to drop will dispatch the message and have any return value thrown away, but *will not* wait for the message delivery to complete.
+/// The caller is expected to do one of five things with this value:
+/// 1. nothing - that is, allowing it to drop will put the message in the actor's queue straight away and have any return value thrown away. This does not need to be done inside a tokio runtime. If the actor has stopped, the message goes to the [`DeadLetter`](crate::DeadLetter) sink.
 /// 2. awaiting this value will wait for the actor to receive and process the message, then yield the return value to the caller
 /// 3. calling [`ignore_return()`][`Envelope::ignore_return`] and awaiting the resulting future *will wait* for the message to be sent, but will not wait for any return value.
 /// 4. calling [`forward_to`][`Envelope::forward_to`] will send the return value directly to a given actor's mailbox.
+/// 5. calling [`send_at`][`Envelope::send_at`] will send the message at a later time, discarding any return value.
+///
+/// **NB**: In case 1, the message is sent at the point the [`Envelope`] drops, so messages dropped on one task are handled in the order they were dropped, as long as they go through the same Role. See the crate documentation for the full ordering rules.
 ///
-/// **NB**: In case 1, there is no ordering established with other calls sent to the same receiver, even from the same sender.
+/// Before doing any of these, [`with_priority`][`Envelope::with_priority`] can be used to let the message overtake others waiting in the same queue.
+///
+/// `Shell` is what the message will be sent to. Calling a method through the Role gives an `Envelope` holding `dyn DestRole`, while calling one of the `_direct` methods on an `Arc` of an actor's shell gives one holding the shell itself, which sends the message without any dynamic dispatch. Both behave the same otherwise.
 #[derive(Debug)]
-pub struct Envelope<DestRole, Output>
+pub struct Envelope<DestRole, Output, Shell = DestRole>
 where
 	DestRole: Role + ?Sized + 'static,
+	Shell: Performs<DestRole> + ?Sized + 'static,
 {
-	val:  Option<DestRole::Payload>,
-	dest: Option<Arc<DestRole>>,
+	val:      Option<DestRole::Payload>,
+	dest:     Option<Arc<Shell>>,
+	priority: i32,
 	// "Type parameter Output is never used"
-	_v:   PhantomData<Output>,
+	_v:       PhantomData<Output>,
 }
 
-impl<DestRole, Output> Envelope<DestRole, Output>
+impl<DestRole, Output, Shell> Envelope<DestRole, Output, Shell>
 where
 	DestRole: Role + ?Sized,
+	Shell: Performs<DestRole> + ?Sized + 'static,
 {
 	#[doc(hidden)]
-	pub fn new(val: DestRole::Payload, dest: Arc<DestRole>) -> Envelope<DestRole, Output> {
+	pub fn new(val: DestRole::Payload, dest: Arc<Shell>) -> Envelope<DestRole, Output, Shell> {
 		Envelope {
-			val:  Some(DestRole::into_payload(val)),
-			dest: Some(dest),
-			_v:   PhantomData {},
+			val:      Some(DestRole::into_payload(val)),
+			dest:     Some(dest),
+			priority: 0,
+			_v:       PhantomData {},
 		}
 	}
 
-	pub(crate) fn unpack(mut self) -> (DestRole::Payload, Arc<DestRole>) {
-		let (payload, dest) = (self.val.take().unwrap(), self.dest.take().unwrap());
+	/// Sets how urgently the actor should handle this message, relative to others sent to it through the same Role.
+	///
+	/// Of the messages waiting in a Role's queue, the actor takes the one with the highest priority first, and takes messages of the same priority in the order they arrived. Messages have priority 0 unless this is called, so a positive priority lets a message, such as a shutdown request or a health check, overtake a backlog, and a negative priority lets background work wait until the queue is otherwise clear.
+	///
+	/// This only orders messages within one Role's queue - see the `priority` option of [`performance`](crate::performance) for ordering the Roles of an actor against each other.
+	#[must_use]
+	pub fn with_priority(mut self, priority: i32) -> Self {
+		self.priority = priority;
+		self
+	}
+
+	pub(crate) fn unpack(
+		mut self,
+		return_path: ReturnPath<DestRole::Return>,
+	) -> (ReturnEnvelope<DestRole>, Arc<Shell>) {
+		let envelope = ReturnEnvelope {
+			payload: self.val.take().unwrap(),
+			return_path,
+			priority: self.priority,
+		};
+		let dest = self.dest.take().unwrap();
 		std::mem::forget(self);
-		(payload, dest)
+		(envelope, dest)
 	}
 
 	/// This method will wait for the message to arrive at the receiving actor, but will not wait for any return value, which will be dropped.
@@ -102,15 +171,9 @@ where
 	/// This function may return `Err` if the actor has already stopped.
 	#[must_use = "The message will not be sent to the actor if this Future isn't processed"]
 	pub async fn ignore_return(self) -> Result<(), Role2SendError<DestRole>> {
-		let (payload, dest) = self.unpack();
+		let (envelope, dest) = self.unpack(ReturnPath::Discard);
 
-		let return_path = ReturnPath::Discard;
-
-		dest.enqueue(ReturnEnvelope {
-			payload,
-			return_path,
-		})
-		.await
+		deliver(dest, envelope).await
 	}
 
 	/// Arranges for the *return value* produced by processing this [`Envelope`] to be forwarded to the given actor, discarding any return value that the second actor produces.
@@ -121,7 +184,7 @@ where
 	///
 	/// While using this method and passing the actor's own handle as the `recipient` will avoid deadlocks - actor A will continue servicing messages - tying the returned value back to the original context currently has no specific support and is left to the developer.
 	///
-	/// Because [`Envelope`] is [`IntoFuture`], this is equivalent to, but more efficient than, passing the same parameters to [`Message::send_when_ready`] **including** that the recipient actor will be kept alive until the message is either processed or the actor originating the [`Envelope`] shuts down.
+	/// Because [`Envelope`] is [`IntoFuture`], this is equivalent to, but more efficient than, passing the same parameters to [`Message::send_when_ready`](crate::Message::send_when_ready) **including** that the recipient actor will be kept alive until the message is either processed or the actor originating the [`Envelope`] shuts down.
 	///
 	/// # Errors
 	///
@@ -132,72 +195,137 @@ where
 	) -> Result<(), Role2SendError<DestRole>>
 	where
 		DestRole: Emits<Output>,
-		RxRole: Accepts<Output> + 'static,
+		RxRole: Accepts<Output> + ?Sized + 'static,
 	{
-		let (payload, original) = self.unpack();
-
-		let bridge_to_rx_role = |sender_payload| -> Pin<Box<dyn Future<Output = ()> + Send>> {
+		let bridge_to_rx_role = |sender_payload| {
 			let discard_envelope = ReturnEnvelope {
 				return_path: ReturnPath::Discard,
 				payload:     RxRole::into_payload(DestRole::from_return_payload(sender_payload)),
+				priority:    0,
 			};
-			Box::pin(async move {
-				let _ = recipient.enqueue(discard_envelope).await;
-			})
+			send_or_spawn(recipient, discard_envelope);
 		};
 
-		let val: ReturnEnvelope<DestRole> = ReturnEnvelope {
-			return_path: ReturnPath::Mailbox(Box::new(bridge_to_rx_role)),
-			payload,
-		};
+		let (val, original) = self.unpack(ReturnPath::Mailbox(Box::new(bridge_to_rx_role)));
 
-		original.enqueue(val).await
+		deliver(original, val).await
+	}
+
+	/// Holds the message back until the given time, then sends it to the actor, discarding any return value.
+	///
+	/// The returned [`TimerHandle`] can cancel the message until it has been sent. Until then, only a weak reference to the actor is held, so the pending message does not keep the actor alive - if the actor has shut down by `at`, the message goes to the [`DeadLetter`](crate::DeadLetter) sink. If `at` has already passed, the message is sent straight away.
+	///
+	/// This must be called from within a tokio runtime.
+	pub fn send_at(self, at: tokio::time::Instant) -> TimerHandle {
+		let (envelope, dest) = self.unpack(ReturnPath::Discard);
+
+		timer::send_at(&dest, at, envelope)
 	}
 }
 
-impl<DestRole, Output> IntoFuture for Envelope<DestRole, Output>
+impl<DestRole, Output, Shell> IntoFuture for Envelope<DestRole, Output, Shell>
 where
 	DestRole: Emits<Output> + ?Sized + 'static,
+	Shell: Performs<DestRole> + ?Sized + 'static,
 {
 	#[doc(hidden)]
-	type IntoFuture = ReturnCaster<DestRole, Output>;
+	type IntoFuture = ReturnCaster<DestRole, Output, Shell>;
 	/// The return received from the envelope can fail if the message handler doesn't complete
 	type Output = Result<Output, EnvelopeErr<DestRole>>;
 
 	fn into_future(self) -> Self::IntoFuture {
-		let (payload, dest) = self.unpack();
-
 		let (return_path, recv_future) = ReturnPath::create_immediate();
 
-		let envelope = ReturnEnvelope {
-			payload,
-			return_path,
-		};
-
-		let sending_fn = { async move || dest.enqueue(envelope).await }();
-		let sender: Option<PinnedAction<_>> = Some(Box::pin(sending_fn));
+		let (envelope, dest) = self.unpack(return_path);
 
 		ReturnCaster {
-			sender,
+			unsent: Some((envelope, dest)),
+			sender: None,
 			recv_future,
 			typ: PhantomData {},
 		}
 	}
 }
 
-impl<DestRole, Output> Drop for Envelope<DestRole, Output>
+impl<DestRole, Output, Shell> Drop for Envelope<DestRole, Output, Shell>
 where
 	DestRole: Role + ?Sized,
+	Shell: Performs<DestRole> + ?Sized + 'static,
 {
 	fn drop(&mut self) {
-		let val = self.val.take().unwrap();
+		let envelope = ReturnEnvelope {
+			payload:     self.val.take().unwrap(),
+			return_path: ReturnPath::Discard,
+			priority:    self.priority,
+		};
 		let dest = self.dest.take().unwrap();
 
-		std::future::ready(val).send_when_ready(dest);
+		send_or_spawn(dest, envelope);
+	}
+}
+
+/// Puts the message in the actor's queue without waiting, unless the queue is full or closed, which needs the full send to wait for room or produce the error
+pub(crate) async fn deliver<R, S>(
+	dest: Arc<S>,
+	envelope: ReturnEnvelope<R>,
+) -> Result<(), Role2SendError<R>>
+where
+	R: Role + ?Sized + 'static,
+	S: Performs<R> + ?Sized,
+{
+	match dest.try_enqueue(envelope) {
+		Ok(()) => Ok(()),
+		Err(TrySendError::Full(envelope) | TrySendError::Closed(envelope)) => {
+			dest.into_role().enqueue(envelope).await
+		}
+	}
+}
+
+/// Puts the message in the actor's queue, waiting for room if needed, and gives it to the dead-letter sink if the actor has stopped. Returns whether the message was delivered
+pub(crate) async fn deliver_or_bury<R, S>(dest: Arc<S>, envelope: ReturnEnvelope<R>) -> bool
+where
+	R: Role + ?Sized + 'static,
+	S: Performs<R> + ?Sized,
+{
+	match dest.try_enqueue(envelope) {
+		Ok(()) => true,
+		Err(TrySendError::Closed(envelope)) => {
+			bury(dest.lifecycle(), envelope, DeadLetterReason::Stopped);
+			false
+		}
+		Err(TrySendError::Full(envelope)) => {
+			let dest = dest.into_role();
+			match dest.enqueue(envelope).await {
+				Ok(()) => true,
+				Err(error) => {
+					let envelope = <Role2Sender<R> as RoleSender<_>>::into_unsent(error);
+					bury(dest.lifecycle(), envelope, DeadLetterReason::Stopped);
+					false
+				}
+			}
+		}
+	}
+}
+
+/// Puts the message in the actor's queue straight away, which keeps messages sent this way in order, and only spawns a task to wait for room if the queue is full. If the actor has stopped, the message goes to the dead-letter sink
+pub(crate) fn send_or_spawn<R, S>(dest: Arc<S>, envelope: ReturnEnvelope<R>)
+where
+	R: Role + ?Sized + 'static,
+	S: Performs<R> + ?Sized + 'static,
+{
+	match dest.try_enqueue(envelope) {
+		Ok(()) => {}
+		Err(TrySendError::Full(envelope)) => {
+			tokio::spawn(deliver_or_bury(dest, envelope));
+		}
+		Err(TrySendError::Closed(envelope)) => {
+			bury(dest.lifecycle(), envelope, DeadLetterReason::Stopped);
+		}
 	}
 }
 
 /// Represents a problem with an [`Envelope`], either sending its message or waiting for a response.
+#[non_exhaustive]
 pub enum EnvelopeErr<R>
 where
 	R: Role + ?Sized,
@@ -212,6 +340,18 @@ where
 	/// This means that the actor either panicked (not necessarily in the handler for this message) or
 	/// called [`Context::stop`][`crate::Context::stop`].
 	Hangup,
+	/// The message was delivered successfully, but was not handled because a later call to the same method arrived before the actor got to it.
+	///
+	/// This only happens for methods marked `#[conflate]` in their role.
+	Superseded,
+	/// The message was delivered successfully, but the actor stopped before handling it, and its `stop` or `catch` function turned it down with [`PendingCall::reject`](crate::PendingCall::reject).
+	Rejected,
+	/// The message was delivered successfully, but the actor was in a phase that has no handler for it.
+	///
+	/// This only happens for roles the actor performs only with `#[performance(when = ...)]`, with no fallback performance for the method.
+	OutOfPhase,
+	/// The message was handled as part of a batch, but the `#[batch]` method gave back a different number of values than there were calls, so which one belongs to this call is unknown.
+	Unmatched,
 }
 
 impl<R> Debug for EnvelopeErr<R>
@@ -222,6 +362,10 @@ where
 		let field = match self {
 			EnvelopeErr::SendingError(_) => "SendingError",
 			EnvelopeErr::Hangup => "Hangup",
+			EnvelopeErr::Superseded => "Superseded",
+			EnvelopeErr::Rejected => "Rejected",
+			EnvelopeErr::OutOfPhase => "OutOfPhase",
+			EnvelopeErr::Unmatched => "Unmatched",
 		};
 		f.debug_tuple(&format!("EnvelopeErr<{}>", type_name::<R>()))
 			.field(&field)
@@ -237,7 +381,11 @@ where
 	fn eq(&self, other: &Self) -> bool {
 		match (self, other) {
 			(EnvelopeErr::SendingError(e), EnvelopeErr::SendingError(f)) => e == f,
-			(EnvelopeErr::Hangup, EnvelopeErr::Hangup) => true,
+			(EnvelopeErr::Hangup, EnvelopeErr::Hangup)
+			| (EnvelopeErr::Superseded, EnvelopeErr::Superseded)
+			| (EnvelopeErr::Rejected, EnvelopeErr::Rejected)
+			| (EnvelopeErr::OutOfPhase, EnvelopeErr::OutOfPhase)
+			| (EnvelopeErr::Unmatched, EnvelopeErr::Unmatched) => true,
 			_ => false,
 		}
 	}
@@ -256,29 +404,42 @@ where
 /// This can fail and produce an `Err` if the actor's message handler aborts without completing.
 ///
 /// This exists because the type for [`Envelope::into_future`] needs to be nameable, which Future::map is not.
-pub struct ReturnCaster<R, V>
+pub struct ReturnCaster<R, V, S = R>
 where
 	R: Role + ?Sized,
+	S: ?Sized,
 {
+	// The message, until the first poll puts it in the queue
+	unsent:      Option<(ReturnEnvelope<R>, Arc<S>)>,
+	// Only used if the message could not be put in the queue without waiting
 	sender:      Option<PinnedAction<Result<(), Role2SendError<R>>>>,
 	#[pin]
-	recv_future: Receiver<<R as Role>::Return>,
+	recv_future: Receiver<Result<<R as Role>::Return, NotHandled>>,
 	typ:         PhantomData<V>,
 }
 
-impl<R, V> Future for ReturnCaster<R, V>
+impl<R, V, S> Future for ReturnCaster<R, V, S>
 where
 	R: Emits<V> + ?Sized + 'static,
+	S: Performs<R> + ?Sized + 'static,
 {
 	type Output = Result<V, EnvelopeErr<R>>;
 
-	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-		// Send the message if we haven't already
-		if let Some(sender) = &mut self.sender {
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let this = self.project();
+		// Send the message if we haven't already, which usually doesn't need to wait
+		if let Some((envelope, dest)) = this.unsent.take()
+			&& let Err(TrySendError::Full(envelope) | TrySendError::Closed(envelope)) =
+				dest.try_enqueue(envelope)
+		{
+			let dest = dest.into_role();
+			*this.sender = Some(Box::pin(async move { dest.enqueue(envelope).await }));
+		}
+		if let Some(sender) = this.sender {
 			match ready!(sender.as_mut().poll(cx)) {
 				Ok(()) => {
 					// If the sending was successful, throw away the sender future
-					self.sender.take();
+					this.sender.take();
 				}
 				Err(e) => return Poll::Ready(Err(EnvelopeErr::SendingError(e))),
 			}
@@ -287,17 +448,21 @@ where
 		// we only get here if either the sending was successful or was already done previously
 
 		// Poll the receiver
-		let inner = self.project().recv_future;
-		inner.poll(cx).map(|result| match result {
-			Ok(val) => Ok(R::from_return_payload(val)),
+		this.recv_future.poll(cx).map(|result| match result {
+			Ok(Ok(val)) => Ok(R::from_return_payload(val)),
+			Ok(Err(NotHandled::Superseded)) => Err(EnvelopeErr::Superseded),
+			Ok(Err(NotHandled::Rejected)) => Err(EnvelopeErr::Rejected),
+			Ok(Err(NotHandled::OutOfPhase)) => Err(EnvelopeErr::OutOfPhase),
+			Ok(Err(NotHandled::Unmatched)) => Err(EnvelopeErr::Unmatched),
 			Err(_) => Err(EnvelopeErr::Hangup),
 		})
 	}
 }
 
-impl<R, V> Debug for ReturnCaster<R, V>
+impl<R, V, S> Debug for ReturnCaster<R, V, S>
 where
 	R: Role + ?Sized,
+	S: ?Sized,
 {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(
@@ -314,6 +479,8 @@ where
 pub struct ReturnEnvelope<R: Role + ?Sized> {
 	pub payload:     R::Payload,
 	pub return_path: ReturnPath<R::Return>,
+	// Messages with a higher priority are taken from the queue first
+	pub priority:    i32,
 }
 
 impl<R: Role + ?Sized> Debug for ReturnEnvelope<R> {
@@ -321,3 +488,16 @@ impl<R: Role + ?Sized> Debug for ReturnEnvelope<R> {
 		write!(f, "<ReturnEnvelope>")
 	}
 }
+
+#[doc(hidden)]
+/// A message that can be taken out of its queue ahead of others
+pub trait Prioritised {
+	/// Messages with a higher priority are handled first
+	fn priority(&self) -> i32;
+}
+
+impl<R: Role + ?Sized> Prioritised for ReturnEnvelope<R> {
+	fn priority(&self) -> i32 {
+		self.priority
+	}
+}
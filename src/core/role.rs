@@ -1,5 +1,9 @@
+use std::sync::Arc;
+
 use super::super::Role2SendError;
-use super::returnval::ReturnEnvelope;
+use super::deadletter::{DeadLetterReason, bury};
+use super::returnval::{ReturnEnvelope, ReturnPath};
+use super::{Context, Monitorable, State};
 
 /// The sender half of a channel used internally by a Role
 #[trait_variant::make(Send)]
@@ -8,6 +12,32 @@ pub trait Sender<T: Send>: Sync + Send + Clone {
 	type Error: Send;
 	#[doc(hidden)]
 	async fn send(&self, msg: T) -> Result<(), Self::Error>;
+	#[doc(hidden)]
+	/// Sends the message only if that can be done without waiting
+	fn try_send(&self, msg: T) -> Result<(), TrySendError<T>>;
+	#[doc(hidden)]
+	/// Gives back the message from an error produced by `send`
+	fn into_unsent(error: Self::Error) -> T;
+}
+
+#[doc(hidden)]
+/// The reason a message could not be sent without waiting, which gives the message back
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+	/// The channel has no room for the message yet
+	Full(T),
+	/// The receiver has gone, so the message can never be delivered
+	Closed(T),
+}
+
+impl<T> TrySendError<T> {
+	/// Converts the message held in the error
+	pub fn map<U>(self, f: impl FnOnce(T) -> U) -> TrySendError<U> {
+		match self {
+			TrySendError::Full(msg) => TrySendError::Full(f(msg)),
+			TrySendError::Closed(msg) => TrySendError::Closed(f(msg)),
+		}
+	}
 }
 
 /// The receiver half of a channel used internally by a Role
@@ -15,8 +45,17 @@ pub trait Sender<T: Send>: Sync + Send + Clone {
 pub trait Receiver<T: Send> {
 	#[doc(hidden)]
 	async fn recv(&mut self) -> Option<T>;
+	#[doc(hidden)]
+	/// Receives up to `limit` messages into `buffer`, returning how many were received, and only waits if none are available
+	async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize;
 	/// Used to avoid bailing out on the dispatch loop too early if all clients have dropped
 	fn is_empty(&self) -> bool;
+	#[doc(hidden)]
+	/// Stops any more messages from being sent, while leaving those already sent to be received
+	fn close(&mut self);
+	#[doc(hidden)]
+	/// Receives the next message if one is available, without waiting
+	fn try_recv(&mut self) -> Option<T>;
 }
 
 /// A marker trait describing a channel underlying a particular role
@@ -41,7 +80,7 @@ pub trait Channel {
 #[trait_variant::make(Send)]
 // This logically *should* be 'static but the compiler can't deal with the lifetime bounds properly. See https://github.com/rust-lang/rust/issues/131488
 // The compiler seems to be OK if 'static is listed separately in the signature of the functions that need it.
-pub trait Role: Sync + Send {
+pub trait Role: Sync + Send + Monitorable {
 	#[doc(hidden)]
 	type Payload: Sized + Send + 'static;
 	#[doc(hidden)]
@@ -49,9 +88,84 @@ pub trait Role: Sync + Send {
 	#[doc(hidden)]
 	type Channel: Channel<Item = ReturnEnvelope<Self>>;
 	#[doc(hidden)]
+	/// Whether the message is a call to a method marked `#[conflate]`
+	fn conflates(payload: &Self::Payload) -> bool;
+	#[doc(hidden)]
+	/// The name of the method the message is a call to
+	fn method_name(payload: &Self::Payload) -> &'static str;
+	#[doc(hidden)]
 	/// Puts a message into the corresponding queue for the actor
 	/// Can potentially error if the actor stops before the message is received
 	async fn enqueue(&self, val: ReturnEnvelope<Self>) -> Result<(), Role2SendError<Self>>;
+	#[doc(hidden)]
+	/// Puts a message into the corresponding queue for the actor, if that can be done without waiting
+	fn try_enqueue(
+		&self,
+		val: ReturnEnvelope<Self>,
+	) -> Result<(), TrySendError<ReturnEnvelope<Self>>>;
+}
+
+/// Denotes that a type can be sent the messages of the Role `R`
+///
+/// This is implemented by the Role itself (that is, `dyn R`) and by the shell of every actor with a performance of `R`. An [`Envelope`](crate::Envelope) holding a concrete actor shell uses this to send its message without going through the Role's vtable, which is what the shell's own methods produce when called on an `Arc` of the shell.
+///
+/// (This trait's implementations are normally automatically generated)
+pub trait Performs<R: Role + ?Sized>: Sync + Send + Monitorable {
+	#[doc(hidden)]
+	/// Converts the shell into the Role, for sends that have to wait
+	fn into_role(self: Arc<Self>) -> Arc<R>;
+	#[doc(hidden)]
+	/// Puts a message into the actor's queue for `R`, if that can be done without waiting
+	fn try_enqueue(&self, val: ReturnEnvelope<R>) -> Result<(), TrySendError<ReturnEnvelope<R>>>;
+}
+
+impl<R: Role + ?Sized> Performs<R> for R {
+	fn into_role(self: Arc<Self>) -> Arc<R> {
+		self
+	}
+
+	fn try_enqueue(&self, val: ReturnEnvelope<R>) -> Result<(), TrySendError<ReturnEnvelope<R>>> {
+		Role::try_enqueue(self, val)
+	}
+}
+
+/// Denotes that a Role has a performance for the state type `S` that was written for every state type meeting some bounds, such as `impl<S: HasHealth> Health for S`
+///
+/// An actor declared with `#[actor(performs(R))]` hands its messages for the Role `R` to this implementation. See the [`performance`](crate::performance) macro for more information.
+///
+/// (This trait's implementations are normally automatically generated)
+#[diagnostic::on_unimplemented(
+	message = "`{Self}` does not have a performance for `{S}`",
+	note = "an actor declared with `performs` needs a performance written for any state type, such as `impl<S: Bound> Role for S`, whose bounds its state meets"
+)]
+pub trait Performance<S: State>: Role {
+	#[doc(hidden)]
+	/// Handles a message sent through the Role
+	fn dispatch(
+		state: &mut S,
+		context: &mut Context<S>,
+		msg: ReturnEnvelope<Self>,
+	) -> impl Future<Output = ()>;
+	#[doc(hidden)]
+	/// Whether the message only needs shared access to the state
+	fn is_shared(msg: &ReturnEnvelope<Self>) -> bool;
+	#[doc(hidden)]
+	/// Handles a message that only needs shared access to the state
+	fn dispatch_shared(
+		state: &S,
+		context: &Context<S>,
+		msg: ReturnEnvelope<Self>,
+	) -> impl Future<Output = ()>;
+	#[doc(hidden)]
+	/// The most calls to the message's method that can be handled at once, if it is batched
+	fn batch_limit(msg: &ReturnEnvelope<Self>) -> Option<usize>;
+	#[doc(hidden)]
+	/// Handles consecutive calls to a batched method at once
+	fn dispatch_batch(
+		state: &mut S,
+		context: &mut Context<S>,
+		msgs: Vec<ReturnEnvelope<Self>>,
+	) -> impl Future<Output = ()>;
 }
 
 /// Denotes that a Role can be sent `T` values
@@ -111,3 +225,51 @@ pub trait Emits<T>: Role {
 	fn from_return_payload(t: Self::Return) -> T;
 }
 // The raw Return type shouldn't be escaping anywhere else, so we don't need a reflexive impl
+
+#[doc(hidden)]
+/// Splits a batch after its last call to the same method as the first, up to `limit` calls in total.
+///
+/// The messages taken off the end were received after the batch ended, so must be handled next to keep the queue's order
+pub fn split_batch<R>(batch: &mut Vec<ReturnEnvelope<R>>, limit: usize) -> Vec<ReturnEnvelope<R>>
+where
+	R: Role + ?Sized,
+{
+	let Some(first) = batch.first() else {
+		return vec![];
+	};
+	let method = std::mem::discriminant(&first.payload);
+	let end = batch
+		.iter()
+		.take(limit)
+		.position(|msg| std::mem::discriminant(&msg.payload) != method)
+		.unwrap_or(batch.len().min(limit));
+	batch.split_off(end)
+}
+
+#[doc(hidden)]
+/// Removes every call to a conflating method that is followed by another call to the same method, telling its caller that it was superseded, or giving it to the actor's dead-letter sink if nothing is waiting on it
+pub fn discard_superseded<R, S>(msgs: &mut Vec<ReturnEnvelope<R>>, actor: &S)
+where
+	R: Role + ?Sized + 'static,
+	S: Monitorable + ?Sized,
+{
+	let mut seen = vec![];
+	let mut kept = Vec::with_capacity(msgs.len());
+	for msg in std::mem::take(msgs).into_iter().rev() {
+		if R::conflates(&msg.payload) {
+			let method = std::mem::discriminant(&msg.payload);
+			if seen.contains(&method) {
+				if let ReturnPath::Immediate(_) = msg.return_path {
+					msg.return_path.supersede();
+				} else {
+					bury(actor.lifecycle(), msg, DeadLetterReason::Superseded);
+				}
+				continue;
+			}
+			seen.push(method);
+		}
+		kept.push(msg);
+	}
+	kept.reverse();
+	*msgs = kept;
+}
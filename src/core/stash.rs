@@ -0,0 +1,60 @@
+use std::ops::{Deref, DerefMut};
+
+use super::{Context, State};
+
+/// The [`Context`] given to the handler of a method marked `#[stashable]`, which can also set aside the message being handled.
+///
+/// `P` is the method's parameters, as a tuple if it has more than one, and `()` if it has none. This dereferences to the actor's [`Context`], so the handler can use it in the same way, and a stashable method that never stashes can take a `&mut Context<Self>` instead.
+///
+/// To access this, the performance signature should take a `&mut StashContext<'_, Self, P>` as its second parameter after the receiver.
+pub struct StashContext<'a, A: State + ?Sized, P> {
+	context: &'a mut Context<A>,
+	stashed: Option<P>,
+}
+
+impl<A: State + ?Sized, P> std::fmt::Debug for StashContext<'_, A, P> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("StashContext")
+			.field("stashed", &self.stashed.is_some())
+			.finish_non_exhaustive()
+	}
+}
+
+impl<'a, A: State + ?Sized, P> StashContext<'a, A, P> {
+	#[doc(hidden)]
+	pub fn new(context: &'a mut Context<A>) -> Self {
+		StashContext {
+			context,
+			stashed: None,
+		}
+	}
+
+	/// Sets aside the message being handled, including the means to reply to its caller, until [`Context::unstash_all`] is called, e.g. while the actor is waiting on a connection.
+	///
+	/// The handler gives back the parameters it was called with, which are put in the stash once it finishes. Whatever it returns is thrown away rather than sent to the caller, who is only answered once the message has been unstashed and handled again. The actor carries on handling other messages in the meantime, so it should perform some other role that can tell it when it is ready.
+	///
+	/// If this is called more than once, only the last parameters are stashed.
+	pub fn stash(&mut self, params: P) {
+		self.stashed = Some(params);
+	}
+
+	#[doc(hidden)]
+	/// The parameters the handler gave back to be stashed, if it asked for its message to be
+	pub fn take_stashed(&mut self) -> Option<P> {
+		self.stashed.take()
+	}
+}
+
+impl<A: State + ?Sized, P> Deref for StashContext<'_, A, P> {
+	type Target = Context<A>;
+
+	fn deref(&self) -> &Context<A> {
+		self.context
+	}
+}
+
+impl<A: State + ?Sized, P> DerefMut for StashContext<'_, A, P> {
+	fn deref_mut(&mut self) -> &mut Context<A> {
+		self.context
+	}
+}
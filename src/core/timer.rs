@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::AbortHandle;
+use tokio::time::{Instant, MissedTickBehavior, interval_at, sleep_until};
+
+use super::deadletter::bury_for_gone;
+use super::returnval::deliver_or_bury;
+use super::{Accepts, Performs, ReturnEnvelope, ReturnPath, Role};
+
+/// A message that has been scheduled to be sent to an actor later, produced by [`Context::send_after`](crate::Context::send_after), [`Context::send_interval`](crate::Context::send_interval) and [`Envelope::send_at`](crate::Envelope::send_at).
+///
+/// The timer only holds a weak reference to the actor, so a pending timer does not keep the actor alive - if the actor has shut down by the time the timer goes off, the message goes to the [`DeadLetter`](crate::DeadLetter) sink and the timer stops.
+///
+/// Dropping this value leaves the timer running, in the same way as dropping a [`JoinHandle`](tokio::task::JoinHandle). Call [`cancel`](TimerHandle::cancel) to stop it.
+#[derive(Debug)]
+pub struct TimerHandle(AbortHandle);
+
+impl TimerHandle {
+	/// Stops the timer, so that no further messages are sent by it.
+	///
+	/// A message the timer has already put in the actor's queue is still handled.
+	pub fn cancel(&self) {
+		self.0.abort();
+	}
+
+	/// Whether the timer will not send any more messages, because it has sent its only message, been cancelled or found its actor had shut down
+	#[must_use]
+	pub fn is_finished(&self) -> bool {
+		self.0.is_finished()
+	}
+}
+
+/// Sends the message to the actor at the given time, if the actor is still running then
+pub(crate) fn send_at<R, S>(dest: &Arc<S>, at: Instant, envelope: ReturnEnvelope<R>) -> TimerHandle
+where
+	R: Role + ?Sized + 'static,
+	S: Performs<R> + ?Sized + 'static,
+{
+	let target = dest.lifecycle().id();
+	let dest = Arc::downgrade(dest);
+	let task = tokio::spawn(async move {
+		sleep_until(at).await;
+		match dest.upgrade() {
+			Some(dest) => {
+				deliver_or_bury(dest, envelope).await;
+			}
+			None => bury_for_gone(target, envelope),
+		}
+	});
+	TimerHandle(task.abort_handle())
+}
+
+/// Sends a copy of the payload to the actor once every `period`, starting one `period` from now, until the actor stops
+pub(crate) fn send_every<R, S, T>(dest: &Arc<S>, period: Duration, payload: T) -> TimerHandle
+where
+	R: Accepts<T> + ?Sized + 'static,
+	S: Performs<R> + ?Sized + 'static,
+	T: Clone + Send + 'static,
+{
+	let target = dest.lifecycle().id();
+	let dest = Arc::downgrade(dest);
+	let task = tokio::spawn(async move {
+		let mut ticks = interval_at(Instant::now() + period, period);
+		// A slow actor should not be sent a burst of messages to catch up
+		ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+		loop {
+			ticks.tick().await;
+			let envelope = ReturnEnvelope {
+				payload:     R::into_payload(payload.clone()),
+				return_path: ReturnPath::Discard,
+				priority:    0,
+			};
+			let Some(dest) = dest.upgrade() else {
+				bury_for_gone(target, envelope);
+				break;
+			};
+			if !deliver_or_bury(dest, envelope).await {
+				break;
+			}
+		}
+	});
+	TimerHandle(task.abort_handle())
+}
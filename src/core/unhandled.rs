@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use super::returnval::{NotHandled, deliver_or_bury};
+use super::{Bury, Monitorable, Performs, ReturnEnvelope, Role, State, TrySendError};
+
+/// The messages an actor had received but not handled when it stopped, which can be given to its `stop` or `catch` function.
+///
+/// A `stop` or `catch` function that takes an extra `Unhandled<Self>` argument is given everything that was still waiting in the actor's queues, or in its stash, when it stopped. Without that argument, these messages are dropped and their callers see [`EnvelopeErr::Hangup`](crate::EnvelopeErr::Hangup), while those that nothing is waiting on are given to the actor's [`DeadLetter`](crate::DeadLetter) sink.
+///
+/// The calls made through each Role are taken out with [`Unhandled::take`], in the order they would have been handled. Any that are left when this value drops are dropped or given to the sink as above.
+pub struct Unhandled<A: State + ?Sized> {
+	messages: Vec<A::Payload>,
+	// Whose dead-letter sink is given what is left, which is only missing for a value made with `default()`
+	actor:    Option<Arc<A::ShellType>>,
+}
+
+impl<A: State + ?Sized> Unhandled<A> {
+	#[doc(hidden)]
+	#[must_use]
+	pub fn new(actor: Arc<A::ShellType>) -> Unhandled<A> {
+		Unhandled {
+			messages: vec![],
+			actor:    Some(actor),
+		}
+	}
+
+	#[doc(hidden)]
+	pub fn push(&mut self, message: A::Payload) {
+		self.messages.push(message);
+	}
+
+	/// Takes out every call that was made through the Role `R`
+	///
+	/// Calls that were going to be handled together as a batch are given back as separate calls.
+	pub fn take<R>(&mut self) -> Vec<PendingCall<R>>
+	where
+		R: Role + ?Sized,
+		A::Payload: PayloadFor<R>,
+	{
+		let mut taken = vec![];
+		let mut kept = vec![];
+		for message in self.messages.drain(..) {
+			match message.into_messages() {
+				Ok(calls) => taken.extend(calls.into_iter().map(PendingCall)),
+				Err(message) => kept.push(message),
+			}
+		}
+		self.messages = kept;
+		taken
+	}
+
+	/// The number of messages left, where a batch counts as one message
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.messages.len()
+	}
+
+	/// Whether there are no messages left
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.messages.is_empty()
+	}
+}
+
+impl<A: State + ?Sized> Default for Unhandled<A> {
+	fn default() -> Self {
+		Unhandled {
+			messages: vec![],
+			actor:    None,
+		}
+	}
+}
+
+impl<A: State + ?Sized> Drop for Unhandled<A> {
+	fn drop(&mut self) {
+		if let Some(actor) = &self.actor {
+			for message in self.messages.drain(..) {
+				message.bury(actor.lifecycle());
+			}
+		}
+	}
+}
+
+impl<A: State + ?Sized> Extend<A::Payload> for Unhandled<A> {
+	fn extend<T: IntoIterator<Item = A::Payload>>(&mut self, iter: T) {
+		self.messages.extend(iter);
+	}
+}
+
+impl<A: State + ?Sized> std::fmt::Debug for Unhandled<A> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Unhandled")
+			.field("len", &self.messages.len())
+			.finish_non_exhaustive()
+	}
+}
+
+/// An actor's message type, which can hold calls made through the Role `R`
+///
+/// (This trait's implementations are normally automatically generated)
+pub trait PayloadFor<R: Role + ?Sized>: Sized {
+	#[doc(hidden)]
+	/// Gives back the calls in this message if it was sent through `R`, or the message unchanged otherwise
+	fn into_messages(self) -> Result<Vec<ReturnEnvelope<R>>, Self>;
+}
+
+/// A call made through the Role `R` that an actor received, but stopped before handling. See [`Unhandled`].
+///
+/// Dropping this value drops the call, so that its caller sees [`EnvelopeErr::Hangup`](crate::EnvelopeErr::Hangup).
+pub struct PendingCall<R: Role + ?Sized>(ReturnEnvelope<R>);
+
+impl<R: Role + ?Sized> PendingCall<R> {
+	/// The name of the Role method that was called
+	#[must_use]
+	pub fn method(&self) -> &'static str {
+		R::method_name(&self.0.payload)
+	}
+
+	/// Tells the caller that the call was turned down, so that it sees [`EnvelopeErr::Rejected`](crate::EnvelopeErr::Rejected)
+	pub fn reject(self) {
+		self.0.return_path.refuse(NotHandled::Rejected);
+	}
+
+	/// Sends the call on to a different actor performing the same Role, which replies to the original caller
+	///
+	/// # Errors
+	///
+	/// Gives back the call if `dest` has also stopped.
+	pub fn forward_to<S>(self, dest: &Arc<S>) -> Result<(), PendingCall<R>>
+	where
+		R: 'static,
+		S: Performs<R> + ?Sized + 'static,
+	{
+		match dest.try_enqueue(self.0) {
+			Ok(()) => Ok(()),
+			Err(TrySendError::Full(envelope)) => {
+				tokio::spawn(deliver_or_bury(Arc::clone(dest), envelope));
+				Ok(())
+			}
+			Err(TrySendError::Closed(envelope)) => Err(PendingCall(envelope)),
+		}
+	}
+}
+
+impl<R: Role + ?Sized> std::fmt::Debug for PendingCall<R> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("PendingCall").field(&self.method()).finish()
+	}
+}
@@ -26,8 +26,12 @@
 //!
 //! Actors can also receive general [`Future`] and [`Stream`][`futures::Stream`] values as messages, using [`Message::send_when_ready`] and [`MessageStream::feed_to`]. These functions work on roles that implement the [`Accepts`] trait, which is implemented for roles which have exactly one method that takes a given tuple of parameters. (And is implemented separately for each such tuple.)
 //!
+//! An actor that only handles one kind of message can be made from a single function with [`spawn_fn`], or `#[actor]` on a function, rather than declaring a module, Role and performance. Its shell, an [`FnActor`], is its own Role, so can be given straight to [`Message::send_when_ready`] and similar.
+//!
 //! **Note**: The API is designed to allow code to work with dynamically typed actors of a given role by using values of type `Arc<dyn Role>`, which `Arc<A>` can be upcast to by normal language rules. This construction does mean that the compiler may need help to correctly disambiguate [`Message::send_when_ready`] (and similar) calls.
 //!
+//! When the concrete shell type is known, its role methods can also be called directly on an `Arc` of the shell, with the same name followed by `_direct`, e.g. `counter.add_direct(1)`. These inherent methods produce an `Envelope<dyn Role, T, Shell>`, which sends straight to the shell's queue without going through the role's vtable, while calling `counter.add(1)` still goes through the role and gives an `Envelope<dyn Role, T>`. They are generated for every role method unless the name they would have is already used by another method of the actor's roles, or would be shared by two of them - those still have to be called through the role traits.
+//!
 //!
 //! ## Defining an actor
 //!
@@ -51,9 +55,9 @@
 //! }
 //! ```
 //!
-//! The module can be named anything. It needs to contain exactly one `struct`, `enum` *or* `union`, which serves as the state type of the actor. This type can similarly have any name that does not collide with the module name, but must be `'static`, `Sized`, and cannot contain free generic parameters (`enum MyState<T>` would not be allowed) but there are no other restrictions on its content. It does not move after the actor is started, so storing large amounts of data inline is not a performance concern.
+//! The module can be named anything. It needs to contain exactly one `struct`, `enum` *or* `union`, which serves as the state type of the actor. This type can similarly have any name that does not collide with the module name, but must be `'static` and `Sized`, but there are no other restrictions on its content. It can have type and const generic parameters (`struct MyState<T: Clone + Send + 'static, const N: usize>`), which the actor shell is then given as well, so that one actor declaration covers `MyActor<u64, 4>` and `MyActor<String, 8>` alike. It does not move after the actor is started, so storing large amounts of data inline is not a performance concern.
 //!
-//! While inherent `impl MyState` blocks are allowed within the module, there is currently no support for calling those methods on the actor state from outside the actor, as the state value is not externally accessible - all externally callable methods must be defined on a performance, discussed below. (See also `canonical` performances later for a simplification of the common case.) Methods from such a block *can* be called normally on the state value from within a performance or on state values *before* they are consumed by `start` - there is nothing unusual about the state type itself, the relevant values are simply only accessible at the point they are passed as parameters to a performance. Free functions (i.e. with no `self`) defined inside inherent `impl` blocks can also be called as normal.
+//! While inherent `impl MyState` blocks are allowed within the module, those methods cannot be called on the actor state from outside the actor, as the state value is not externally accessible - all externally callable methods must be defined on a performance, discussed below, or be marked `#[message]`, which turns them into messages that can be sent through the actor's shell without declaring a Role. Methods marked `#[handler]` similarly handle a message type implementing [`Request`], which can be sent through [`Handles`], so that the set of messages an actor handles can grow without a Role listing them all. (Another exception is an actor declared with `#[actor(inspect)]`, whose shell has a `with_state` method that runs a closure on the state in the actor's task, which is meant for tests and debugging.) (See also `canonical` performances later for a simplification of the common case.) Methods from such a block *can* be called normally on the state value from within a performance or on state values *before* they are consumed by `start` - there is nothing unusual about the state type itself, the relevant values are simply only accessible at the point they are passed as parameters to a performance. Free functions (i.e. with no `self`) defined inside inherent `impl` blocks can also be called as normal.
 //!
 //! ### Performances
 //!
@@ -125,6 +129,8 @@
 //!
 //! Both of these functions can have any `'static + Sized` return type, and any return values from these functions will be passed back to the [`ExitHandle`].
 //!
+//! Either function can also take a final `Unhandled<Self>` argument, e.g. `stop(self, pending: Unhandled<Self>)`, to be given the calls that were still waiting to be handled when the actor stopped. Each [`PendingCall`] taken out of the [`Unhandled`] can be turned down with a meaningful error, forwarded to a successor actor that answers the original caller, or kept to be persisted. Without this argument, the waiting calls are dropped and their callers see [`EnvelopeErr::Hangup`], while those that nothing is waiting on become dead letters.
+//!
 //! **N.B.**: The `catch` function is not technically running in an unwinding context, so a secondary panic will not abort the process. However, Shakespeare leaves behaviour in the event of panic within `catch` unspecified except that safety is upheld, and **the exact behaviour may change even in patch releases**.
 //!
 //!
@@ -136,20 +142,28 @@
 //!
 //! ### Synchronisation
 //!
-//! The order the actor responds to calls from different tasks is unspecified. The order the actor responds to calls made via two different roles is unspecified *even from the same task or from the same handle.* Calls made by an actor's own performances count as being made on the same task as each other.
+//! The order the actor responds to calls from different tasks is unspecified. The order the actor responds to calls made via two different roles is unspecified *even from the same task or from the same handle*, unless the actor was declared with `#[actor(single_mailbox)]`. Calls made by an actor's own performances count as being made on the same task as each other.
 //!
 //! A call will *happen-before* another call if all of the following hold:
-//!   1) both calls are made via methods (not necessarily the same method) defined by the same role
+//!   1) both calls are made via methods (not necessarily the same method) defined by the same role, or the actor was declared with `single_mailbox`, in which case the calls can be made via any of its roles
 //!   2) both calls are made from the same task
-//!   3) the [`Envelope`] returned from the first call is `await`ed before the second call is made. If you do not need the return value of the first call, you must use [`Envelope::ignore_return`] to await only the sending, as implicitly dropping the `Envelope` by letting it leave scope does *not* meet this condition. (Dropped envelopes have no particular ordering with respect to anything, including other such envelopes.)
+//!   3) the [`Envelope`] returned from the first call is `await`ed, or dropped, before the second call is made. If you do not need the return value of the first call, you can use [`Envelope::ignore_return`] to await only the sending, or drop the `Envelope` by letting it leave scope, which sends the message immediately. (This relies on there being room in the queue at the point of the drop, which is always the case for the current unbounded queues.)
+//!   4) the second call was not given a higher priority than the first with [`Envelope::with_priority`]
 //!
 //! ### Shutting down
 //!
 //! The actor can stop processing messages and shut down in several circumstances:
 //!
 //! 1. If a message handler panics, `catch` is called (or the panic value passed straight up to the [`ExitHandle`] if there is no `catch`) immediately. No further messages are processed, and attempting to send messages to the actor will fail by returning `Err` to the caller via the [`Envelope`].
-//! 2. If the [`Context::stop`] is called, no further messages are processed, calls against the actor will return `Err`, but the actor's `stop` function is called rather than `catch`. This similarly passes the returned value up to the [`ExitHandle`].
-//! 3. If the `Arc` that was returned from `start` and all of its copies drop, *and* no further messages are waiting to be processed, `stop` will be called as in case 2. By definition, it is not possible for an external client to be sending messages to the actor at this point. (Note that functions directly subscribing the actor to a future result, such as [`MessageStream::feed_to`] implicitly hold an `Arc` and will preclude this case until that value yields to exhaustion.) As explained below, there is currently an indeterminate delay between this condition becoming true and `stop` actually being called - to be sure that the actor has completely stopped, `await` the [`ExitHandle`].
+//! 2. If the [`Context::stop`] is called, no further messages are processed once the current handler finishes, calls against the actor will return `Err`, but the actor's `stop` function is called rather than `catch`. This similarly passes the returned value up to the [`ExitHandle`].
+//! 3. If the `Arc` that was returned from `start` and all of its copies drop, *and* no further messages are waiting to be processed, `stop` will be called as in case 2. By definition, it is not possible for an external client to be sending messages to the actor at this point. (Note that functions directly subscribing the actor to a future result, such as [`MessageStream::feed_to`] implicitly hold an `Arc` and will preclude this case until that value yields to exhaustion. Timers from [`Context::send_after`] and similar only hold a weak reference, so do not.) As explained below, there is currently an indeterminate delay between this condition becoming true and `stop` actually being called - to be sure that the actor has completely stopped, `await` the [`ExitHandle`].
+//! 4. If the actor was started with [`Context::spawn_child`] and its parent stops, it is stopped as in case 2, unless it is still running a handler a second later, in which case that handler is abandoned and `stop` is called straight away. The parent's own `stop` or `catch` is only called once all of its children have stopped.
+//!
+//! Only one owner can await an actor's [`ExitHandle`], but any number of other actors can be told when it stops, with a summary of how, by monitoring it with [`Context::monitor`].
+//!
+//! An actor can also be replaced by a successor, possibly of a different type, without invalidating any of its handles, by calling [`Context::hand_off`]. It stops as in case 2, but passes its waiting messages, and any sent to it afterwards, on to the successor.
+//!
+//! Messages sent to an actor after it has stopped with nothing waiting on the result, such as from a dropped [`Envelope`], can be collected by setting a [`DeadLetter`] sink, as can those still waiting to be handled when it stops.
 //!
 //! **N.B:** Because method implementations can get hold of the actor's own handle via the [`Context`], then even if all other copies have dropped at any given time, a running event handler can "save" the actor by sending a new copy of the handle out of the actor. This is not treated as the actor being revived from having shut down, but instead it has not shut down in the first place.
 //!
@@ -174,25 +188,31 @@ use std::any::Any;
 use std::future::Future;
 
 #[doc(hidden)]
-pub use ::async_trait as async_trait_export;
+pub use ::futures as futures_export;
 #[doc(hidden)]
 pub use ::tokio as tokio_export;
 pub use shakespeare_macro::{actor, performance, role};
 #[doc(hidden)]
-pub use tokio::TokioUnbounded;
+pub use tokio::{PriorityReceiver, PriorityUnbounded, TokioUnbounded};
 
 mod core;
 mod sendable;
 mod tokio;
 
 pub use core::{
-	Accepts, ActorHandles, Context, Emits, Envelope, EnvelopeErr, ExitHandle,
-	Outcome as ActorOutcome, Role, Shell as ActorShell, State as ActorState,
+	Accepts, ActorHandles, ActorId, Context, DeadLetter, DeadLetterReason, DeadLetterSinkGuard,
+	Emits, Envelope, EnvelopeErr, ExitHandle, FnActor, Handles, Monitorable,
+	Outcome as ActorOutcome, PendingCall, Performance, Performs, Request, Role,
+	Shell as ActorShell, StashContext, State as ActorState, StateQuery, StateQueryErr, Termination,
+	TimerHandle, Unhandled, clear_dead_letter_sink, scoped_dead_letter_sink, set_dead_letter_sink,
+	spawn_fn,
 };
 #[doc(hidden)]
 pub use core::{
-	Channel, Receiver as RoleReceiver, ReturnCaster, ReturnEnvelope, ReturnPath,
-	Sender as RoleSender,
+	Bury, Channel, FnState, ForwardTo, IDLE_TIMEOUT, IdleTimer, Inspection, Lifecycle, PayloadFor,
+	Prioritised, Receiver as RoleReceiver, ReturnCaster, ReturnEnvelope, ReturnPath,
+	Sender as RoleSender, StopSignal, TrySendError, Wait, close_and_drain, discard_superseded,
+	forward, run_until_killed, split_batch, wait_for_mail,
 };
 
 pub use sendable::{Message, MessageStream};
@@ -209,7 +229,7 @@ type Role2Sender<R> = <<R as Role>::Channel as Channel>::Sender;
 pub type Role2SendError<R> = <Role2Sender<R> as RoleSender<ReturnEnvelope<R>>>::Error;
 
 #[doc(hidden)]
-/// Create a new future that will wrap the given future and catch any panic.
+/// Used by [`run_until_killed`], which runs the event loops of generated and function actors alike
 /// Used by [`::shakespeare_macro::actor::output::SpawningFunction`]
 /// Included here to avoid clients having to depend on `futures` crate
 pub fn catch_future<T>(fut: T) -> impl Future<Output = Result<T::Output, Box<dyn Any + Send>>>
@@ -3,6 +3,7 @@ use std::sync::Arc;
 
 use futures::{Stream, StreamExt, pin_mut};
 
+use crate::core::deliver_or_bury;
 use crate::{Accepts, ReturnEnvelope, ReturnPath};
 
 /// Extension utilities for [`Future<T>`]. Blanket implemented for all values that meet the requirements.
@@ -15,7 +16,7 @@ pub trait Message: Future + Send + 'static {
 	/// See also [`MessageStream::feed_to`] if you have a [`Stream`] of items to deliver rather than a single value.
 	///
 	/// **N.B**: the created task retains the `Arc<dyn Role>` for as long as the future is pending, and will keep the actor alive for that time
-	/// if it does not panic or explicitly stops.
+	/// if it does not panic or explicitly stops. If the actor has stopped by the time the future resolves, the output goes to the [`DeadLetter`](crate::DeadLetter) sink.
 	fn send_when_ready<R>(self, actor: Arc<R>)
 	where
 		Self: Sized,
@@ -26,9 +27,10 @@ pub trait Message: Future + Send + 'static {
 			let envelope = ReturnEnvelope {
 				payload,
 				return_path: ReturnPath::Discard,
+				priority: 0,
 			};
 
-			let _ = actor.enqueue(envelope).await;
+			deliver_or_bury(actor, envelope).await;
 		});
 	}
 }
@@ -41,7 +43,7 @@ pub trait MessageStream: Stream<Item: Send> + Send + 'static {
 	///
 	/// See the [`Accepts`] documentation for the conditions that allow an actor to use this function.
 	///
-	/// This function does not do anything to inform the actor when the stream closes, successfully or otherwise. If sending the stream item to the actor fails, the stream will be dropped. If an actor explicitly shuts down with an active stream, the stream will be dropped with any remaining items unread. A sent stream prevents an actor shutting down from zero remaining handles until the stream runs out, and conversely, the stream running out will release the held handle.
+	/// This function does not do anything to inform the actor when the stream closes, successfully or otherwise. If sending the stream item to the actor fails, the item goes to the [`DeadLetter`](crate::DeadLetter) sink and the stream will be dropped. If an actor explicitly shuts down with an active stream, the stream will be dropped with any remaining items unread. A sent stream prevents an actor shutting down from zero remaining handles until the stream runs out, and conversely, the stream running out will release the held handle.
 	fn feed_to<R>(self, actor: Arc<R>)
 	where
 		Self: Sized,
@@ -55,8 +57,9 @@ pub trait MessageStream: Stream<Item: Send> + Send + 'static {
 				let envelope = ReturnEnvelope {
 					payload,
 					return_path: ReturnPath::Discard,
+					priority: 0,
 				};
-				if actor.enqueue(envelope).await.is_err() {
+				if !deliver_or_bury(Arc::clone(&actor), envelope).await {
 					break;
 				}
 			}
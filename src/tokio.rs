@@ -1,7 +1,10 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
 
-use super::{RoleReceiver, RoleSender};
+use super::{Prioritised, RoleReceiver, RoleSender, TrySendError};
 
 impl<T: Send> RoleSender<T> for UnboundedSender<T> {
 	type Error = SendError<T>;
@@ -9,6 +12,16 @@ impl<T: Send> RoleSender<T> for UnboundedSender<T> {
 	async fn send(&self, msg: T) -> Result<(), SendError<T>> {
 		self.send(msg)
 	}
+
+	fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+		// An unbounded channel is never full
+		self.send(msg)
+			.map_err(|SendError(msg)| TrySendError::Closed(msg))
+	}
+
+	fn into_unsent(SendError(msg): SendError<T>) -> T {
+		msg
+	}
 }
 
 impl<T: Send> RoleReceiver<T> for UnboundedReceiver<T> {
@@ -16,9 +29,21 @@ impl<T: Send> RoleReceiver<T> for UnboundedReceiver<T> {
 		self.recv().await
 	}
 
+	async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize {
+		self.recv_many(buffer, limit).await
+	}
+
 	fn is_empty(&self) -> bool {
 		self.is_empty()
 	}
+
+	fn close(&mut self) {
+		self.close();
+	}
+
+	fn try_recv(&mut self) -> Option<T> {
+		self.try_recv().ok()
+	}
 }
 
 #[doc(hidden)]
@@ -35,3 +60,187 @@ impl<T: Send> super::Channel for TokioUnbounded<T> {
 		unbounded_channel()
 	}
 }
+
+/// The receiving end of a queue, which hands out the waiting message with the highest priority first
+///
+/// Messages of equal priority are handled in the order they arrived. Messages at the default priority are kept in a plain queue, so a role that is never sent anything else doesn't pay for ordering them.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct PriorityReceiver<T> {
+	inner:      UnboundedReceiver<T>,
+	arrived:    VecDeque<T>,
+	overtaking: BinaryHeap<Queued<T>>,
+	next_seq:   u64,
+}
+
+impl<T: Prioritised> PriorityReceiver<T> {
+	fn push(&mut self, msg: T) {
+		if msg.priority() == 0 {
+			self.arrived.push_back(msg);
+			return;
+		}
+		let seq = self.next_seq;
+		self.next_seq += 1;
+		self.overtaking.push(Queued { seq, msg });
+	}
+
+	/// Moves everything that has arrived so far out of the channel, so that it can be ordered
+	fn collect_arrived(&mut self) {
+		while let Ok(msg) = self.inner.try_recv() {
+			self.push(msg);
+		}
+	}
+
+	/// Waits for a message to arrive if there are none waiting, returning false if the channel has closed
+	async fn wait_for_message(&mut self) -> bool {
+		self.collect_arrived();
+		if self.is_waiting_empty() {
+			let Some(msg) = self.inner.recv().await else {
+				return false;
+			};
+			self.push(msg);
+			self.collect_arrived();
+		}
+		true
+	}
+
+	fn is_waiting_empty(&self) -> bool {
+		self.arrived.is_empty() && self.overtaking.is_empty()
+	}
+
+	/// Whether the next message comes from the heap rather than the plain queue, which is when it has a higher priority than the default, or nothing at the default priority is waiting
+	fn overtakes(&self) -> bool {
+		self.overtaking
+			.peek()
+			.is_some_and(|queued| queued.msg.priority() > 0 || self.arrived.is_empty())
+	}
+
+	fn pop(&mut self) -> Option<T> {
+		if self.overtakes() {
+			self.overtaking.pop().map(|queued| queued.msg)
+		} else {
+			self.arrived.pop_front()
+		}
+	}
+
+	/// Takes up to `limit` of the messages that have already arrived, stopping at the first one that `unwrap` gives back, which stays in the queue
+	pub fn recv_run<U>(
+		&mut self,
+		buffer: &mut Vec<U>,
+		limit: usize,
+		mut unwrap: impl FnMut(T) -> Result<U, T>,
+	) {
+		self.collect_arrived();
+		for _ in 0..limit {
+			let (seq, msg) = if self.overtakes() {
+				let Some(Queued { seq, msg }) = self.overtaking.pop() else {
+					return;
+				};
+				(Some(seq), msg)
+			} else {
+				let Some(msg) = self.arrived.pop_front() else {
+					return;
+				};
+				(None, msg)
+			};
+			match unwrap(msg) {
+				Ok(item) => buffer.push(item),
+				Err(msg) => {
+					match seq {
+						Some(seq) => self.overtaking.push(Queued { seq, msg }),
+						None => self.arrived.push_front(msg),
+					}
+					return;
+				}
+			}
+		}
+	}
+}
+
+impl<T: Prioritised + Send> RoleReceiver<T> for PriorityReceiver<T> {
+	async fn recv(&mut self) -> Option<T> {
+		if !self.wait_for_message().await {
+			return None;
+		}
+		self.pop()
+	}
+
+	async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize {
+		if limit == 0 || !self.wait_for_message().await {
+			return 0;
+		}
+		let before = buffer.len();
+		buffer.extend(std::iter::from_fn(|| self.pop()).take(limit));
+		buffer.len() - before
+	}
+
+	fn is_empty(&self) -> bool {
+		self.is_waiting_empty() && self.inner.is_empty()
+	}
+
+	fn close(&mut self) {
+		self.inner.close();
+	}
+
+	fn try_recv(&mut self) -> Option<T> {
+		self.collect_arrived();
+		self.pop()
+	}
+}
+
+struct Queued<T> {
+	seq: u64,
+	msg: T,
+}
+
+impl<T> std::fmt::Debug for Queued<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "<Queued {}>", self.seq)
+	}
+}
+
+impl<T: Prioritised> Ord for Queued<T> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		// The heap gives the greatest first, so the earlier of two messages with the same priority is the greater
+		self.msg
+			.priority()
+			.cmp(&other.msg.priority())
+			.then_with(|| other.seq.cmp(&self.seq))
+	}
+}
+
+impl<T: Prioritised> PartialOrd for Queued<T> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<T> PartialEq for Queued<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.seq == other.seq
+	}
+}
+
+impl<T> Eq for Queued<T> {}
+
+#[doc(hidden)]
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Default)]
+pub struct PriorityUnbounded<T>(std::marker::PhantomData<T>);
+impl<T: Prioritised + Send> super::Channel for PriorityUnbounded<T> {
+	type Input = ();
+	type Item = T;
+	type Receiver = PriorityReceiver<T>;
+	type Sender = UnboundedSender<T>;
+
+	fn new((): ()) -> (UnboundedSender<T>, PriorityReceiver<T>) {
+		let (sender, inner) = unbounded_channel();
+		let receiver = PriorityReceiver {
+			inner,
+			arrived: VecDeque::new(),
+			overtaking: BinaryHeap::new(),
+			next_seq: 0,
+		};
+		(sender, receiver)
+	}
+}
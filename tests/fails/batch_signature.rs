@@ -0,0 +1,16 @@
+use shakespeare::actor;
+
+#[actor]
+mod actor {
+	struct A {}
+
+	#[shakespeare::performance(canonical)]
+	impl Writer for A {
+		#[batch(limit = 8)]
+		fn write(&mut self, row: u32) -> usize {
+			0
+		}
+	}
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+use shakespeare::actor;
+
+#[actor]
+mod Holder {
+	struct HolderState<T: Clone + Send + 'static> {
+		value: T,
+	}
+
+	#[performance(canonical)]
+	impl<T: Clone + Send + 'static> Hold for HolderState<T> {
+		fn peek(&self) -> bool {
+			self.value.clone();
+			true
+		}
+	}
+}
+
+struct NotClone;
+
+fn main() {
+	let _ = Holder::start(HolderState { value: NotClone });
+}
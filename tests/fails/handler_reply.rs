@@ -0,0 +1,21 @@
+use shakespeare::{Request, actor};
+
+struct Ping;
+
+impl Request for Ping {
+	type Reply = u32;
+}
+
+#[actor]
+mod Pinger {
+	struct PingerState {}
+
+	impl PingerState {
+		#[handler]
+		fn ping(&self, _: Ping) -> String {
+			String::new()
+		}
+	}
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use shakespeare::actor;
+
+struct B {}
+
+#[actor]
+mod actor {
+	struct A {}
+
+	impl super::B {
+		#[message]
+		fn ping(&mut self) {}
+	}
+}
+
+fn main() {}
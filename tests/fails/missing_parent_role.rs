@@ -0,0 +1,25 @@
+use shakespeare::{actor, role};
+
+#[role]
+trait Reader {
+	fn read(&self) -> u32;
+}
+
+#[role]
+trait Admin: Reader {
+	fn write(&self, value: u32);
+}
+
+#[actor]
+mod Db {
+	struct DbState(u32);
+
+	#[performance]
+	impl Admin for DbState {
+		fn write(&mut self, value: u32) {
+			self.0 = value;
+		}
+	}
+}
+
+fn main() {}
@@ -0,0 +1,24 @@
+use shakespeare::{actor, performance, role};
+
+trait HasHealth {
+	fn healthy(&self) -> bool;
+}
+
+#[role]
+trait Health {
+	fn check(&self) -> bool;
+}
+
+#[performance]
+impl<S: HasHealth> Health for S {
+	fn check(&self) -> bool {
+		self.healthy()
+	}
+}
+
+#[actor(performs(Health))]
+mod Worker {
+	struct WorkerState;
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[shakespeare::role(canonical)]
+trait Counter {
+	fn add(&mut self, amount: u32) -> u32;
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use shakespeare::role;
+
+#[role]
+trait Reader {
+	fn read(&self) -> u32;
+}
+
+#[role]
+trait Admin: Reader + ?Sized {
+	fn write(&self, value: u32);
+}
+
+fn main() {}
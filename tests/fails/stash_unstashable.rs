@@ -0,0 +1,19 @@
+use shakespeare::{StashContext, actor};
+
+#[actor]
+mod Connection {
+	struct ConnectionState {
+		connected: bool,
+	}
+
+	#[performance(canonical)]
+	impl Data for ConnectionState {
+		fn send(&mut self, ctx: &mut StashContext<'_, Self, usize>, val: usize) {
+			if !self.connected {
+				ctx.stash(val);
+			}
+		}
+	}
+}
+
+fn main() {}
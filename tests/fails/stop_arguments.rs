@@ -0,0 +1,17 @@
+use shakespeare::actor;
+
+#[actor]
+mod Stopper {
+	struct StopperState {}
+
+	#[performance(canonical)]
+	impl Stopping for StopperState {
+		fn halt(&mut self) {}
+	}
+
+	fn stop(self, reason: String) -> String {
+		reason
+	}
+}
+
+fn main() {}
@@ -4,8 +4,18 @@ fn ux() {
 		let t = trybuild::TestCases::new();
 		//t.pass("tests/successes/basic.rs");
 		//t.pass("tests/successes/modules.rs");
+		t.compile_fail("tests/fails/batch_signature.rs");
 		t.compile_fail("tests/fails/empty.rs");
+		t.compile_fail("tests/fails/generic_bounds.rs");
+		t.compile_fail("tests/fails/handler_reply.rs");
 		t.compile_fail("tests/fails/missing_data.rs");
+		t.compile_fail("tests/fails/message_state.rs");
+		t.compile_fail("tests/fails/missing_parent_role.rs");
 		t.compile_fail("tests/fails/multiple_data.rs");
+		t.compile_fail("tests/fails/performs_bounds.rs");
+		t.compile_fail("tests/fails/role_arguments.rs");
+		t.compile_fail("tests/fails/role_supertraits.rs");
+		t.compile_fail("tests/fails/stash_unstashable.rs");
+		t.compile_fail("tests/fails/stop_arguments.rs");
 	}
 }
@@ -1,10 +1,10 @@
 use anyhow::Error;
-use proc_macro2::TokenStream;
+use proc_macro2::{TokenStream, TokenTree};
 use quote::{ToTokens, quote};
 use syn::visit_mut::VisitMut;
 use syn::{AttrStyle, Attribute, Item, ItemMod, Meta, MetaList, parse_file};
 
-use crate::stripped_macro::{make_actor, make_performance, make_role};
+use crate::stripped_macro::{make_actor, make_actor_fn, make_performance, make_role};
 
 fn find_attribute(attrs: &mut Vec<Attribute>, needle: &str) -> Option<Attribute> {
 	let mut index = None;
@@ -35,8 +35,12 @@ impl VisitMut for Walker {
 	fn visit_item_mod_mut(&mut self, i: &mut syn::ItemMod) {
 		let attrs = &mut i.attrs;
 		let present = find_attribute(attrs, "actor");
-		if present.is_some() {
-			let tokens = match make_actor(i.clone()) {
+		if let Some(attr) = present {
+			let args = match attr.meta {
+				Meta::List(MetaList { tokens, .. }) => tokens,
+				_ => TokenStream::new(),
+			};
+			let tokens = match make_actor(args, i.clone()) {
 				Ok(actor_ouput) => actor_ouput.to_token_stream(),
 				Err(e) => e.into_compile_error(),
 			};
@@ -88,8 +92,36 @@ impl VisitMut for Walker {
 	fn visit_item_impl_mut(&mut self, i: &mut syn::ItemImpl) {
 		let attrs = &mut i.attrs;
 		let present = find_attribute(attrs, "performance");
-		if present.is_some() {
-			let tokens = match make_performance(i.clone()) {
+		if let Some(attr) = present {
+			let canonical = match attr.meta {
+				Meta::List(MetaList { tokens, .. }) => tokens
+					.into_iter()
+					.any(|t| matches!(t, TokenTree::Ident(ident) if ident == "canonical")),
+				_ => false,
+			};
+			let tokens = match make_performance(i.clone(), canonical) {
+				Ok((perf, role)) => {
+					let mut tokens = perf.to_token_stream();
+					role.to_tokens(&mut tokens);
+					tokens
+				}
+				Err(e) => e.into_compile_error(),
+			};
+			self.0.extend(tokens);
+		} else {
+			self.0.extend(i.into_token_stream());
+		}
+	}
+
+	fn visit_item_fn_mut(&mut self, i: &mut syn::ItemFn) {
+		let attrs = &mut i.attrs;
+		let present = find_attribute(attrs, "actor");
+		if let Some(attr) = present {
+			let args = match attr.meta {
+				Meta::List(MetaList { tokens, .. }) => tokens,
+				_ => TokenStream::new(),
+			};
+			let tokens = match make_actor_fn(args, i.clone()) {
 				Ok(actor_ouput) => actor_ouput.to_token_stream(),
 				Err(e) => e.into_compile_error(),
 			};
@@ -102,6 +134,7 @@ impl VisitMut for Walker {
 	fn visit_item_mut(&mut self, i: &mut Item) {
 		//dbg!(&i);
 		match i {
+			syn::Item::Fn(i) => self.visit_item_fn_mut(i),
 			syn::Item::Impl(i) => self.visit_item_impl_mut(i),
 			syn::Item::Mod(i) => self.visit_item_mod_mut(i),
 			syn::Item::Trait(i) => self.visit_item_trait_mut(i),
@@ -11,6 +11,8 @@ use duct::cmd;
 use fs_extra::dir::{create_all, get_dir_content};
 use fs_extra::file::remove;
 #[cfg(test)]
+use futures as _;
+#[cfg(test)]
 use shakespeare as _;
 #[cfg(test)]
 use tokio as _;